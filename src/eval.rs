@@ -0,0 +1,9 @@
+mod builtins;
+mod environment;
+mod evaluator;
+mod object;
+
+pub use self::{
+    evaluator::Evaluator,
+    object::{Builtin, ErrorKind, Function, Object, RuntimeError},
+};
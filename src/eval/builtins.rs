@@ -0,0 +1,231 @@
+use super::evaluator::{Eval, Evaluator};
+use super::object::{Builtin, BuiltinFunction, ErrorKind, Object};
+use std::convert::TryFrom;
+use std::io::Write;
+
+const BUILTINS: &[(&str, BuiltinFunction)] = &[
+    ("len", len),
+    ("first", first),
+    ("last", last),
+    ("rest", rest),
+    ("push", push),
+    ("puts", puts),
+    ("ok", ok),
+    ("err", err),
+    ("bytes", bytes),
+    ("string", string),
+    ("char", char),
+    ("int", int),
+];
+
+/// The builtin `resolver::BUILTINS` lists as `name`.
+pub(super) fn get(name: &str) -> Option<Builtin> {
+    BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(name, function)| Builtin {
+            name,
+            function: *function,
+        })
+}
+
+/// The builtins `value.name(...)` calls for each type, with `value` as the
+/// first argument, before it looks for a field.
+const METHODS: &[(&str, &[&str])] = &[
+    ("int", &["char", "string"]),
+    ("char", &["int", "string"]),
+    ("string", &["len", "bytes", "char", "int"]),
+    ("bytes", &["len", "push", "string"]),
+    ("array", &["len", "first", "last", "rest", "push", "bytes"]),
+    ("hash", &["len"]),
+];
+
+/// The method `name` of `value`'s type.
+pub(super) fn method(value: &Object, name: &str) -> Option<Builtin> {
+    METHODS
+        .iter()
+        .find(|(type_name, _)| *type_name == value.type_name())
+        .filter(|(_, methods)| methods.contains(&name))
+        .and_then(|_| get(name))
+}
+
+fn arity(evaluator: &Evaluator, name: &str, arguments: &[Object], expected: usize) -> Eval<()> {
+    if arguments.len() == expected {
+        return Ok(());
+    }
+    let message = format!(
+        "wrong number of arguments to {}: expected {}, found {}",
+        name,
+        expected,
+        arguments.len()
+    );
+    Err(evaluator.error(ErrorKind::Arity, message))
+}
+
+fn unsupported(evaluator: &Evaluator, name: &str, argument: &Object) -> Eval<Object> {
+    let message = format!("{} does not accept {}", name, argument.type_name());
+    Err(evaluator.error(ErrorKind::Type, message))
+}
+
+fn len(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "len", &arguments, 1)?;
+    let len = match &arguments[0] {
+        Object::String(value) => value.chars().count(),
+        Object::Bytes(value) => value.len(),
+        Object::Array(elements) => elements.borrow().len(),
+        Object::Hash(fields) => fields.borrow().len(),
+        argument => return unsupported(evaluator, "len", argument),
+    };
+    Ok(Object::Int(len as i64))
+}
+
+fn first(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "first", &arguments, 1)?;
+    match &arguments[0] {
+        Object::Array(elements) => Ok(elements.borrow().first().cloned().unwrap_or(Object::Null)),
+        argument => unsupported(evaluator, "first", argument),
+    }
+}
+
+fn last(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "last", &arguments, 1)?;
+    match &arguments[0] {
+        Object::Array(elements) => Ok(elements.borrow().last().cloned().unwrap_or(Object::Null)),
+        argument => unsupported(evaluator, "last", argument),
+    }
+}
+
+/// Every element but the first, as a new array, or `null` for an empty one.
+fn rest(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "rest", &arguments, 1)?;
+    match &arguments[0] {
+        Object::Array(elements) => Ok(match elements.borrow().split_first() {
+            Some((_, rest)) => Object::from(rest.to_vec()),
+            None => Object::Null,
+        }),
+        argument => unsupported(evaluator, "rest", argument),
+    }
+}
+
+/// A new array or byte string with `value` appended; the original is left
+/// as it was.
+fn push(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "push", &arguments, 2)?;
+    match &arguments[0] {
+        Object::Array(elements) => {
+            let mut elements = elements.borrow().clone();
+            elements.push(arguments[1].clone());
+            Ok(Object::from(elements))
+        }
+        Object::Bytes(value) => {
+            let mut value = value.to_vec();
+            value.push(byte(evaluator, &arguments[1])?);
+            Ok(Object::Bytes(value.into()))
+        }
+        argument => unsupported(evaluator, "push", argument),
+    }
+}
+
+/// Writes each argument on a line of its own.
+fn puts(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    for argument in &arguments {
+        if let Err(e) = writeln!(evaluator.output, "{}", argument) {
+            return Err(evaluator.error(ErrorKind::Io, e.to_string()));
+        }
+    }
+    Ok(Object::Null)
+}
+
+fn ok(evaluator: &mut Evaluator, mut arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "ok", &arguments, 1)?;
+    Ok(Object::from(Ok(arguments.remove(0))))
+}
+
+fn err(evaluator: &mut Evaluator, mut arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "err", &arguments, 1)?;
+    Ok(Object::from(Err(arguments.remove(0))))
+}
+
+fn byte(evaluator: &Evaluator, value: &Object) -> Eval<u8> {
+    match value {
+        Object::Int(value) => u8::try_from(*value).map_err(|_| {
+            let message = format!("{} is not a byte; bytes are 0 to 255", value);
+            evaluator.error(ErrorKind::Type, message)
+        }),
+        value => {
+            let message = format!("expected a byte, found {}", value.type_name());
+            Err(evaluator.error(ErrorKind::Type, message))
+        }
+    }
+}
+
+/// The UTF-8 encoding of a string, or the bytes of an array of ints.
+fn bytes(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "bytes", &arguments, 1)?;
+    match &arguments[0] {
+        Object::Bytes(value) => Ok(Object::Bytes(value.clone())),
+        Object::String(value) => Ok(Object::Bytes(value.as_bytes().into())),
+        Object::Array(elements) => {
+            let value = elements
+                .borrow()
+                .iter()
+                .map(|element| byte(evaluator, element))
+                .collect::<Eval<Vec<u8>>>()?;
+            Ok(Object::Bytes(value.into()))
+        }
+        argument => unsupported(evaluator, "bytes", argument),
+    }
+}
+
+/// Decodes bytes as UTF-8; any other value is written as `puts` would.
+fn string(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "string", &arguments, 1)?;
+    match &arguments[0] {
+        Object::Bytes(value) => match std::str::from_utf8(value) {
+            Ok(value) => Ok(Object::from(value)),
+            Err(e) => Err(evaluator.error(ErrorKind::Type, format!("invalid UTF-8: {}", e))),
+        },
+        argument => Ok(Object::from(argument.to_string().as_str())),
+    }
+}
+
+/// The char with a code point, or the only char of a string.
+fn char(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "char", &arguments, 1)?;
+    match &arguments[0] {
+        Object::Char(value) => Ok(Object::Char(*value)),
+        Object::Int(value) => u32::try_from(*value)
+            .ok()
+            .and_then(std::char::from_u32)
+            .map(Object::Char)
+            .ok_or_else(|| {
+                let message = format!("{} is not a unicode code point", value);
+                evaluator.error(ErrorKind::Type, message)
+            }),
+        Object::String(value) => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(value), None) => Ok(Object::Char(value)),
+                _ => {
+                    let message = format!("{:?} is not a single char", value);
+                    Err(evaluator.error(ErrorKind::Type, message))
+                }
+            }
+        }
+        argument => unsupported(evaluator, "char", argument),
+    }
+}
+
+/// The code point of a char, or the int a string spells in decimal.
+fn int(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Eval<Object> {
+    arity(evaluator, "int", &arguments, 1)?;
+    match &arguments[0] {
+        Object::Int(value) => Ok(Object::Int(*value)),
+        Object::Char(value) => Ok(Object::Int(i64::from(u32::from(*value)))),
+        Object::String(value) => value.parse().map(Object::Int).map_err(|_| {
+            let message = format!("cannot parse {:?} as an int", value);
+            evaluator.error(ErrorKind::Type, message)
+        }),
+        argument => unsupported(evaluator, "int", argument),
+    }
+}
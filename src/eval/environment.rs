@@ -0,0 +1,74 @@
+use super::object::Object;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The values of one scope, stored by the slots the resolver gave their
+/// names. Each block, parameter list, loop variable, `catch` parameter and
+/// `match` arm the resolver opens a scope for gets an environment of its own
+/// at runtime, so a binding's depth counts environments out from the use.
+#[derive(Default)]
+pub struct Environment {
+    slots: RefCell<Vec<Slot>>,
+    parent: Option<Rc<Environment>>,
+}
+
+#[derive(Default, Clone)]
+struct Slot {
+    /// `None` until the declaration has run.
+    value: Option<Object>,
+    constant: bool,
+}
+
+impl Environment {
+    pub fn enclosed(parent: &Rc<Environment>) -> Rc<Environment> {
+        Rc::new(Environment {
+            slots: RefCell::default(),
+            parent: Some(Rc::clone(parent)),
+        })
+    }
+
+    pub fn define(&self, slot: usize, value: Object, constant: bool) {
+        let mut slots = self.slots.borrow_mut();
+        if slots.len() <= slot {
+            slots.resize(slot + 1, Slot::default());
+        }
+        slots[slot] = Slot {
+            value: Some(value),
+            constant,
+        };
+    }
+
+    pub fn get(&self, depth: usize, slot: usize) -> Option<Object> {
+        let slots = self.ancestor(depth)?.slots.borrow();
+        slots.get(slot)?.value.clone()
+    }
+
+    /// Replaces a defined value. Returns `Err` with the current value if the
+    /// slot is a const, and `Ok(false)` if it has not been defined yet.
+    pub fn assign(&self, depth: usize, slot: usize, value: Object) -> Result<bool, ()> {
+        let environment = match self.ancestor(depth) {
+            Some(environment) => environment,
+            None => return Ok(false),
+        };
+        let mut slots = environment.slots.borrow_mut();
+        match slots.get_mut(slot) {
+            Some(Slot { constant: true, .. }) => Err(()),
+            Some(Slot {
+                value: current @ Some(_),
+                ..
+            }) => {
+                *current = Some(value);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn ancestor(&self, depth: usize) -> Option<&Environment> {
+        let mut environment = self;
+        for _ in 0..depth {
+            environment = environment.parent.as_deref()?;
+        }
+        Some(environment)
+    }
+}
@@ -0,0 +1,1565 @@
+use super::builtins;
+use super::environment::Environment;
+use super::object::{Body, ErrorKind, Function, Object, RuntimeError};
+use crate::lexer::Span;
+use crate::module::{Module, ModuleId};
+use crate::parser::ast::{self, Operator, Pattern};
+use crate::resolver::{self, Resolution};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// How deep calls may nest before the program is stopped with a
+/// `recursion` error.
+const MAX_DEPTH: usize = 1000;
+
+/// Why evaluation left the statement it was running, other than by
+/// finishing it.
+pub(super) enum Flow {
+    Return(Object),
+    Break,
+    Continue,
+    Throw(Rc<RuntimeError>),
+}
+
+pub(super) type Eval<T> = std::result::Result<T, Flow>;
+
+/// A tree-walking interpreter. Identifiers are looked up by the depth and
+/// slot the resolver gave them, so the evaluator opens an environment
+/// exactly where the resolver opens a scope.
+pub struct Evaluator {
+    builtins: Rc<Environment>,
+    environment: Rc<Environment>,
+    resolution: Rc<Resolution>,
+    /// The span of the code being run, for errors raised where the AST
+    /// records no position of its own, such as inside a builtin.
+    span: Span,
+    depth: usize,
+    /// The module being run, if it was loaded by a `ModuleLoader`.
+    module: Option<Rc<ModuleId>>,
+    /// The exports of each module that has been run, so that a module
+    /// imported twice runs once.
+    modules: HashMap<ModuleId, Object>,
+    /// The exports the current module's `import` statements bind, by alias.
+    imports: HashMap<String, Object>,
+    pub(super) output: Box<dyn Write>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new()
+    }
+}
+
+impl Evaluator {
+    /// The stack a thread running the evaluator needs so that the deepest
+    /// recursion allowed ends in a `recursion` error rather than a stack
+    /// overflow. The main thread's is usually too small.
+    pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+    /// An evaluator whose `puts` writes to stdout.
+    pub fn new() -> Evaluator {
+        Evaluator::with_output(io::stdout())
+    }
+
+    pub fn with_output(output: impl Write + 'static) -> Evaluator {
+        let builtins = Rc::new(Environment::default());
+        for (slot, name) in resolver::BUILTINS.iter().enumerate() {
+            if let Some(builtin) = builtins::get(name) {
+                builtins.define(slot, Object::Builtin(builtin), true);
+            }
+        }
+        Evaluator {
+            environment: Rc::clone(&builtins),
+            builtins,
+            resolution: Rc::default(),
+            span: Span::default(),
+            depth: 0,
+            module: None,
+            modules: HashMap::new(),
+            imports: HashMap::new(),
+            output: Box::new(output),
+        }
+    }
+
+    /// Runs `program` and returns the value of its last expression
+    /// statement. An error that no `catch` handled is returned as `Err`.
+    ///
+    /// The program is resolved here; callers that want to refuse programs
+    /// with unknown names should check `resolver::resolve` first. Its
+    /// `import` statements fail, as there is no module to bind; use
+    /// `run_module` for programs that import.
+    pub fn eval(&mut self, program: &ast::Program) -> Result<Object, RuntimeError> {
+        let (result, _) = self.eval_program(program);
+        self.finish(result)
+    }
+
+    /// Runs a module loaded by a `ModuleLoader`, after running each module
+    /// it imports that this evaluator has not run yet. An import is bound
+    /// to a hash of the imported module's exports.
+    pub fn run_module(&mut self, module: &Module) -> Result<Object, RuntimeError> {
+        let result = self.run_imported(module).map(|(value, _)| value);
+        self.finish(result)
+    }
+
+    fn run_imported(&mut self, module: &Module) -> Eval<(Object, Object)> {
+        let mut imports = HashMap::new();
+        for (alias, import) in &module.imports {
+            let exports = match self.modules.get(&import.id) {
+                Some(exports) => exports.clone(),
+                None => {
+                    let (_, exports) = self.run_imported(import)?;
+                    self.modules.insert(import.id.clone(), exports.clone());
+                    exports
+                }
+            };
+            imports.insert(alias.clone(), exports);
+        }
+
+        let imports = std::mem::replace(&mut self.imports, imports);
+        let id = Rc::new(module.id.clone());
+        let outer = self.module.replace(Rc::clone(&id));
+        let (result, environment) = self.eval_program(&module.program);
+        self.module = outer;
+        self.imports = imports;
+
+        let value = result.map_err(|flow| in_module(flow, &id))?;
+        let mut exports = BTreeMap::new();
+        for statement in &module.program.statements {
+            let names = match statement {
+                ast::Statement::Export(declaration) => match &**declaration {
+                    ast::Statement::Let(pattern, _, _) | ast::Statement::Const(pattern, _, _) => {
+                        pattern.identifiers()
+                    }
+                    ast::Statement::FnDecl { name, .. } => vec![name],
+                    _ => vec![],
+                },
+                _ => continue,
+            };
+            for name in names {
+                let slot = self.resolution.declarations[&name.1.start];
+                let value = environment.get(0, slot).unwrap_or(Object::Null);
+                exports.insert(name.0.clone(), value);
+            }
+        }
+        Ok((value, Object::from(exports)))
+    }
+
+    /// Runs `program` in a new environment, and returns that environment
+    /// along with the result.
+    fn eval_program(&mut self, program: &ast::Program) -> (Eval<Object>, Rc<Environment>) {
+        self.resolution = Rc::new(resolver::resolve(program));
+        let environment = Environment::enclosed(&self.builtins);
+        let result = self.in_environment(Rc::clone(&environment), |evaluator| {
+            evaluator.eval_statements(&program.statements)
+        });
+        (result, environment)
+    }
+
+    /// Calls a function value, such as one returned by `eval`.
+    pub fn call(
+        &mut self,
+        function: &Object,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        let result = self.call_value(function, arguments, self.span);
+        self.finish(result)
+    }
+
+    fn finish(&mut self, result: Eval<Object>) -> Result<Object, RuntimeError> {
+        match result {
+            Ok(value) | Err(Flow::Return(value)) => Ok(value),
+            Err(Flow::Break) | Err(Flow::Continue) => Ok(Object::Null),
+            Err(Flow::Throw(error)) => Err(Rc::try_unwrap(error).unwrap_or_else(|e| (*e).clone())),
+        }
+    }
+
+    /// An error at the span of the code being run.
+    pub(super) fn error(&self, kind: ErrorKind, message: impl Into<String>) -> Flow {
+        error_at(self.span, kind, message)
+    }
+
+    fn in_environment<T>(
+        &mut self,
+        environment: Rc<Environment>,
+        eval: impl FnOnce(&mut Evaluator) -> Eval<T>,
+    ) -> Eval<T> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = eval(self);
+        self.environment = previous;
+        result
+    }
+
+    /// The slot the resolver gave a declared name.
+    fn slot(&self, identifier: &ast::Identifier) -> usize {
+        self.resolution.declarations[&identifier.1.start]
+    }
+
+    fn define(&self, identifier: &ast::Identifier, value: Object, constant: bool) {
+        self.environment
+            .define(self.slot(identifier), value, constant);
+    }
+
+    fn eval_statements(&mut self, statements: &[ast::Statement]) -> Eval<Object> {
+        self.hoist(statements);
+        let mut value = Object::Null;
+        for statement in statements {
+            value = self.eval_statement(statement)?;
+        }
+        Ok(value)
+    }
+
+    /// Binds the named functions declared in `statements`, so that they can
+    /// be called before their declaration is reached.
+    fn hoist(&mut self, statements: &[ast::Statement]) {
+        for statement in statements {
+            let statement = match statement {
+                ast::Statement::Export(declaration) => declaration,
+                statement => statement,
+            };
+            if let ast::Statement::FnDecl {
+                name,
+                parameters,
+                body,
+                ..
+            } = statement
+            {
+                let function =
+                    self.closure(Some(name.0.clone()), parameters, Body::Block(body.clone()));
+                self.define(name, function, false);
+            }
+        }
+    }
+
+    fn eval_block(&mut self, block: &ast::BlockStatement) -> Eval<Object> {
+        let environment = Environment::enclosed(&self.environment);
+        self.in_environment(environment, |evaluator| {
+            evaluator.eval_statements(&block.statements)
+        })
+    }
+
+    /// Runs a statement, returning the value of an expression statement and
+    /// `null` for everything else.
+    fn eval_statement(&mut self, statement: &ast::Statement) -> Eval<Object> {
+        if let Some(position) = statement.position() {
+            self.span = Span {
+                start: position,
+                end: position,
+            };
+        }
+        match statement {
+            ast::Statement::Let(pattern, _, value) | ast::Statement::Const(pattern, _, value) => {
+                let constant = matches!(statement, ast::Statement::Const(..));
+                let value = self.eval_expression(value)?;
+                if !self.bind(pattern, &value, constant)? {
+                    let span = match pattern.identifiers().first() {
+                        Some(identifier) => identifier.1,
+                        None => self.span,
+                    };
+                    let message = format!("cannot destructure {} as {}", value.inspect(), pattern);
+                    return Err(error_at(span, ErrorKind::Shape, message));
+                }
+            }
+            ast::Statement::Return(value, _) => {
+                return Err(Flow::Return(self.eval_expression(value)?))
+            }
+            ast::Statement::Expr(expression) => return self.eval_expression(expression),
+            ast::Statement::While { condition, body } => {
+                while self.eval_expression(condition)?.is_truthy() {
+                    match self.eval_block(body) {
+                        Ok(_) | Err(Flow::Continue) => {}
+                        Err(Flow::Break) => break,
+                        Err(flow) => return Err(flow),
+                    }
+                }
+            }
+            ast::Statement::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                let span = self.span_of(iterable);
+                let iterable = self.eval_expression(iterable)?;
+                for element in self.elements(&iterable, span)? {
+                    let environment = Environment::enclosed(&self.environment);
+                    environment.define(self.slot(variable), element, false);
+                    match self.in_environment(environment, |evaluator| evaluator.eval_block(body)) {
+                        Ok(_) | Err(Flow::Continue) => {}
+                        Err(Flow::Break) => break,
+                        Err(flow) => return Err(flow),
+                    }
+                }
+            }
+            ast::Statement::Break(_) => return Err(Flow::Break),
+            ast::Statement::Continue(_) => return Err(Flow::Continue),
+            ast::Statement::FnDecl { .. } => {}
+            ast::Statement::Import { path, alias } => match self.imports.get(&alias.0) {
+                Some(exports) => self.define(alias, exports.clone(), true),
+                None => {
+                    let message = format!("cannot import \"{}\" without a module loader", path);
+                    return Err(error_at(alias.1, ErrorKind::Import, message));
+                }
+            },
+            ast::Statement::Export(declaration) => {
+                self.eval_statement(declaration)?;
+            }
+            ast::Statement::Throw(value, span) => {
+                let error = match self.eval_expression(value)? {
+                    Object::Error(error) => error,
+                    value => Rc::new(RuntimeError {
+                        kind: ErrorKind::Thrown,
+                        message: value.to_string(),
+                        span: *span,
+                        module: None,
+                    }),
+                };
+                return Err(Flow::Throw(error));
+            }
+            ast::Statement::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let mut result = self.eval_block(body);
+                if let (Err(Flow::Throw(error)), Some(catch)) = (&result, catch) {
+                    let environment = Environment::enclosed(&self.environment);
+                    environment.define(
+                        self.slot(&catch.parameter),
+                        Object::Error(Rc::clone(error)),
+                        false,
+                    );
+                    result = self
+                        .in_environment(environment, |evaluator| evaluator.eval_block(&catch.body));
+                }
+                if let Some(finally) = finally {
+                    self.eval_block(finally)?;
+                }
+                result?;
+            }
+        }
+        Ok(Object::Null)
+    }
+
+    fn eval_expression(&mut self, expression: &ast::Expression) -> Eval<Object> {
+        match expression {
+            ast::Expression::Ident(identifier) => self.lookup(identifier),
+            ast::Expression::IntegerLiteral { value, .. } => Ok(Object::Int(*value)),
+            ast::Expression::BooleanLiteral(value) => Ok(Object::Bool(*value)),
+            ast::Expression::NullLiteral => Ok(Object::Null),
+            ast::Expression::StringLiteral { value, .. } => Ok(Object::from(value.as_str())),
+            ast::Expression::Template(segments) => {
+                let mut value = String::new();
+                for segment in segments {
+                    match segment {
+                        ast::TemplateSegment::Literal(literal) => value.push_str(literal),
+                        ast::TemplateSegment::Interpolation { expression, .. } => {
+                            value.push_str(&self.eval_expression(expression)?.to_string())
+                        }
+                    }
+                }
+                Ok(Object::from(value.as_str()))
+            }
+            ast::Expression::CharLiteral(value) => Ok(Object::Char(*value)),
+            ast::Expression::ByteStringLiteral(value) => Ok(Object::Bytes(value.as_slice().into())),
+            ast::Expression::Prefix {
+                operator,
+                right,
+                span,
+                ..
+            } => {
+                let right = self.eval_expression(right)?;
+                prefix(operator, right, *span)
+            }
+            ast::Expression::Infix {
+                left,
+                operator,
+                right,
+                span,
+                ..
+            } => {
+                let left = self.eval_expression(left)?;
+                match operator {
+                    Operator::And if !left.is_truthy() => Ok(Object::Bool(false)),
+                    Operator::Or if left.is_truthy() => Ok(Object::Bool(true)),
+                    Operator::And | Operator::Or => {
+                        Ok(Object::Bool(self.eval_expression(right)?.is_truthy()))
+                    }
+                    Operator::Coalesce if left != Object::Null => Ok(left),
+                    Operator::Coalesce => self.eval_expression(right),
+                    operator => {
+                        let right = self.eval_expression(right)?;
+                        infix(operator, left, right, *span)
+                    }
+                }
+            }
+            ast::Expression::Assign {
+                target,
+                operator,
+                value,
+                ..
+            } => self.eval_assignment(target, operator, value),
+            ast::Expression::Index {
+                left,
+                index,
+                optional,
+            } => {
+                let span = self.span_of(left);
+                let left = self.eval_expression(left)?;
+                if *optional && left == Object::Null {
+                    return Ok(Object::Null);
+                }
+                let index = self.eval_expression(index)?;
+                get_index(&left, &index, span)
+            }
+            ast::Expression::Member {
+                object,
+                property,
+                optional,
+            } => {
+                let object = self.eval_expression(object)?;
+                if *optional && object == Object::Null {
+                    return Ok(Object::Null);
+                }
+                get_field(&object, property)
+            }
+            ast::Expression::Function {
+                parameters, body, ..
+            } => Ok(self.closure(None, parameters, Body::Block(body.clone()))),
+            ast::Expression::Lambda {
+                parameters, body, ..
+            } => Ok(self.closure(None, parameters, Body::Expression((**body).clone()))),
+            ast::Expression::Call {
+                function,
+                arguments,
+            } if matches!(**function, ast::Expression::Member { .. }) => {
+                self.eval_method_call(function, arguments)
+            }
+            ast::Expression::Call {
+                function,
+                arguments,
+            } => {
+                let span = self.span_of(function);
+                let function = self.eval_expression(function)?;
+                let arguments = self.eval_arguments(arguments)?;
+                self.call_value(&function, arguments, span)
+            }
+            ast::Expression::Spread(value) => Err(error_at(
+                self.span_of(value),
+                ErrorKind::Type,
+                "`...` can only spread call arguments",
+            )),
+            ast::Expression::Propagate(value) => {
+                let span = self.span_of(value);
+                match self.eval_expression(value)? {
+                    Object::Ok(value) => Ok(*value),
+                    Object::Err(error) => Err(Flow::Return(Object::Err(error))),
+                    value => {
+                        let message =
+                            format!("`?` needs an ok or err value, found {}", value.type_name());
+                        Err(error_at(span, ErrorKind::Type, message))
+                    }
+                }
+            }
+            ast::Expression::Pipe { value, function } => {
+                let value = self.eval_expression(value)?;
+                let (span, function, mut arguments) = match &**function {
+                    ast::Expression::Call {
+                        function,
+                        arguments,
+                    } => (
+                        self.span_of(function),
+                        self.eval_expression(function)?,
+                        self.eval_arguments(arguments)?,
+                    ),
+                    function => (
+                        self.span_of(function),
+                        self.eval_expression(function)?,
+                        vec![],
+                    ),
+                };
+                arguments.insert(0, value);
+                self.call_value(&function, arguments, span)
+            }
+            ast::Expression::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let span = self.span_of(start);
+                match (self.eval_expression(start)?, self.eval_expression(end)?) {
+                    (Object::Int(start), Object::Int(end)) => Ok(Object::Range {
+                        start,
+                        end,
+                        inclusive: *inclusive,
+                    }),
+                    (start, end) => {
+                        let message = format!(
+                            "range bounds must be ints, found {} and {}",
+                            start.type_name(),
+                            end.type_name()
+                        );
+                        Err(error_at(span, ErrorKind::Type, message))
+                    }
+                }
+            }
+            ast::Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                if self.eval_expression(condition)?.is_truthy() {
+                    self.eval_block(consequence)
+                } else if let Some(alternative) = alternative {
+                    self.eval_block(alternative)
+                } else {
+                    Ok(Object::Null)
+                }
+            }
+            ast::Expression::Match { value, arms } => {
+                let span = self.span_of(value);
+                let value = self.eval_expression(value)?;
+                for arm in arms {
+                    let environment = Environment::enclosed(&self.environment);
+                    let result = self.in_environment(environment, |evaluator| {
+                        if !evaluator.bind(&arm.pattern, &value, false)? {
+                            return Ok(None);
+                        }
+                        if let Some(guard) = &arm.guard {
+                            if !evaluator.eval_expression(guard)?.is_truthy() {
+                                return Ok(None);
+                            }
+                        }
+                        evaluator.eval_expression(&arm.body).map(Some)
+                    })?;
+                    if let Some(result) = result {
+                        return Ok(result);
+                    }
+                }
+                let message = format!("no arm matches {}", value.inspect());
+                Err(error_at(span, ErrorKind::NoMatch, message))
+            }
+        }
+    }
+
+    fn lookup(&self, identifier: &ast::Identifier) -> Eval<Object> {
+        let ast::Identifier(name, span) = identifier;
+        let binding = match self.resolution.bindings.get(&span.start) {
+            Some(binding) => binding,
+            None => {
+                let message = format!("unknown identifier {}", name);
+                return Err(error_at(*span, ErrorKind::Name, message));
+            }
+        };
+        match self.environment.get(binding.depth, binding.slot) {
+            Some(value) => Ok(value),
+            None => {
+                let message = format!("{} is used before its definition", name);
+                Err(error_at(*span, ErrorKind::Name, message))
+            }
+        }
+    }
+
+    fn assign(&self, identifier: &ast::Identifier, value: Object) -> Eval<()> {
+        let ast::Identifier(name, span) = identifier;
+        let binding = match self.resolution.bindings.get(&span.start) {
+            Some(binding) => binding,
+            None => {
+                let message = format!("unknown identifier {}", name);
+                return Err(error_at(*span, ErrorKind::Name, message));
+            }
+        };
+        match self.environment.assign(binding.depth, binding.slot, value) {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                let message = format!("{} is used before its definition", name);
+                Err(error_at(*span, ErrorKind::Name, message))
+            }
+            Err(()) => {
+                let message = format!("cannot assign to const {}", name);
+                Err(error_at(*span, ErrorKind::Const, message))
+            }
+        }
+    }
+
+    /// `target = value`, or a compound assignment such as `target += value`.
+    /// The target's own subexpressions are evaluated once, before `value`.
+    fn eval_assignment(
+        &mut self,
+        target: &ast::Expression,
+        operator: &Operator,
+        value: &ast::Expression,
+    ) -> Eval<Object> {
+        let span = self.span_of(target);
+        let operator = match operator {
+            Operator::PlusAssign => Some(Operator::Plus),
+            Operator::MinusAssign => Some(Operator::Minus),
+            Operator::AsteriskAssign => Some(Operator::Asterisk),
+            Operator::SlashAssign => Some(Operator::Slash),
+            _ => None,
+        };
+        match target {
+            ast::Expression::Ident(identifier) => {
+                let mut value = self.eval_expression(value)?;
+                if let Some(operator) = &operator {
+                    value = infix(operator, self.lookup(identifier)?, value, span)?;
+                }
+                self.assign(identifier, value.clone())?;
+                Ok(value)
+            }
+            ast::Expression::Index { left, index, .. } => {
+                let container = self.eval_expression(left)?;
+                let index = self.eval_expression(index)?;
+                let mut value = self.eval_expression(value)?;
+                if let Some(operator) = &operator {
+                    let current = get_index(&container, &index, span)?;
+                    value = infix(operator, current, value, span)?;
+                }
+                set_index(&container, index, value.clone(), span)?;
+                Ok(value)
+            }
+            ast::Expression::Member {
+                object, property, ..
+            } => {
+                let object = self.eval_expression(object)?;
+                let mut value = self.eval_expression(value)?;
+                if let Some(operator) = &operator {
+                    value = infix(operator, get_field(&object, property)?, value, span)?;
+                }
+                match &object {
+                    Object::Hash(fields) => {
+                        fields
+                            .borrow_mut()
+                            .insert(property.0.clone(), value.clone());
+                        Ok(value)
+                    }
+                    object => {
+                        let message =
+                            format!("cannot set field {} on {}", property, object.type_name());
+                        Err(error_at(property.1, ErrorKind::Type, message))
+                    }
+                }
+            }
+            target => {
+                let message = format!("cannot assign to {}", target);
+                Err(error_at(span, ErrorKind::Type, message))
+            }
+        }
+    }
+
+    /// `value.name(arguments)`: a method of `value`'s type, called with
+    /// `value` first, or else a function stored in a hash field.
+    fn eval_method_call(
+        &mut self,
+        function: &ast::Expression,
+        arguments: &[ast::Expression],
+    ) -> Eval<Object> {
+        let (object, property, optional) = match function {
+            ast::Expression::Member {
+                object,
+                property,
+                optional,
+            } => (object, property, *optional),
+            _ => unreachable!("method calls are calls on a member"),
+        };
+        let object = self.eval_expression(object)?;
+        if optional && object == Object::Null {
+            return Ok(Object::Null);
+        }
+        let (function, receiver) = match builtins::method(&object, &property.0) {
+            Some(method) => (Object::Builtin(method), Some(object)),
+            None if matches!(object, Object::Hash(_)) => (get_field(&object, property)?, None),
+            None => {
+                let message = format!("{} has no method {}", object.type_name(), property);
+                return Err(error_at(property.1, ErrorKind::Type, message));
+            }
+        };
+        let mut arguments = self.eval_arguments(arguments)?;
+        if let Some(receiver) = receiver {
+            arguments.insert(0, receiver);
+        }
+        self.call_value(&function, arguments, property.1)
+    }
+
+    /// Evaluates call arguments, expanding `...spread` ones in place.
+    fn eval_arguments(&mut self, arguments: &[ast::Expression]) -> Eval<Vec<Object>> {
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            match argument {
+                ast::Expression::Spread(value) => {
+                    let span = self.span_of(value);
+                    let value = self.eval_expression(value)?;
+                    match value {
+                        Object::Array(_) | Object::Range { .. } => {
+                            values.extend(self.elements(&value, span)?)
+                        }
+                        value => {
+                            let message = format!("cannot spread {}", value.type_name());
+                            return Err(error_at(span, ErrorKind::Type, message));
+                        }
+                    }
+                }
+                argument => values.push(self.eval_expression(argument)?),
+            }
+        }
+        Ok(values)
+    }
+
+    fn closure(&self, name: Option<String>, parameters: &[ast::Parameter], body: Body) -> Object {
+        Object::Function(Rc::new(Function {
+            name,
+            parameters: parameters.to_vec(),
+            body,
+            environment: Rc::clone(&self.environment),
+            resolution: Rc::clone(&self.resolution),
+            module: self.module.clone(),
+        }))
+    }
+
+    fn call_value(
+        &mut self,
+        function: &Object,
+        arguments: Vec<Object>,
+        span: Span,
+    ) -> Eval<Object> {
+        match function {
+            Object::Function(function) => self.call_function(function, arguments, span),
+            Object::Builtin(builtin) => {
+                self.span = span;
+                (builtin.function)(self, arguments)
+            }
+            value => {
+                let message = format!("{} is not a function", value.type_name());
+                Err(error_at(span, ErrorKind::Type, message))
+            }
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        function: &Rc<Function>,
+        mut arguments: Vec<Object>,
+        span: Span,
+    ) -> Eval<Object> {
+        let parameters = &function.parameters;
+        let rest = parameters.last().is_some_and(|p| p.rest);
+        let positional = parameters.len() - usize::from(rest);
+        let required = parameters
+            .iter()
+            .take_while(|p| p.default.is_none() && !p.rest)
+            .count();
+        if arguments.len() < required || (!rest && arguments.len() > positional) {
+            let expected = if rest {
+                format!("at least {}", required)
+            } else if required < positional {
+                format!("{} to {}", required, positional)
+            } else {
+                required.to_string()
+            };
+            let message = format!(
+                "wrong number of arguments to {}: expected {}, found {}",
+                function,
+                expected,
+                arguments.len()
+            );
+            return Err(error_at(span, ErrorKind::Arity, message));
+        }
+        if self.depth == MAX_DEPTH {
+            let message = format!("calls nested more than {} deep", MAX_DEPTH);
+            return Err(error_at(span, ErrorKind::Recursion, message));
+        }
+
+        let mut rest = if rest && arguments.len() > positional {
+            arguments.split_off(positional)
+        } else {
+            vec![]
+        };
+        let mut arguments = arguments.into_iter();
+        let environment = Environment::enclosed(&function.environment);
+        let resolution = std::mem::replace(&mut self.resolution, Rc::clone(&function.resolution));
+        let module = std::mem::replace(&mut self.module, function.module.clone());
+        self.depth += 1;
+        let result = self.in_environment(environment, |evaluator| {
+            for parameter in parameters {
+                let value = match (&parameter.default, arguments.next()) {
+                    _ if parameter.rest => Object::from(std::mem::take(&mut rest)),
+                    (_, Some(value)) => value,
+                    (Some(default), None) => evaluator.eval_expression(default)?,
+                    (None, None) => unreachable!("arity was checked"),
+                };
+                evaluator.define(&parameter.name, value, false);
+            }
+            match &function.body {
+                Body::Block(block) => evaluator.eval_block(block),
+                Body::Expression(expression) => evaluator.eval_expression(expression),
+            }
+        });
+        self.depth -= 1;
+        self.resolution = resolution;
+        let module = std::mem::replace(&mut self.module, module);
+        match result {
+            Ok(value) | Err(Flow::Return(value)) => Ok(value),
+            Err(Flow::Break) | Err(Flow::Continue) => Ok(Object::Null),
+            Err(flow) => Err(match &module {
+                Some(module) => in_module(flow, module),
+                None => flow,
+            }),
+        }
+    }
+
+    /// Matches `value` against `pattern`, binding its names in the current
+    /// environment as it goes. Returns whether the value matched.
+    fn bind(&mut self, pattern: &Pattern, value: &Object, constant: bool) -> Eval<bool> {
+        match pattern {
+            Pattern::Wildcard => Ok(true),
+            Pattern::Binding(identifier) => {
+                self.define(identifier, value.clone(), constant);
+                Ok(true)
+            }
+            Pattern::Literal(literal) => Ok(self.eval_expression(literal)? == *value),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = self.eval_expression(start)?;
+                let end = self.eval_expression(end)?;
+                Ok(match (start, value, end) {
+                    (Object::Int(start), Object::Int(value), Object::Int(end)) => {
+                        start <= *value && (*value < end || *inclusive && *value == end)
+                    }
+                    (Object::Char(start), Object::Char(value), Object::Char(end)) => {
+                        start <= *value && (*value < end || *inclusive && *value == end)
+                    }
+                    _ => false,
+                })
+            }
+            Pattern::Array(patterns) => {
+                let elements = match value {
+                    Object::Array(elements) => elements.borrow().clone(),
+                    _ => return Ok(false),
+                };
+                let rest = patterns.iter().position(|p| matches!(p, Pattern::Rest(_)));
+                let (before, after) = match rest {
+                    Some(rest) => (&patterns[..rest], &patterns[rest + 1..]),
+                    None => (patterns.as_slice(), &[][..]),
+                };
+                if elements.len() < before.len() + after.len() && rest.is_some()
+                    || elements.len() > patterns.len() && rest.is_none()
+                {
+                    return Ok(false);
+                }
+                for (i, pattern) in before.iter().enumerate() {
+                    let matched = match elements.get(i) {
+                        Some(element) => self.bind(pattern, element, constant)?,
+                        None => self.bind_missing(pattern, constant)?,
+                    };
+                    if !matched {
+                        return Ok(false);
+                    }
+                }
+                if let Some(rest) = rest {
+                    let tail = elements.len() - after.len();
+                    if let Pattern::Rest(Some(identifier)) = &patterns[rest] {
+                        let middle = elements[before.len()..tail].to_vec();
+                        self.define(identifier, Object::from(middle), constant);
+                    }
+                    for (pattern, element) in after.iter().zip(&elements[tail..]) {
+                        if !self.bind(pattern, element, constant)? {
+                            return Ok(false);
+                        }
+                    }
+                }
+                Ok(true)
+            }
+            Pattern::Rest(_) => Ok(false),
+            Pattern::Hash(fields) => {
+                for (key, pattern) in fields {
+                    let field = match value {
+                        Object::Hash(fields) => fields.borrow().get(&key.0).cloned(),
+                        Object::Error(error) => error_field(error, &key.0),
+                        Object::Ok(_) | Object::Err(_) => result_field(value, &key.0),
+                        _ => return Ok(false),
+                    };
+                    let matched = match field {
+                        Some(field) => self.bind(pattern, &field, constant)?,
+                        None => self.bind_missing(pattern, constant)?,
+                    };
+                    if !matched {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Pattern::Default { pattern, .. } => self.bind(pattern, value, constant),
+        }
+    }
+
+    /// Matches a pattern whose value is absent: only a pattern with a
+    /// default matches, against the default.
+    fn bind_missing(&mut self, pattern: &Pattern, constant: bool) -> Eval<bool> {
+        match pattern {
+            Pattern::Default { pattern, default } => {
+                let default = self.eval_expression(default)?;
+                self.bind(pattern, &default, constant)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// The values a `for` loop or spread visits in `value`.
+    pub(super) fn elements(&self, value: &Object, span: Span) -> Eval<Vec<Object>> {
+        Ok(match value {
+            Object::Range {
+                start,
+                end,
+                inclusive: false,
+            } => (*start..*end).map(Object::Int).collect(),
+            Object::Range {
+                start,
+                end,
+                inclusive: true,
+            } => (*start..=*end).map(Object::Int).collect(),
+            Object::Array(elements) => elements.borrow().clone(),
+            Object::Hash(fields) => fields
+                .borrow()
+                .keys()
+                .map(|k| Object::from(k.as_str()))
+                .collect(),
+            Object::String(value) => value.chars().map(Object::Char).collect(),
+            Object::Bytes(value) => value.iter().map(|b| Object::Int(i64::from(*b))).collect(),
+            value => {
+                let message = format!("cannot iterate over {}", value.type_name());
+                return Err(error_at(span, ErrorKind::Type, message));
+            }
+        })
+    }
+
+    /// Where an error about `expression` should point: the span the AST
+    /// records for it, or the statement being run.
+    fn span_of(&self, expression: &ast::Expression) -> Span {
+        expression_span(expression).unwrap_or(self.span)
+    }
+}
+
+fn expression_span(expression: &ast::Expression) -> Option<Span> {
+    match expression {
+        ast::Expression::Ident(identifier) => Some(identifier.1),
+        ast::Expression::Prefix { span, .. }
+        | ast::Expression::Infix { span, .. }
+        | ast::Expression::If { span, .. }
+        | ast::Expression::Lambda { span, .. } => Some(*span),
+        ast::Expression::Member { property, .. } => Some(property.1),
+        ast::Expression::Function { body, .. } => Some(body.span),
+        ast::Expression::Assign { target: value, .. }
+        | ast::Expression::Index { left: value, .. }
+        | ast::Expression::Call {
+            function: value, ..
+        }
+        | ast::Expression::Spread(value)
+        | ast::Expression::Propagate(value)
+        | ast::Expression::Match { value, .. } => expression_span(value),
+        ast::Expression::Pipe { value, function } => {
+            expression_span(value).or_else(|| expression_span(function))
+        }
+        ast::Expression::Range { start, end, .. } => {
+            expression_span(start).or_else(|| expression_span(end))
+        }
+        ast::Expression::Template(segments) => segments.iter().find_map(|segment| match segment {
+            ast::TemplateSegment::Interpolation { expression, .. } => expression_span(expression),
+            ast::TemplateSegment::Literal(_) => None,
+        }),
+        ast::Expression::IntegerLiteral { .. }
+        | ast::Expression::BooleanLiteral(_)
+        | ast::Expression::NullLiteral
+        | ast::Expression::StringLiteral { .. }
+        | ast::Expression::CharLiteral(_)
+        | ast::Expression::ByteStringLiteral(_) => None,
+    }
+}
+
+/// Records that an error not yet placed in a module was raised in `module`.
+fn in_module(flow: Flow, module: &ModuleId) -> Flow {
+    match flow {
+        Flow::Throw(mut error) if error.module.is_none() => {
+            Rc::make_mut(&mut error).module = Some(module.clone());
+            Flow::Throw(error)
+        }
+        flow => flow,
+    }
+}
+
+pub(super) fn error_at(span: Span, kind: ErrorKind, message: impl Into<String>) -> Flow {
+    Flow::Throw(Rc::new(RuntimeError {
+        kind,
+        message: message.into(),
+        span,
+        module: None,
+    }))
+}
+
+/// The fields scripts can read from a caught error.
+fn error_field(error: &RuntimeError, name: &str) -> Option<Object> {
+    match name {
+        "message" => Some(Object::from(error.message.as_str())),
+        "kind" => Some(Object::from(error.kind.name())),
+        "line" => Some(Object::Int(error.span.start.line as i64)),
+        "column" => Some(Object::Int(error.span.start.column as i64)),
+        _ => None,
+    }
+}
+
+/// The `value` of an `ok` or the `error` of an `err`.
+fn result_field(result: &Object, name: &str) -> Option<Object> {
+    match (result, name) {
+        (Object::Ok(value), "value") | (Object::Err(value), "error") => Some((**value).clone()),
+        _ => None,
+    }
+}
+
+/// A hash's field, or a field of an error or result. A result's `value` or
+/// `error`, like a hash's missing key, is `null` when it has none.
+fn get_field(object: &Object, property: &ast::Identifier) -> Eval<Object> {
+    let field = match object {
+        Object::Hash(fields) => Some(
+            fields
+                .borrow()
+                .get(&property.0)
+                .cloned()
+                .unwrap_or(Object::Null),
+        ),
+        Object::Error(error) => error_field(error, &property.0),
+        Object::Ok(_) | Object::Err(_) if matches!(&*property.0, "value" | "error") => {
+            Some(result_field(object, &property.0).unwrap_or(Object::Null))
+        }
+        _ => None,
+    };
+    field.ok_or_else(|| {
+        let message = format!("{} has no field {}", object.type_name(), property);
+        error_at(property.1, ErrorKind::Type, message)
+    })
+}
+
+/// `container[index]`. Indexing past the end gives `null`, but slicing
+/// past it is an error.
+fn get_index(container: &Object, index: &Object, span: Span) -> Eval<Object> {
+    match (container, index) {
+        (Object::Array(elements), Object::Int(i)) => {
+            let elements = elements.borrow();
+            Ok(usize::try_from(*i)
+                .ok()
+                .and_then(|i| elements.get(i))
+                .cloned()
+                .unwrap_or(Object::Null))
+        }
+        (Object::String(value), Object::Int(i)) => Ok(usize::try_from(*i)
+            .ok()
+            .and_then(|i| value.chars().nth(i))
+            .map_or(Object::Null, Object::Char)),
+        (Object::Bytes(value), Object::Int(i)) => Ok(usize::try_from(*i)
+            .ok()
+            .and_then(|i| value.get(i))
+            .map_or(Object::Null, |b| Object::Int(i64::from(*b)))),
+        (Object::Hash(fields), Object::String(key)) => {
+            Ok(fields.borrow().get(&**key).cloned().unwrap_or(Object::Null))
+        }
+        (
+            Object::Array(_) | Object::String(_) | Object::Bytes(_),
+            Object::Range {
+                start,
+                end,
+                inclusive,
+            },
+        ) => {
+            let len = match container {
+                Object::Array(elements) => elements.borrow().len(),
+                Object::String(value) => value.chars().count(),
+                Object::Bytes(value) => value.len(),
+                _ => unreachable!(),
+            };
+            let end = if *inclusive {
+                end.checked_add(1)
+            } else {
+                Some(*end)
+            };
+            let bounds = match (usize::try_from(*start), end.map(usize::try_from)) {
+                (Ok(start), Some(Ok(end))) if start <= end && end <= len => Some(start..end),
+                _ => None,
+            };
+            let bounds = bounds.ok_or_else(|| {
+                let message = format!("range {} is out of bounds for length {}", index, len);
+                error_at(span, ErrorKind::Index, message)
+            })?;
+            Ok(match container {
+                Object::Array(elements) => Object::from(elements.borrow()[bounds].to_vec()),
+                Object::String(value) => {
+                    let value: String = value
+                        .chars()
+                        .skip(bounds.start)
+                        .take(bounds.len())
+                        .collect();
+                    Object::from(value.as_str())
+                }
+                Object::Bytes(value) => Object::Bytes(value[bounds].into()),
+                _ => unreachable!(),
+            })
+        }
+        (container, index) => {
+            let message = format!(
+                "cannot index {} with {}",
+                container.type_name(),
+                index.type_name()
+            );
+            Err(error_at(span, ErrorKind::Type, message))
+        }
+    }
+}
+
+fn set_index(container: &Object, index: Object, value: Object, span: Span) -> Eval<()> {
+    match (container, index) {
+        (Object::Array(elements), Object::Int(i)) => {
+            let mut elements = elements.borrow_mut();
+            let len = elements.len();
+            match usize::try_from(i).ok().and_then(|i| elements.get_mut(i)) {
+                Some(element) => {
+                    *element = value;
+                    Ok(())
+                }
+                None => {
+                    let message = format!("index {} is out of bounds for length {}", i, len);
+                    Err(error_at(span, ErrorKind::Index, message))
+                }
+            }
+        }
+        (Object::Hash(fields), Object::String(key)) => {
+            fields.borrow_mut().insert(key.to_string(), value);
+            Ok(())
+        }
+        (container, index) => {
+            let message = format!(
+                "cannot assign to {} indexed with {}",
+                container.type_name(),
+                index.type_name()
+            );
+            Err(error_at(span, ErrorKind::Type, message))
+        }
+    }
+}
+
+fn prefix(operator: &Operator, right: Object, span: Span) -> Eval<Object> {
+    match (operator, right) {
+        (Operator::Bang, right) => Ok(Object::Bool(!right.is_truthy())),
+        (Operator::Minus, Object::Int(right)) => right
+            .checked_neg()
+            .map(Object::Int)
+            .ok_or_else(|| error_at(span, ErrorKind::Overflow, format!("-{} overflows", right))),
+        (Operator::Tilde, Object::Int(right)) => Ok(Object::Int(!right)),
+        (operator, right) => {
+            let message = format!("cannot apply {} to {}", operator, right.type_name());
+            Err(error_at(span, ErrorKind::Type, message))
+        }
+    }
+}
+
+fn infix(operator: &Operator, left: Object, right: Object, span: Span) -> Eval<Object> {
+    let overflow = |left: i64, right: i64| {
+        let message = format!("{} {} {} overflows", left, operator, right);
+        error_at(span, ErrorKind::Overflow, message)
+    };
+    match (operator, &left, &right) {
+        (Operator::Eq, _, _) => Ok(Object::Bool(left == right)),
+        (Operator::Neq, _, _) => Ok(Object::Bool(left != right)),
+        (Operator::In, _, _) => contains(&right, &left, span).map(Object::Bool),
+        (Operator::Slash, Object::Int(_), Object::Int(0))
+        | (Operator::Percent, Object::Int(_), Object::Int(0)) => Err(error_at(
+            span,
+            ErrorKind::DivisionByZero,
+            "division by zero",
+        )),
+        (Operator::ShiftLeft, Object::Int(_), Object::Int(r))
+        | (Operator::ShiftRight, Object::Int(_), Object::Int(r))
+            if !(0..64).contains(r) =>
+        {
+            let message = format!("cannot shift by {}", r);
+            Err(error_at(span, ErrorKind::Shift, message))
+        }
+        (Operator::Power, Object::Int(_), Object::Int(r)) if *r < 0 => {
+            let message = format!("negative exponent {}", r);
+            Err(error_at(span, ErrorKind::NegativeExponent, message))
+        }
+        (operator, Object::Int(l), Object::Int(r)) => {
+            let (l, r) = (*l, *r);
+            let value = match operator {
+                Operator::Plus => l.checked_add(r),
+                Operator::Minus => l.checked_sub(r),
+                Operator::Asterisk => l.checked_mul(r),
+                Operator::Slash => l.checked_div(r),
+                Operator::Percent => l.checked_rem(r),
+                Operator::Power => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+                Operator::Ampersand => Some(l & r),
+                Operator::Pipe => Some(l | r),
+                Operator::Caret => Some(l ^ r),
+                Operator::ShiftLeft => Some(l << r),
+                Operator::ShiftRight => Some(l >> r),
+                Operator::Lt => return Ok(Object::Bool(l < r)),
+                Operator::Gt => return Ok(Object::Bool(l > r)),
+                Operator::LtEq => return Ok(Object::Bool(l <= r)),
+                Operator::GtEq => return Ok(Object::Bool(l >= r)),
+                _ => return Err(mismatch(operator, &left, &right, span)),
+            };
+            value.map(Object::Int).ok_or_else(|| overflow(l, r))
+        }
+        (Operator::Plus, Object::String(l), Object::String(r)) => {
+            Ok(Object::from(format!("{}{}", l, r).as_str()))
+        }
+        (Operator::Plus, Object::Bytes(l), Object::Bytes(r)) => {
+            Ok(Object::Bytes([&l[..], &r[..]].concat().into()))
+        }
+        (Operator::Plus, Object::Array(l), Object::Array(r)) => {
+            let elements = [&l.borrow()[..], &r.borrow()[..]].concat();
+            Ok(Object::from(elements))
+        }
+        (operator, Object::String(l), Object::String(r)) => compare(operator, l, r)
+            .map(Object::Bool)
+            .ok_or_else(|| mismatch(operator, &left, &right, span)),
+        (operator, Object::Char(l), Object::Char(r)) => compare(operator, l, r)
+            .map(Object::Bool)
+            .ok_or_else(|| mismatch(operator, &left, &right, span)),
+        (operator, _, _) => Err(mismatch(operator, &left, &right, span)),
+    }
+}
+
+fn compare<T: PartialOrd + ?Sized>(operator: &Operator, left: &T, right: &T) -> Option<bool> {
+    match operator {
+        Operator::Lt => Some(left < right),
+        Operator::Gt => Some(left > right),
+        Operator::LtEq => Some(left <= right),
+        Operator::GtEq => Some(left >= right),
+        _ => None,
+    }
+}
+
+fn mismatch(operator: &Operator, left: &Object, right: &Object, span: Span) -> Flow {
+    let message = format!(
+        "cannot apply {} to {} and {}",
+        operator,
+        left.type_name(),
+        right.type_name()
+    );
+    error_at(span, ErrorKind::Type, message)
+}
+
+/// `value in container`.
+fn contains(container: &Object, value: &Object, span: Span) -> Eval<bool> {
+    match (container, value) {
+        (
+            Object::Range {
+                start,
+                end,
+                inclusive,
+            },
+            Object::Int(value),
+        ) => Ok(start <= value && (value < end || *inclusive && value == end)),
+        (Object::Array(elements), value) => Ok(elements.borrow().contains(value)),
+        (Object::Hash(fields), Object::String(key)) => Ok(fields.borrow().contains_key(&**key)),
+        (Object::String(container), Object::String(value)) => Ok(container.contains(&**value)),
+        (Object::String(container), Object::Char(value)) => Ok(container.contains(*value)),
+        (Object::Bytes(container), Object::Int(value)) => {
+            Ok(u8::try_from(*value).is_ok_and(|b| container.contains(&b)))
+        }
+        (container, value) => {
+            let message = format!(
+                "cannot look for {} in {}",
+                value.type_name(),
+                container.type_name()
+            );
+            Err(error_at(span, ErrorKind::Type, message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::ModuleLoader;
+    use crate::parser::parse;
+    use std::cell::RefCell;
+    use std::thread;
+
+    /// Collects what `puts` writes, for tests to read back.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn eval_source(input: &str) -> Result<Object, RuntimeError> {
+        match parse(input) {
+            Ok(program) => Evaluator::with_output(io::sink()).eval(&program),
+            Err(errors) => panic!("{}: parser errors:\n{}", input, errors),
+        }
+    }
+
+    #[test]
+    fn test_values() {
+        let tests = vec![
+            ("1 + 2 * 3", "7"),
+            ("2 ** 10 - 7 % 4", "1021"),
+            ("-(1 << 4) | 3", "-13"),
+            ("\"ab\" + \"cd\" == \"abcd\"", "true"),
+            ("'a' < 'b' && !(1 > 2)", "true"),
+            ("null ?? 5", "5"),
+            ("1 == \"1\"", "false"),
+            ("3 in 1..3", "false"),
+            ("3 in 1..=3", "true"),
+            ("let x = 2; let s = \"x is ${x * 2}\"; s", "x is 4"),
+            ("fn list(...xs) { xs } list(1, ...1..3, 'c', \"d\")", "[1, 1, 2, 'c', \"d\"]"),
+            ("fn list(...xs) { xs } let xs = list(1, 2, 3); xs[1] = 5; xs", "[1, 5, 3]"),
+            ("fn list(...xs) { xs } let xs = list(1, 2, 3); xs[1..3]", "[2, 3]"),
+            ("fn list(...xs) { xs } list(1, 2)[5]", "null"),
+            ("fn list(...xs) { xs } rest(push(list(1), 2))", "[2]"),
+            ("len(\"héllo\") + len(b\"ab\")", "7"),
+            ("\"hello\"[1..=3]", "ell"),
+            ("push(push(b\"\\x01\", 2), 255) + bytes(\"é\")", "b\"\\x01\\x02\\xff\\xc3\\xa9\""),
+            ("fn list(...xs) { xs } bytes(list(104, 105))", "b\"hi\""),
+            ("string(bytes(\"héllo\")) + string(1..3) + string('c')", "héllo1..3c"),
+            ("int(char(int('a') + 1)) + int(\"-5\")", "93"),
+            ("char(\"é\") == 'é'", "true"),
+            ("\"héllo\".bytes().len() + \"héllo\".len()", "11"),
+            ("b\"\".push(104).push(105).string()", "hi"),
+            ("fn list(...xs) { xs } list(1, 2).push(3).rest()", "[2, 3]"),
+            ("'a'.int().char().string() + 7.string()", "a7"),
+            ("let x = null; x?.len()", "null"),
+            ("let x = 1; x += 2; x *= 3; x", "9"),
+            ("let f = |x, y = x * 2| x + y; f(1) + f(1, 1)", "5"),
+            ("fn add(a, b) { a + b } 1 |> add(2)", "3"),
+            (
+                "fn counter() { let n = 0; fn() { n += 1; n } } let c = counter(); c(); c()",
+                "2",
+            ),
+            ("fn f() { g() } fn g() { 42 } f()", "42"),
+            ("let total = 0; for (i in 0..10) { if (i % 2 == 0) { continue; } if (i > 6) { break; } total += i; } total", "9"),
+            ("let i = 0; while (i < 3) { i += 1; } i", "3"),
+            ("fn f(n) { if (n > 0) { return \"positive\"; } \"other\" } f(1) + f(0)", "positiveother"),
+            ("match (5) { 0 => \"zero\", 1..=9 if false => \"never\", n if n > 3 => \"big\", _ => \"other\" }", "big"),
+            ("fn list(...xs) { xs } let [a, ..middle, z] = list(1, 2, 3, 4); middle + list(a, z)", "[2, 3, 1, 4]"),
+            ("fn list(...xs) { xs } let [a, b = 10] = list(1); a + b", "11"),
+            ("let s = \"\"; for (c in \"abc\") { s = \"${c}${s}\"; } s", "cba"),
+        ];
+
+        for (input, expected) in tests {
+            match eval_source(input) {
+                Ok(value) => assert_eq!(value.to_string(), expected, "{}", input),
+                Err(e) => panic!("{}: {}", input, e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let tests = vec![
+            ("1 / 0", "1:3: division by zero [division-by-zero]"),
+            ("let x = 5;\nx % (x - 5)", "2:3: division by zero [division-by-zero]"),
+            ("9223372036854775807 + 1", "1:21: 9223372036854775807 + 1 overflows [overflow]"),
+            ("1 << 64", "1:3: cannot shift by 64 [shift]"),
+            ("2 ** -1", "1:3: negative exponent -1 [negative-exponent]"),
+            ("1 + \"a\"", "1:3: cannot apply + to int and string [type]"),
+            ("let f = 1; f()", "1:12: int is not a function [type]"),
+            ("let x = 1; x.y", "1:14: int has no field y [type]"),
+            (
+                "fn f(a, b = 1) { a } f(1, 2, 3)",
+                "1:22: wrong number of arguments to fn f(a, b = 1): expected 1 to 2, found 3 [arity]",
+            ),
+            ("let x = 1; x.len()", "1:14: int has no method len [type]"),
+            ("len(1, 2)", "1:1: wrong number of arguments to len: expected 1, found 2 [arity]"),
+            ("push(b\"\", 256)", "1:1: 256 is not a byte; bytes are 0 to 255 [type]"),
+            ("string(b\"\\xff\")", "1:1: invalid UTF-8: invalid utf-8 sequence of 1 bytes from index 0 [type]"),
+            ("char(55296)", "1:1: 55296 is not a unicode code point [type]"),
+            ("int(\"12a\")", "1:1: cannot parse \"12a\" as an int [type]"),
+            ("len = 1", "1:1: cannot assign to const len [const]"),
+            ("fn list(...xs) { xs } list(1)[0..2]", "1:23: range 0..2 is out of bounds for length 1 [index]"),
+            ("fn list(...xs) { xs } let [a] = list(1, 2);", "1:28: cannot destructure [1, 2] as [a] [shape]"),
+            ("let x = 3;\nmatch (x) { 1 => 1 }", "2:8: no arm matches 3 [no-match]"),
+            ("throw \"bad\";", "1:1: bad [thrown]"),
+            (
+                "fn f() { g() } f(); let g = fn() { 1 };",
+                "1:10: g is used before its definition [name]",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match eval_source(input) {
+                Ok(value) => panic!("{}: expected an error, got {}", input, value),
+                Err(e) => assert_eq!(e.to_string(), expected, "{}", input),
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_catch() {
+        let tests = vec![
+            ("try { 1 / 0; } catch (e) { puts(e.kind, e.message, e.line, e.column); }", "division-by-zero\ndivision by zero\n1\n9\n"),
+            ("try { throw 42; } catch (e) { puts(e.message); } finally { puts(\"done\"); }", "42\ndone\n"),
+            ("try { puts(1); } finally { puts(2); }", "1\n2\n"),
+            ("try { try { -\"a\"; } finally { puts(\"inner\"); } } catch (e) { puts(e); }", "inner\n1:13: cannot apply - to string [type]\n"),
+            ("try { 1 + null; } catch (e) { try { throw e; } catch (again) { puts(again == e); } }", "true\n"),
+            ("fn f() { try { return 1; } finally { puts(\"finally\"); } } puts(f());", "finally\n1\n"),
+            ("let r = 0; for (i in 0..3) { try { r += 6 / (i - 1); } catch (e) { continue; } } puts(r);", "0\n"),
+            ("try { throw 1; } catch (e) { let {kind, message} = e; puts(kind, message); }", "thrown\n1\n"),
+        ];
+
+        for (input, expected) in tests {
+            let program = match parse(input) {
+                Ok(program) => program,
+                Err(errors) => panic!("{}: parser errors:\n{}", input, errors),
+            };
+            let output = Output::default();
+            if let Err(e) = Evaluator::with_output(output.clone()).eval(&program) {
+                panic!("{}: {}", input, e);
+            }
+            let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+            assert_eq!(written, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_results() {
+        let parse_row = "fn parse_row(n) { if (n < 0) { err(\"negative\") } else { ok(n * 2) } }\n";
+        let tests = vec![
+            ("ok(1)", "ok(1)"),
+            ("err(\"bad\")", "err(\"bad\")"),
+            ("fn f() { let x = ok(2)?; x + 1 } f()", "3"),
+            ("fn f() { let x = parse_row(-1)?; puts(\"unreachable\"); x } f()", "err(\"negative\")"),
+            ("fn f(a, b) { ok(parse_row(a)? + parse_row(b)?) } f(1, 2)", "ok(6)"),
+            ("parse_row(3).value", "6"),
+            ("parse_row(3).error", "null"),
+            ("parse_row(-3).error", "negative"),
+            (
+                "fn describe(r) { match (r) { {value} => \"ok ${value}\", {error} => \"err ${error}\" } } describe(ok(1)) + \", \" + describe(err(2))",
+                "ok 1, err 2",
+            ),
+            ("ok(1) == ok(1) && ok(1) != err(1)", "true"),
+            (
+                "fn f() { try { 1 / 0 } catch (e) { return err(e.kind); } } f()",
+                "err(\"division-by-zero\")",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let input = format!("{}{}", parse_row, input);
+            match eval_source(&input) {
+                Ok(value) => assert_eq!(value.to_string(), expected, "{}", input),
+                Err(e) => panic!("{}: {}", input, e),
+            }
+        }
+
+        match eval_source("fn f(x) { x? } f(1)") {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "1:11: `?` needs an ok or err value, found int [type]"
+            ),
+            Ok(value) => panic!("expected an error, got {}", value),
+        }
+    }
+
+    #[test]
+    fn test_result_round_trip() {
+        let program =
+            parse("fn half(n) { if (n % 2 == 0) { ok(n / 2) } else { err(\"odd\") } } half")
+                .expect("parses");
+        let mut evaluator = Evaluator::with_output(io::sink());
+        let half = evaluator.eval(&program).expect("evaluates");
+
+        let tests = vec![(4, Ok(Object::Int(2))), (3, Err(Object::from("odd")))];
+        for (n, expected) in tests {
+            let result = evaluator.call(&half, vec![Object::Int(n)]).expect("runs");
+            assert_eq!(result.clone().into_result(), Some(expected.clone()));
+            assert_eq!(Object::from(expected), result);
+        }
+        assert_eq!(Object::Int(1).into_result(), None);
+    }
+
+    #[test]
+    fn test_hash_methods() {
+        let mut evaluator = Evaluator::with_output(io::sink());
+        let mut eval = |input| match parse(input) {
+            Ok(program) => evaluator.eval(&program).expect("evaluates"),
+            Err(errors) => panic!("{}: parser errors:\n{}", input, errors),
+        };
+        let double = eval("fn double(x) { x * 2 } double");
+        let call = eval("fn(h) { h.double(h.len()) + h.n }");
+
+        let mut fields = BTreeMap::new();
+        fields.insert("double".to_string(), double);
+        fields.insert("n".to_string(), Object::Int(1));
+        let result = evaluator.call(&call, vec![Object::from(fields)]);
+        assert_eq!(result, Ok(Object::Int(5)));
+    }
+
+    #[test]
+    fn test_modules() {
+        let mut loader = ModuleLoader::new();
+        let modules = [
+            (
+                "main",
+                "import \"strings\" as s;\nimport \"counter\" as c;\ns.shout(s.greeting) + string(c.bump())",
+            ),
+            (
+                "strings",
+                "import \"counter\" as counter;\nexport fn shout(x) { counter.bump(); x + \"!\" }\nexport const greeting = \"hi\";\nexport fn fail() { 1 / 0 }",
+            ),
+            ("counter", "let n = 0;\nexport fn bump() { n += 1; n }"),
+            ("failing", "import \"strings\" as s;\ns.fail()"),
+        ];
+        for (path, source) in &modules {
+            loader.add_virtual_module(*path, *source);
+        }
+
+        let mut evaluator = Evaluator::with_output(io::sink());
+        let main = loader.load("main").expect("loads");
+        // `counter` is imported twice but runs once, so both see one count.
+        assert_eq!(evaluator.run_module(&main), Ok(Object::from("hi!2")));
+
+        let failing = loader.load("failing").expect("loads");
+        let error = Evaluator::with_output(io::sink())
+            .run_module(&failing)
+            .expect_err("fails");
+        assert_eq!(
+            error.to_string(),
+            "4:22: division by zero [division-by-zero]"
+        );
+        assert_eq!(error.module, Some(ModuleId::Virtual("strings".to_string())));
+
+        match eval_source("import \"strings\" as s;") {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "1:21: cannot import \"strings\" without a module loader [import]"
+            ),
+            Ok(value) => panic!("expected an error, got {}", value),
+        }
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let input = "fn deep(n) { if (n == 0) { 0 } else { 1 + deep(n - 1) } }\n\
+                     let ok = deep(500);\n\
+                     let r = 0;\n\
+                     try { deep(5000); } catch (e) { r = ok + len(e.kind); }\n\
+                     r";
+        let result = thread::Builder::new()
+            .stack_size(Evaluator::STACK_SIZE)
+            .spawn(move || eval_source(input).map(|value| value.to_string()))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(result, Ok("509".to_string()));
+    }
+}
@@ -0,0 +1,326 @@
+use super::environment::Environment;
+use super::evaluator::{Eval, Evaluator};
+use crate::lexer::Span;
+use crate::module::ModuleId;
+use crate::parser::ast;
+use crate::resolver::Resolution;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use thiserror::Error;
+
+/// A runtime value. Arrays and hashes are shared: assigning one to another
+/// name, or passing it to a function, does not copy it.
+#[derive(Debug, Clone)]
+pub enum Object {
+    Int(i64),
+    Bool(bool),
+    String(Rc<str>),
+    Char(char),
+    Bytes(Rc<[u8]>),
+    Null,
+    /// `start..end`, or `start..=end` when `inclusive`. A range is never
+    /// expanded unless every element is asked for.
+    Range {
+        start: i64,
+        end: i64,
+        inclusive: bool,
+    },
+    Array(Rc<RefCell<Vec<Object>>>),
+    /// Keys are kept sorted, so iterating over a hash is deterministic.
+    Hash(Rc<RefCell<BTreeMap<String, Object>>>),
+    Function(Rc<Function>),
+    Builtin(Builtin),
+    /// An error that was thrown, as bound by `catch`.
+    Error(Rc<RuntimeError>),
+    /// `ok(value)`, which `?` unwraps.
+    Ok(Box<Object>),
+    /// `err(error)`, which `?` returns from the enclosing function.
+    Err(Box<Object>),
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Int(_) => "int",
+            Object::Bool(_) => "bool",
+            Object::String(_) => "string",
+            Object::Char(_) => "char",
+            Object::Bytes(_) => "bytes",
+            Object::Null => "null",
+            Object::Range { .. } => "range",
+            Object::Array(_) => "array",
+            Object::Hash(_) => "hash",
+            Object::Function(_) | Object::Builtin(_) => "function",
+            Object::Error(_) => "error",
+            Object::Ok(_) | Object::Err(_) => "result",
+        }
+    }
+
+    /// The `Result` an `ok` or `err` value stands for, or `None` if the
+    /// value is neither.
+    pub fn into_result(self) -> Option<Result<Object, Object>> {
+        match self {
+            Object::Ok(value) => Some(Ok(*value)),
+            Object::Err(error) => Some(Err(*error)),
+            _ => None,
+        }
+    }
+
+    /// `false` and `null` are falsy; everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Bool(false) | Object::Null)
+    }
+
+    /// How the value is written inside an array or hash: like `Display`, but
+    /// with strings and chars quoted.
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::String(value) => format!("{:?}", value),
+            Object::Char(value) => format!("{:?}", value),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl PartialEq for Object {
+    /// Values are compared by content, except functions and errors, which
+    /// are only equal to themselves. Values of different types are unequal.
+    fn eq(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Object::Int(a), Object::Int(b)) => a == b,
+            (Object::Bool(a), Object::Bool(b)) => a == b,
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Char(a), Object::Char(b)) => a == b,
+            (Object::Bytes(a), Object::Bytes(b)) => a == b,
+            (Object::Null, Object::Null) => true,
+            (
+                Object::Range {
+                    start: a_start,
+                    end: a_end,
+                    inclusive: a_inclusive,
+                },
+                Object::Range {
+                    start: b_start,
+                    end: b_end,
+                    inclusive: b_inclusive,
+                },
+            ) => a_start == b_start && a_end == b_end && a_inclusive == b_inclusive,
+            (Object::Array(a), Object::Array(b)) => Rc::ptr_eq(a, b) || *a.borrow() == *b.borrow(),
+            (Object::Hash(a), Object::Hash(b)) => Rc::ptr_eq(a, b) || *a.borrow() == *b.borrow(),
+            (Object::Function(a), Object::Function(b)) => Rc::ptr_eq(a, b),
+            (Object::Builtin(a), Object::Builtin(b)) => a.name == b.name,
+            (Object::Error(a), Object::Error(b)) => Rc::ptr_eq(a, b),
+            (Object::Ok(a), Object::Ok(b)) | (Object::Err(a), Object::Err(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Object::Int(value) => write!(f, "{}", value),
+            Object::Bool(value) => write!(f, "{}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Char(value) => write!(f, "{}", value),
+            Object::Bytes(value) => write!(f, "b\"{}\"", value.escape_ascii()),
+            Object::Null => write!(f, "null"),
+            Object::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let operator = if *inclusive { "..=" } else { ".." };
+                write!(f, "{}{}{}", start, operator, end)
+            }
+            Object::Array(elements) => write!(
+                f,
+                "[{}]",
+                elements
+                    .borrow()
+                    .iter()
+                    .map(|e| e.inspect())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Object::Hash(fields) => write!(
+                f,
+                "{{{}}}",
+                fields
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value.inspect()))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Object::Function(function) => write!(f, "{}", function),
+            Object::Builtin(builtin) => write!(f, "builtin {}", builtin.name),
+            Object::Error(error) => write!(f, "{}", error),
+            Object::Ok(value) => write!(f, "ok({})", value.inspect()),
+            Object::Err(error) => write!(f, "err({})", error.inspect()),
+        }
+    }
+}
+
+impl From<i64> for Object {
+    fn from(value: i64) -> Object {
+        Object::Int(value)
+    }
+}
+
+impl From<bool> for Object {
+    fn from(value: bool) -> Object {
+        Object::Bool(value)
+    }
+}
+
+impl From<&str> for Object {
+    fn from(value: &str) -> Object {
+        Object::String(value.into())
+    }
+}
+
+impl From<Vec<Object>> for Object {
+    fn from(elements: Vec<Object>) -> Object {
+        Object::Array(Rc::new(RefCell::new(elements)))
+    }
+}
+
+impl From<BTreeMap<String, Object>> for Object {
+    fn from(fields: BTreeMap<String, Object>) -> Object {
+        Object::Hash(Rc::new(RefCell::new(fields)))
+    }
+}
+
+impl From<Result<Object, Object>> for Object {
+    fn from(result: Result<Object, Object>) -> Object {
+        match result {
+            Ok(value) => Object::Ok(Box::new(value)),
+            Err(error) => Object::Err(Box::new(error)),
+        }
+    }
+}
+
+/// A closure: a function literal, lambda or `fn` declaration together with
+/// the scope it was created in.
+pub struct Function {
+    pub(super) name: Option<String>,
+    pub(super) parameters: Vec<ast::Parameter>,
+    pub(super) body: Body,
+    pub(super) environment: Rc<Environment>,
+    /// The resolution of the program the function was written in, which its
+    /// identifiers are looked up with.
+    pub(super) resolution: Rc<Resolution>,
+    pub(super) module: Option<Rc<ModuleId>>,
+}
+
+pub(super) enum Body {
+    Block(ast::BlockStatement),
+    Expression(ast::Expression),
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        match &self.name {
+            Some(name) => write!(f, "fn {}({})", name, parameters),
+            None => write!(f, "fn({})", parameters),
+        }
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+pub(super) type BuiltinFunction = fn(&mut Evaluator, Vec<Object>) -> Eval<Object>;
+
+/// A function implemented in Rust, such as `len`.
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub(super) name: &'static str,
+    pub(super) function: BuiltinFunction,
+}
+
+impl Builtin {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "builtin {}", self.name)
+    }
+}
+
+/// An error raised while running a program, whether by `throw` or by a
+/// failing operation. Scripts can catch it with `try`/`catch`.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{span}: {message} [{}]", .kind.name())]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Where the error was raised, as near as the AST records it.
+    pub span: Span,
+    /// The module `span` is in, when the program was run with
+    /// `Evaluator::run_module`.
+    pub module: Option<ModuleId>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorKind {
+    /// A value passed to `throw`.
+    Thrown,
+    Type,
+    DivisionByZero,
+    Overflow,
+    NegativeExponent,
+    Shift,
+    Arity,
+    /// A name with no value, either unknown or used before its definition.
+    Name,
+    Const,
+    Index,
+    /// No arm of a `match` accepted the value.
+    NoMatch,
+    /// A value could not be destructured by a `let` pattern.
+    Shape,
+    Recursion,
+    /// An `import` that could not be loaded.
+    Import,
+    /// Output could not be written.
+    Io,
+}
+
+impl ErrorKind {
+    /// The name scripts see as the error's `kind`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Thrown => "thrown",
+            ErrorKind::Type => "type",
+            ErrorKind::DivisionByZero => "division-by-zero",
+            ErrorKind::Overflow => "overflow",
+            ErrorKind::NegativeExponent => "negative-exponent",
+            ErrorKind::Shift => "shift",
+            ErrorKind::Arity => "arity",
+            ErrorKind::Name => "name",
+            ErrorKind::Const => "const",
+            ErrorKind::Index => "index",
+            ErrorKind::NoMatch => "no-match",
+            ErrorKind::Shape => "shape",
+            ErrorKind::Recursion => "recursion",
+            ErrorKind::Import => "import",
+            ErrorKind::Io => "io",
+        }
+    }
+}
@@ -1,4 +1,10 @@
+#[allow(clippy::module_inception)]
 mod lexer;
+mod span;
 mod token;
 
-pub use self::{lexer::Lexer, token::Token};
+pub use self::{
+    lexer::{Lexer, KEYWORDS},
+    span::{Position, Span},
+    token::{TemplatePart, Token},
+};
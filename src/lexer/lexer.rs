@@ -1,27 +1,83 @@
+use std::rc::Rc;
 use std::str;
 
-use crate::lexer::Token;
+use crate::lexer::{Position, Span, TemplatePart, Token};
+
+/// Every word that `lookup_ident` turns into a keyword token.
+pub const KEYWORDS: &[&str] = &[
+    "let", "const", "fn", "true", "false", "if", "else", "return", "while", "for", "in", "break",
+    "continue", "null", "match", "import", "export", "as", "try", "catch", "finally", "throw",
+];
 
 pub struct Lexer {
-    input: String,
+    input: Rc<str>,
     position: usize,
     read_position: usize,
     ch: u8,
+    line: usize,
+    column: usize,
+    span: Span,
+    comments: Vec<(Position, String)>,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Self {
+        Lexer::starting_at(input, Position::default())
+    }
+
+    /// A lexer for `input` taken from a larger source at `start`, so that
+    /// spans point into the larger source.
+    pub fn starting_at(input: &str, start: Position) -> Self {
         let mut lexer = Lexer {
-            input: input.to_string(),
+            input: Rc::from(input),
             position: 0,
             read_position: 0,
             ch: 0,
+            line: start.line,
+            column: start.column - 1,
+            span: Span::default(),
+            comments: vec![],
         };
         lexer.read_char();
         lexer
     }
 
+    /// A copy of this lexer at the same place, for looking ahead without
+    /// consuming tokens. Comments the copy skips are not recorded here.
+    pub fn fork(&self) -> Lexer {
+        Lexer {
+            input: Rc::clone(&self.input),
+            comments: vec![],
+            ..*self
+        }
+    }
+
+    /// The span of the token most recently returned by `next_token`.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The `//` comments skipped so far, with where each starts. The text
+    /// excludes the slashes.
+    pub fn comments(&self) -> &[(Position, String)] {
+        &self.comments
+    }
+
+    fn cursor(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
     fn read_char(&mut self) {
+        if self.ch == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
         if self.read_position >= self.input.len() {
             self.ch = 0;
         } else {
@@ -33,17 +89,47 @@ impl Lexer {
 
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        let start = self.cursor();
+        let tok = self.read_token();
+        self.span = Span {
+            start,
+            end: self.cursor(),
+        };
+        tok
+    }
+
+    fn read_token(&mut self) -> Token {
         let tok = match self.ch {
-            b'=' => {
-                if self.peek_char() == b'=' {
+            b'=' => match self.peek_char() {
+                b'=' => {
                     self.read_char();
                     Token::Eq
+                }
+                b'>' => {
+                    self.read_char();
+                    Token::FatArrow
+                }
+                _ => Token::Assign,
+            },
+            b'+' => {
+                if self.peek_char() == b'=' {
+                    self.read_char();
+                    Token::PlusAssign
                 } else {
-                    Token::Assign
+                    Token::Plus
                 }
             }
-            b'+' => Token::Plus,
-            b'-' => Token::Minus,
+            b'-' => match self.peek_char() {
+                b'=' => {
+                    self.read_char();
+                    Token::MinusAssign
+                }
+                b'>' => {
+                    self.read_char();
+                    Token::Arrow
+                }
+                _ => Token::Minus,
+            },
             b'!' => {
                 if self.peek_char() == b'=' {
                     self.read_char();
@@ -52,16 +138,114 @@ impl Lexer {
                     Token::Bang
                 }
             }
-            b'/' => Token::Slash,
-            b'*' => Token::Asterisk,
-            b'<' => Token::Lt,
-            b'>' => Token::Gt,
+            b'/' => {
+                if self.peek_char() == b'=' {
+                    self.read_char();
+                    Token::SlashAssign
+                } else {
+                    Token::Slash
+                }
+            }
+            b'*' => match self.peek_char() {
+                b'*' => {
+                    self.read_char();
+                    Token::Power
+                }
+                b'=' => {
+                    self.read_char();
+                    Token::AsteriskAssign
+                }
+                _ => Token::Asterisk,
+            },
+            b'%' => Token::Percent,
+            b'<' => match self.peek_char() {
+                b'=' => {
+                    self.read_char();
+                    Token::LtEq
+                }
+                b'<' => {
+                    self.read_char();
+                    Token::ShiftLeft
+                }
+                _ => Token::Lt,
+            },
+            b'>' => match self.peek_char() {
+                b'=' => {
+                    self.read_char();
+                    Token::GtEq
+                }
+                b'>' => {
+                    self.read_char();
+                    Token::ShiftRight
+                }
+                _ => Token::Gt,
+            },
+            b'&' => {
+                if self.peek_char() == b'&' {
+                    self.read_char();
+                    Token::And
+                } else {
+                    Token::Ampersand
+                }
+            }
+            b'|' => match self.peek_char() {
+                b'|' => {
+                    self.read_char();
+                    Token::Or
+                }
+                b'>' => {
+                    self.read_char();
+                    Token::Pipeline
+                }
+                _ => Token::Pipe,
+            },
+            b'?' => match self.peek_char() {
+                b'.' => {
+                    self.read_char();
+                    Token::QuestionDot
+                }
+                b'?' => {
+                    self.read_char();
+                    Token::Coalesce
+                }
+                _ => Token::Question,
+            },
+            b'^' => Token::Caret,
+            b'~' => Token::Tilde,
             b';' => Token::Semicolon,
             b'(' => Token::Lparen,
             b')' => Token::Rparen,
             b',' => Token::Comma,
+            b':' => Token::Colon,
+            b'.' => {
+                if self.peek_char() == b'.' {
+                    self.read_char();
+                    match self.peek_char() {
+                        b'=' => {
+                            self.read_char();
+                            Token::DotDotEq
+                        }
+                        b'.' => {
+                            self.read_char();
+                            Token::Ellipsis
+                        }
+                        _ => Token::DotDot,
+                    }
+                } else {
+                    Token::Dot
+                }
+            }
             b'{' => Token::Lbrace,
             b'}' => Token::Rbrace,
+            b'"' if self.peek_char() == b'"' && self.peek_nth_char(1) == b'"' => {
+                return self.read_multi_line_string()
+            }
+            b'"' => return self.read_string(),
+            b'r' if matches!(self.peek_char(), b'"' | b'#') => return self.read_raw_string(),
+            b'b' if self.peek_char() == b'"' => return self.read_byte_string(),
+            b'\'' => return self.read_char_literal(),
+            b'[' => Token::Lbracket,
+            b']' => Token::Rbracket,
             0 => Token::EOF,
             _ => {
                 if self.ch.is_ascii_alphabetic() || self.ch == b'_' {
@@ -91,7 +275,10 @@ impl Lexer {
     fn read_number(&mut self) -> Token {
         let from = self.position;
 
-        while self.ch.is_ascii_digit() {
+        // Radix prefixes, digit separators and stray letters are all kept in
+        // the literal so the parser can report exactly what is wrong with it.
+        // A `.` is never consumed here, so `1..3` still lexes as a range.
+        while self.ch.is_ascii_alphanumeric() || self.ch == b'_' {
             self.read_char();
         }
 
@@ -100,37 +287,395 @@ impl Lexer {
         Token::Int(parsed.to_string())
     }
 
+    fn read_string(&mut self) -> Token {
+        let mut parts = vec![];
+        let mut literal = vec![];
+        let mut malformed = None;
+        self.read_char();
+
+        loop {
+            match self.ch {
+                0 => return Token::Illegal,
+                b'"' => break,
+                b'\\' => {
+                    self.read_char();
+                    match self.ch {
+                        b'n' => literal.push(b'\n'),
+                        b't' => literal.push(b'\t'),
+                        b'r' => literal.push(b'\r'),
+                        b'0' => literal.push(b'\0'),
+                        b'\\' | b'"' | b'$' => literal.push(self.ch),
+                        0 => return Token::Illegal,
+                        _ => {
+                            let escape = self.current_char();
+                            malformed.get_or_insert(format!("unknown escape \\{}", escape));
+                        }
+                    }
+                    self.read_char();
+                }
+                b'$' if self.peek_char() == b'{' => {
+                    self.read_char();
+                    self.read_char();
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(bytes_to_string(&literal)));
+                        literal.clear();
+                    }
+                    let start = self.cursor();
+                    match self.read_interpolation() {
+                        Some(source) => parts.push(TemplatePart::Interpolation { source, start }),
+                        None => return Token::Illegal,
+                    }
+                }
+                ch => {
+                    literal.push(ch);
+                    self.read_char();
+                }
+            }
+        }
+        self.read_char();
+
+        if let Some(reason) = malformed {
+            return Token::Invalid(reason);
+        }
+        if parts.is_empty() {
+            return Token::String(bytes_to_string(&literal));
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(bytes_to_string(&literal)));
+        }
+        Token::Template(parts)
+    }
+
+    fn read_char_literal(&mut self) -> Token {
+        self.read_char();
+        if self.ch == b'\'' {
+            self.read_char();
+            return Token::Invalid("empty char literal".to_string());
+        }
+
+        let ch = match self.ch {
+            0 | b'\n' => Err("unterminated char literal".to_string()),
+            b'\\' => {
+                self.read_char();
+                self.read_escape().and_then(|escape| match escape {
+                    Escape::Byte(byte) if byte.is_ascii() => Ok(byte as char),
+                    Escape::Byte(byte) => Err(format!(
+                        "\\x{:02X} is not ASCII; use \\u{{{:X}}} in a char literal",
+                        byte, byte
+                    )),
+                    Escape::Unicode(ch) => Ok(ch),
+                })
+            }
+            _ => {
+                let ch = self.current_char();
+                for _ in 1..ch.len_utf8() {
+                    self.read_char();
+                }
+                Ok(ch)
+            }
+        };
+        self.read_char();
+
+        match ch {
+            Ok(ch) if self.ch == b'\'' => {
+                self.read_char();
+                Token::Char(ch)
+            }
+            _ => {
+                while !matches!(self.ch, 0 | b'\'' | b'\n') {
+                    self.read_char();
+                }
+                let closed = self.ch == b'\'';
+                if closed {
+                    self.read_char();
+                }
+                match ch {
+                    Err(reason) => Token::Invalid(reason),
+                    Ok(_) if closed => {
+                        Token::Invalid("more than one character in char literal".to_string())
+                    }
+                    Ok(_) => Token::Invalid("unterminated char literal".to_string()),
+                }
+            }
+        }
+    }
+
+    fn read_byte_string(&mut self) -> Token {
+        self.read_char();
+        self.read_char();
+        let mut bytes = vec![];
+        let mut malformed = None;
+
+        loop {
+            match self.ch {
+                0 => return Token::Illegal,
+                b'"' => break,
+                b'\\' => {
+                    self.read_char();
+                    match self.read_escape() {
+                        Ok(Escape::Byte(byte)) => bytes.push(byte),
+                        Ok(Escape::Unicode(ch)) => {
+                            let reason = format!("unicode escape for {:?} in byte string", ch);
+                            malformed.get_or_insert(reason);
+                        }
+                        Err(reason) => {
+                            malformed.get_or_insert(reason);
+                        }
+                    }
+                }
+                ch if ch.is_ascii() => bytes.push(ch),
+                _ => {
+                    let ch = self.current_char();
+                    for _ in 1..ch.len_utf8() {
+                        self.read_char();
+                    }
+                    malformed.get_or_insert(format!("non-ASCII character {:?} in byte string", ch));
+                }
+            }
+            self.read_char();
+        }
+        self.read_char();
+
+        match malformed {
+            Some(reason) => Token::Invalid(reason),
+            None => Token::ByteString(bytes),
+        }
+    }
+
+    /// Reads the escape sequence after a `\\` in a char or byte string literal,
+    /// leaving the lexer on its last character.
+    fn read_escape(&mut self) -> Result<Escape, String> {
+        Ok(match self.ch {
+            b'n' => Escape::Byte(b'\n'),
+            b't' => Escape::Byte(b'\t'),
+            b'r' => Escape::Byte(b'\r'),
+            b'0' => Escape::Byte(0),
+            b'\\' | b'\'' | b'"' => Escape::Byte(self.ch),
+            b'x' => {
+                let byte = self
+                    .input
+                    .get(self.read_position..self.read_position + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| "\\x must be followed by two hex digits".to_string())?;
+                self.read_char();
+                self.read_char();
+                Escape::Byte(byte)
+            }
+            b'u' if self.peek_char() == b'{' => {
+                self.read_char();
+                let from = self.read_position;
+                while self.peek_char().is_ascii_hexdigit() {
+                    self.read_char();
+                }
+                let digits = self.input[from..self.read_position].to_string();
+                let invalid = || format!("invalid unicode escape \\u{{{}}}", digits);
+                if self.peek_char() != b'}' {
+                    return Err(invalid());
+                }
+                let ch = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32);
+                self.read_char();
+                Escape::Unicode(ch.ok_or_else(invalid)?)
+            }
+            _ => return Err(format!("unknown escape \\{}", self.current_char())),
+        })
+    }
+
+    /// Reads `r"..."` or `r#"..."#`. Nothing is escaped inside; the string ends
+    /// at the first `"` followed by as many `#` as opened it.
+    fn read_raw_string(&mut self) -> Token {
+        self.read_char();
+        let mut hashes = 0;
+        while self.ch == b'#' {
+            hashes += 1;
+            self.read_char();
+        }
+        if self.ch != b'"' {
+            return Token::Illegal;
+        }
+        self.read_char();
+
+        let from = self.position;
+        loop {
+            match self.ch {
+                0 => return Token::Illegal,
+                b'"' if (0..hashes).all(|n| self.peek_nth_char(n) == b'#') => break,
+                _ => self.read_char(),
+            }
+        }
+        let value = self.input[from..self.position].to_string();
+        for _ in 0..=hashes {
+            self.read_char();
+        }
+
+        Token::RawString { value, hashes }
+    }
+
+    /// Reads a `"""..."""` string. Its text is taken literally, except that
+    /// the line break after the opening quotes, the line holding the closing
+    /// quotes and the indentation common to all non-blank lines are removed.
+    fn read_multi_line_string(&mut self) -> Token {
+        for _ in 0..3 {
+            self.read_char();
+        }
+
+        let from = self.position;
+        while !(self.ch == b'"' && self.peek_char() == b'"' && self.peek_nth_char(1) == b'"') {
+            if self.ch == 0 {
+                return Token::Illegal;
+            }
+            self.read_char();
+        }
+        let text = self.input[from..self.position].to_string();
+        for _ in 0..3 {
+            self.read_char();
+        }
+
+        let text = text
+            .strip_prefix("\r\n")
+            .or_else(|| text.strip_prefix('\n'))
+            .unwrap_or(&text);
+        let mut lines = text.lines().collect::<Vec<_>>();
+        if text.ends_with('\n') {
+            lines.push("");
+        }
+        if lines.len() > 1 && lines.last().is_some_and(|l| l.trim().is_empty()) {
+            lines.pop();
+        }
+
+        // Tabs and spaces are not interchangeable, so the indentation removed
+        // is the longest run of whitespace every non-blank line starts with.
+        let mut indent: Option<&str> = None;
+        for line in lines.iter().filter(|l| !l.trim().is_empty()) {
+            let whitespace = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+            let common = match indent {
+                Some(indent) => indent
+                    .bytes()
+                    .zip(whitespace.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count(),
+                None => whitespace.len(),
+            };
+            indent = Some(&whitespace[..common]);
+        }
+        let indent = indent.unwrap_or("");
+        let value = lines
+            .iter()
+            .map(|l| l.strip_prefix(indent).unwrap_or(""))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Token::MultiLineString(value)
+    }
+
+    /// Reads the source of a `${...}` interpolation up to its matching `}`,
+    /// skipping over nested braces and string literals.
+    fn read_interpolation(&mut self) -> Option<String> {
+        let from = self.position;
+        let mut depth = 0;
+
+        loop {
+            match self.ch {
+                0 => return None,
+                b'{' => depth += 1,
+                b'}' if depth == 0 => break,
+                b'}' => depth -= 1,
+                b'"' => {
+                    if matches!(self.read_string(), Token::Illegal | Token::Invalid(_)) {
+                        return None;
+                    }
+                    continue;
+                }
+                b'\'' => {
+                    if matches!(self.read_char_literal(), Token::Illegal | Token::Invalid(_)) {
+                        return None;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            self.read_char();
+        }
+
+        let source = self.input[from..self.position].to_string();
+        self.read_char();
+        Some(source)
+    }
+
     fn lookup_ident(&self, ident: &str) -> Token {
         match ident {
             "let" => Token::Let,
+            "const" => Token::Const,
             "fn" => Token::Function,
             "true" => Token::True,
             "false" => Token::False,
             "if" => Token::If,
             "else" => Token::Else,
             "return" => Token::Return,
+            "while" => Token::While,
+            "for" => Token::For,
+            "in" => Token::In,
+            "break" => Token::Break,
+            "continue" => Token::Continue,
+            "null" => Token::Null,
+            "match" => Token::Match,
+            "import" => Token::Import,
+            "export" => Token::Export,
+            "as" => Token::As,
+            "try" => Token::Try,
+            "catch" => Token::Catch,
+            "finally" => Token::Finally,
+            "throw" => Token::Throw,
             ident => Token::Ident(ident.to_string()),
         }
     }
 
+    /// Skips whitespace and `//` comments, which run to the end of the line.
     fn skip_whitespace(&mut self) {
         loop {
             match self.ch {
                 b' ' | b'\t' | b'\n' | b'\r' => self.read_char(),
+                b'/' if self.peek_char() == b'/' => {
+                    let (start, position) = (self.cursor(), self.position);
+                    while !matches!(self.ch, b'\n' | 0) {
+                        self.read_char();
+                    }
+                    let text = self.input[position + 2..self.position].to_string();
+                    self.comments.push((start, text));
+                }
                 _ => break,
             }
         }
     }
 
+    /// The character starting at the current byte, which may span several.
+    fn current_char(&self) -> char {
+        self.input[self.position..].chars().next().unwrap_or('\0')
+    }
+
     fn peek_char(&self) -> u8 {
-        if self.read_position >= self.input.len() {
+        self.peek_nth_char(0)
+    }
+
+    fn peek_nth_char(&self, n: usize) -> u8 {
+        if self.read_position + n >= self.input.len() {
             0
         } else {
-            self.input.as_bytes()[self.read_position]
+            self.input.as_bytes()[self.read_position + n]
         }
     }
 }
 
+enum Escape {
+    Byte(u8),
+    Unicode(char),
+}
+
+fn bytes_to_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -156,6 +701,22 @@ mod tests {
 
         10 == 10;
         10 != 9;
+        a <= b >= c && d || e % 2;
+        ~a & b | c ^ d << 1 >> 2;
+        2 ** 3 * 4;
+        x = arr[0] += obj.k -= 1 *= 2 /= 3;
+        while for in break continue
+        0..10 a..=b c.d
+        a?.b ?? null
+        match (x) { _ => 1 }
+        {kind: k}
+        const
+        xs |> f
+        fn(head, ...tail) |x| x
+        import "lib/strings" as s; export
+        try catch finally throw
+        f(x)?;
+        fn(a: int) -> bool
         "#;
         let tests = vec![
             Token::Let,
@@ -231,6 +792,387 @@ mod tests {
             Token::Neq,
             Token::Int("9".to_string()),
             Token::Semicolon,
+            Token::Ident("a".to_string()),
+            Token::LtEq,
+            Token::Ident("b".to_string()),
+            Token::GtEq,
+            Token::Ident("c".to_string()),
+            Token::And,
+            Token::Ident("d".to_string()),
+            Token::Or,
+            Token::Ident("e".to_string()),
+            Token::Percent,
+            Token::Int("2".to_string()),
+            Token::Semicolon,
+            Token::Tilde,
+            Token::Ident("a".to_string()),
+            Token::Ampersand,
+            Token::Ident("b".to_string()),
+            Token::Pipe,
+            Token::Ident("c".to_string()),
+            Token::Caret,
+            Token::Ident("d".to_string()),
+            Token::ShiftLeft,
+            Token::Int("1".to_string()),
+            Token::ShiftRight,
+            Token::Int("2".to_string()),
+            Token::Semicolon,
+            Token::Int("2".to_string()),
+            Token::Power,
+            Token::Int("3".to_string()),
+            Token::Asterisk,
+            Token::Int("4".to_string()),
+            Token::Semicolon,
+            Token::Ident("x".to_string()),
+            Token::Assign,
+            Token::Ident("arr".to_string()),
+            Token::Lbracket,
+            Token::Int("0".to_string()),
+            Token::Rbracket,
+            Token::PlusAssign,
+            Token::Ident("obj".to_string()),
+            Token::Dot,
+            Token::Ident("k".to_string()),
+            Token::MinusAssign,
+            Token::Int("1".to_string()),
+            Token::AsteriskAssign,
+            Token::Int("2".to_string()),
+            Token::SlashAssign,
+            Token::Int("3".to_string()),
+            Token::Semicolon,
+            Token::While,
+            Token::For,
+            Token::In,
+            Token::Break,
+            Token::Continue,
+            Token::Int("0".to_string()),
+            Token::DotDot,
+            Token::Int("10".to_string()),
+            Token::Ident("a".to_string()),
+            Token::DotDotEq,
+            Token::Ident("b".to_string()),
+            Token::Ident("c".to_string()),
+            Token::Dot,
+            Token::Ident("d".to_string()),
+            Token::Ident("a".to_string()),
+            Token::QuestionDot,
+            Token::Ident("b".to_string()),
+            Token::Coalesce,
+            Token::Null,
+            Token::Match,
+            Token::Lparen,
+            Token::Ident("x".to_string()),
+            Token::Rparen,
+            Token::Lbrace,
+            Token::Ident("_".to_string()),
+            Token::FatArrow,
+            Token::Int("1".to_string()),
+            Token::Rbrace,
+            Token::Lbrace,
+            Token::Ident("kind".to_string()),
+            Token::Colon,
+            Token::Ident("k".to_string()),
+            Token::Rbrace,
+            Token::Const,
+            Token::Ident("xs".to_string()),
+            Token::Pipeline,
+            Token::Ident("f".to_string()),
+            Token::Function,
+            Token::Lparen,
+            Token::Ident("head".to_string()),
+            Token::Comma,
+            Token::Ellipsis,
+            Token::Ident("tail".to_string()),
+            Token::Rparen,
+            Token::Pipe,
+            Token::Ident("x".to_string()),
+            Token::Pipe,
+            Token::Ident("x".to_string()),
+            Token::Import,
+            Token::String("lib/strings".to_string()),
+            Token::As,
+            Token::Ident("s".to_string()),
+            Token::Semicolon,
+            Token::Export,
+            Token::Try,
+            Token::Catch,
+            Token::Finally,
+            Token::Throw,
+            Token::Ident("f".to_string()),
+            Token::Lparen,
+            Token::Ident("x".to_string()),
+            Token::Rparen,
+            Token::Question,
+            Token::Semicolon,
+            Token::Function,
+            Token::Lparen,
+            Token::Ident("a".to_string()),
+            Token::Colon,
+            Token::Ident("int".to_string()),
+            Token::Rparen,
+            Token::Arrow,
+            Token::Ident("bool".to_string()),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+        for t in tests {
+            let tok = lexer.next_token();
+            assert_eq!(t, tok);
+        }
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let input = "let x = 10; // ten\n  x += 1;// x /= 2;\n// done";
+        let tests = vec![
+            (Token::Let, (1, 1), (1, 4)),
+            (Token::Ident("x".to_string()), (1, 5), (1, 6)),
+            (Token::Assign, (1, 7), (1, 8)),
+            (Token::Int("10".to_string()), (1, 9), (1, 11)),
+            (Token::Semicolon, (1, 11), (1, 12)),
+            (Token::Ident("x".to_string()), (2, 3), (2, 4)),
+            (Token::PlusAssign, (2, 5), (2, 7)),
+            (Token::Int("1".to_string()), (2, 8), (2, 9)),
+            (Token::Semicolon, (2, 9), (2, 10)),
+        ];
+        let mut lexer = Lexer::new(input);
+        for (t, (start_line, start_column), (end_line, end_column)) in tests {
+            let tok = lexer.next_token();
+            assert_eq!(t, tok);
+            assert_eq!(
+                lexer.span(),
+                Span {
+                    start: Position {
+                        line: start_line,
+                        column: start_column
+                    },
+                    end: Position {
+                        line: end_line,
+                        column: end_column
+                    },
+                }
+            );
+        }
+        assert_eq!(lexer.next_token(), Token::EOF);
+
+        let comments: Vec<(usize, usize, &str)> = lexer
+            .comments()
+            .iter()
+            .map(|(start, text)| (start.line, start.column, text.as_str()))
+            .collect();
+        assert_eq!(
+            comments,
+            vec![(1, 13, " ten"), (2, 10, " x /= 2;"), (3, 1, " done")]
+        );
+    }
+
+    #[test]
+    fn test_keywords() {
+        let lexer = Lexer::new("");
+        for keyword in KEYWORDS {
+            assert!(
+                !matches!(lexer.lookup_ident(keyword), Token::Ident(_)),
+                "{}",
+                keyword
+            );
+        }
+    }
+
+    #[test]
+    fn test_string_literals() {
+        let input = r#""hello" "a\"b\\c\n" "" "price: \${x}" "\q" "unterminated"#;
+        let tests = vec![
+            Token::String("hello".to_string()),
+            Token::String("a\"b\\c\n".to_string()),
+            Token::String("".to_string()),
+            Token::String("price: ${x}".to_string()),
+            Token::Invalid(r"unknown escape \q".to_string()),
+            Token::Illegal,
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+        for t in tests {
+            let tok = lexer.next_token();
+            assert_eq!(t, tok);
+        }
+    }
+
+    #[test]
+    fn test_raw_string_literals() {
+        let input = r####"r"C:\path\n" r#"contains "quotes""# r##"a "# b"## r"" r#"open"####;
+        let tests = vec![
+            Token::RawString {
+                value: r"C:\path\n".to_string(),
+                hashes: 0,
+            },
+            Token::RawString {
+                value: r#"contains "quotes""#.to_string(),
+                hashes: 1,
+            },
+            Token::RawString {
+                value: r##"a "# b"##.to_string(),
+                hashes: 2,
+            },
+            Token::RawString {
+                value: "".to_string(),
+                hashes: 0,
+            },
+            Token::Illegal,
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+        for t in tests {
+            let tok = lexer.next_token();
+            assert_eq!(t, tok);
+        }
+
+        let mut lexer = Lexer::new("r + r2");
+        assert_eq!(lexer.next_token(), Token::Ident("r".to_string()));
+    }
+
+    #[test]
+    fn test_multi_line_string_literals() {
+        let input = "let q = \"\"\"\n    SELECT *\n      FROM t\n\n    WHERE a = \"x\"\n    \"\"\";\n\"\"\"one line\"\"\" \"\"\"\\d+\"\"\" \"\"\"open";
+        let tests = vec![
+            Token::Let,
+            Token::Ident("q".to_string()),
+            Token::Assign,
+            Token::MultiLineString("SELECT *\n  FROM t\n\nWHERE a = \"x\"".to_string()),
+            Token::Semicolon,
+            Token::MultiLineString("one line".to_string()),
+            Token::MultiLineString("\\d+".to_string()),
+            Token::Illegal,
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+        for t in tests {
+            let tok = lexer.next_token();
+            assert_eq!(t, tok);
+        }
+
+        let tests = vec![
+            ("\"\"\"\n\ta\n        b\n\"\"\"", "\ta\n        b"),
+            ("\"\"\"\n\t  a\n  \t  b\n\"\"\"", "\t  a\n  \t  b"),
+            ("\"\"\"\n\t\ta\n\t  b\n\"\"\"", "\ta\n  b"),
+        ];
+        for (input, expected) in tests {
+            let tok = Lexer::new(input).next_token();
+            assert_eq!(
+                tok,
+                Token::MultiLineString(expected.to_string()),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_char_literals() {
+        let input = r"'a' '\n' '\'' '\x41' '\u{1F600}' 'é' 'ab' '\xFF' '' '\q' '\u{110000}' b";
+        let tests = vec![
+            Token::Char('a'),
+            Token::Char('\n'),
+            Token::Char('\''),
+            Token::Char('A'),
+            Token::Char('\u{1F600}'),
+            Token::Char('é'),
+            Token::Invalid("more than one character in char literal".to_string()),
+            Token::Invalid(r"\xFF is not ASCII; use \u{FF} in a char literal".to_string()),
+            Token::Invalid("empty char literal".to_string()),
+            Token::Invalid(r"unknown escape \q".to_string()),
+            Token::Invalid(r"invalid unicode escape \u{110000}".to_string()),
+            Token::Ident("b".to_string()),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+        for t in tests {
+            let tok = lexer.next_token();
+            assert_eq!(t, tok);
+        }
+    }
+
+    #[test]
+    fn test_byte_string_literals() {
+        let input = r#"b"GET\r\n" b"\x00\xFF\"" b"" b"caf\u{e9}" b"é" b"\x4" b"#;
+        let tests = vec![
+            Token::ByteString(b"GET\r\n".to_vec()),
+            Token::ByteString(vec![0x00, 0xFF, b'"']),
+            Token::ByteString(vec![]),
+            Token::Invalid("unicode escape for 'é' in byte string".to_string()),
+            Token::Invalid("non-ASCII character 'é' in byte string".to_string()),
+            Token::Invalid(r"\x must be followed by two hex digits".to_string()),
+            Token::Ident("b".to_string()),
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+        for t in tests {
+            let tok = lexer.next_token();
+            assert_eq!(t, tok);
+        }
+    }
+
+    #[test]
+    fn test_template_literals() {
+        let input = r#""Hello, ${user.name}! You have ${count + 1} messages"
+        "${ {a: "}"}.a }" "${f("${x}")}-" "${"#;
+        let tests = vec![
+            Token::Template(vec![
+                TemplatePart::Literal("Hello, ".to_string()),
+                TemplatePart::Interpolation {
+                    source: "user.name".to_string(),
+                    start: Position {
+                        line: 1,
+                        column: 11,
+                    },
+                },
+                TemplatePart::Literal("! You have ".to_string()),
+                TemplatePart::Interpolation {
+                    source: "count + 1".to_string(),
+                    start: Position {
+                        line: 1,
+                        column: 34,
+                    },
+                },
+                TemplatePart::Literal(" messages".to_string()),
+            ]),
+            Token::Template(vec![TemplatePart::Interpolation {
+                source: r#" {a: "}"}.a "#.to_string(),
+                start: Position {
+                    line: 2,
+                    column: 12,
+                },
+            }]),
+            Token::Template(vec![
+                TemplatePart::Interpolation {
+                    source: r#"f("${x}")"#.to_string(),
+                    start: Position {
+                        line: 2,
+                        column: 30,
+                    },
+                },
+                TemplatePart::Literal("-".to_string()),
+            ]),
+            Token::Illegal,
+            Token::EOF,
+        ];
+        let mut lexer = Lexer::new(input);
+        for t in tests {
+            let tok = lexer.next_token();
+            assert_eq!(t, tok);
+        }
+    }
+
+    #[test]
+    fn test_number_literals() {
+        let input = "0xFF 0o755 0b1010 1_000_000 0x 0b102 12ab";
+        let tests = vec![
+            Token::Int("0xFF".to_string()),
+            Token::Int("0o755".to_string()),
+            Token::Int("0b1010".to_string()),
+            Token::Int("1_000_000".to_string()),
+            Token::Int("0x".to_string()),
+            Token::Int("0b102".to_string()),
+            Token::Int("12ab".to_string()),
             Token::EOF,
         ];
         let mut lexer = Lexer::new(input);
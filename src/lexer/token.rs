@@ -1,59 +1,193 @@
+use super::span::Position;
 use std::fmt;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Illegal,
+    /// A literal that could not be read, with the reason why.
+    Invalid(String),
     EOF,
     Ident(String),
     Int(String),
+    String(String),
+    RawString {
+        value: String,
+        hashes: usize,
+    },
+    MultiLineString(String),
+    Template(Vec<TemplatePart>),
+    Char(char),
+    ByteString(Vec<u8>),
     Assign,
+    FatArrow,
+    Arrow,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
     Plus,
     Minus,
     Bang,
     Asterisk,
+    Power,
     Slash,
+    Percent,
     Lt,
     Gt,
+    LtEq,
+    GtEq,
     Eq,
     Neq,
+    And,
+    Or,
+    Question,
+    QuestionDot,
+    Coalesce,
+    Ampersand,
+    Pipe,
+    Pipeline,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
     Comma,
+    Colon,
+    Dot,
+    DotDot,
+    DotDotEq,
+    Ellipsis,
     Semicolon,
     Lparen,
     Rparen,
     Lbrace,
     Rbrace,
+    Lbracket,
+    Rbracket,
     Function,
     Let,
+    Const,
     True,
     False,
     If,
     Else,
-    Return
+    Return,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Null,
+    Match,
+    Import,
+    Export,
+    As,
+    Try,
+    Catch,
+    Finally,
+    Throw,
 }
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Token::Ident(name) => write!(f, "{}", name),
+            Token::Invalid(reason) => write!(f, "{}", reason),
             Token::Int(val) => write!(f, "{}", val),
+            Token::String(val) => write!(f, "\"{}\"", val),
+            Token::RawString { value, hashes } => {
+                let hashes = "#".repeat(*hashes);
+                write!(f, "r{}\"{}\"{}", hashes, value, hashes)
+            }
+            Token::MultiLineString(val) => write!(f, "\"\"\"\n{}\n\"\"\"", val),
+            Token::Char(val) => write!(f, "'{}'", val.escape_default()),
+            Token::ByteString(val) => write!(f, "b\"{}\"", val.escape_ascii()),
+            Token::Template(parts) => {
+                write!(f, "\"")?;
+                for part in parts {
+                    match part {
+                        TemplatePart::Literal(val) => write!(f, "{}", val)?,
+                        TemplatePart::Interpolation { source, .. } => write!(f, "${{{}}}", source)?,
+                    }
+                }
+                write!(f, "\"")
+            }
             Token::Minus => write!(f, "-"),
             Token::Plus => write!(f, "+"),
             Token::Bang => write!(f, "!"),
             Token::Asterisk => write!(f, "*"),
+            Token::Power => write!(f, "**"),
             Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
             Token::Gt => write!(f, ">"),
             Token::Lt => write!(f, "<"),
+            Token::GtEq => write!(f, ">="),
+            Token::LtEq => write!(f, "<="),
             Token::Eq => write!(f, "=="),
             Token::Neq => write!(f, "!="),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::QuestionDot => write!(f, "?."),
+            Token::Coalesce => write!(f, "??"),
+            Token::Ampersand => write!(f, "&"),
+            Token::Pipe => write!(f, "|"),
+            Token::Pipeline => write!(f, "|>"),
+            Token::Caret => write!(f, "^"),
+            Token::Tilde => write!(f, "~"),
+            Token::ShiftLeft => write!(f, "<<"),
+            Token::ShiftRight => write!(f, ">>"),
             Token::Semicolon => write!(f, ";"),
             Token::Assign => write!(f, "="),
+            Token::FatArrow => write!(f, "=>"),
+            Token::PlusAssign => write!(f, "+="),
+            Token::MinusAssign => write!(f, "-="),
+            Token::AsteriskAssign => write!(f, "*="),
+            Token::SlashAssign => write!(f, "/="),
             Token::Function => write!(f, "fn"),
+            Token::Let => write!(f, "let"),
+            Token::Const => write!(f, "const"),
+            Token::True => write!(f, "true"),
+            Token::False => write!(f, "false"),
+            Token::If => write!(f, "if"),
+            Token::Else => write!(f, "else"),
+            Token::Return => write!(f, "return"),
+            Token::While => write!(f, "while"),
+            Token::For => write!(f, "for"),
+            Token::In => write!(f, "in"),
+            Token::Break => write!(f, "break"),
+            Token::Continue => write!(f, "continue"),
+            Token::Null => write!(f, "null"),
+            Token::Match => write!(f, "match"),
+            Token::Import => write!(f, "import"),
+            Token::Export => write!(f, "export"),
+            Token::As => write!(f, "as"),
+            Token::Try => write!(f, "try"),
+            Token::Catch => write!(f, "catch"),
+            Token::Finally => write!(f, "finally"),
+            Token::Throw => write!(f, "throw"),
             Token::Lparen => write!(f, "("),
             Token::Rparen => write!(f, ")"),
             Token::Lbrace => write!(f, "{{"),
             Token::Rbrace => write!(f, "}}"),
+            Token::Lbracket => write!(f, "["),
+            Token::Rbracket => write!(f, "]"),
+            Token::Question => write!(f, "?"),
+            Token::Arrow => write!(f, "->"),
             Token::Comma => write!(f, ","),
+            Token::Colon => write!(f, ":"),
+            Token::Dot => write!(f, "."),
+            Token::DotDot => write!(f, ".."),
+            Token::DotDotEq => write!(f, "..="),
+            Token::Ellipsis => write!(f, "..."),
             tok => write!(f, "{:?}", tok),
         }
     }
 }
+
+/// A piece of a string literal containing `${...}` interpolations. The source
+/// of each interpolation is kept as-is for the parser to parse separately.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplatePart {
+    Literal(String),
+    Interpolation { source: String, start: Position },
+}
@@ -1,3 +1,9 @@
-mod lexer;
-mod parser;
+pub mod eval;
+pub mod lexer;
+pub mod lint;
+pub mod lsp;
+pub mod module;
+pub mod parser;
 pub mod repl;
+pub mod resolver;
+pub mod typeck;
@@ -0,0 +1,683 @@
+use crate::lexer::{Lexer, Position, Span, Token};
+use crate::parser::ast;
+use crate::resolver::{self, Diagnostic};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use thiserror::Error;
+
+/// The config file `interpreter-rs lint` reads from the working directory
+/// when no `--config` is given.
+pub const CONFIG_FILE: &str = "lint.conf";
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Rule {
+    UnusedVariable,
+    ShadowedLet,
+    ConstantCondition,
+    SelfComparison,
+    DoubleNegation,
+    UnreachableCode,
+    SuspiciousPrecedence,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::UnusedVariable,
+        Rule::ShadowedLet,
+        Rule::ConstantCondition,
+        Rule::SelfComparison,
+        Rule::DoubleNegation,
+        Rule::UnreachableCode,
+        Rule::SuspiciousPrecedence,
+    ];
+
+    /// The name used for the rule in config files and `lint:allow` comments.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::ShadowedLet => "shadowed-let",
+            Rule::ConstantCondition => "constant-condition",
+            Rule::SelfComparison => "self-comparison",
+            Rule::DoubleNegation => "double-negation",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::SuspiciousPrecedence => "suspicious-precedence",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|rule| rule.name() == name)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Warning {
+    pub rule: Rule,
+    pub position: Position,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.position, self.message, self.rule)
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ConfigError {
+    #[error("line {line}: unknown lint rule {name}")]
+    UnknownRule { line: usize, name: String },
+    #[error("line {line}: expect `<rule> = on` or `<rule> = off`, found {found:?}")]
+    Syntax { line: usize, found: String },
+}
+
+/// Which rules are enabled. Every rule is on by default.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    disabled: HashSet<Rule>,
+}
+
+impl Config {
+    /// Parses a config file: one `<rule> = on` or `<rule> = off` per line.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn parse(source: &str) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let syntax_error = || ConfigError::Syntax {
+                line: i + 1,
+                found: line.to_string(),
+            };
+            let (name, setting) = line.split_once('=').ok_or_else(syntax_error)?;
+            let rule = Rule::from_name(name.trim()).ok_or_else(|| ConfigError::UnknownRule {
+                line: i + 1,
+                name: name.trim().to_string(),
+            })?;
+            match setting.trim() {
+                "on" => config.disabled.remove(&rule),
+                "off" => config.disabled.insert(rule),
+                _ => return Err(syntax_error()),
+            };
+        }
+        Ok(config)
+    }
+
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+}
+
+/// Checks `program`, parsed from `source`, against the rules enabled in
+/// `config`. Warnings are sorted by position.
+///
+/// A `// lint:allow(rule, ...)` comment silences the listed rules on its own
+/// line, or on the next line when the comment is alone on its line.
+pub fn lint(source: &str, program: &ast::Program, config: &Config) -> Vec<Warning> {
+    let mut linter = Linter {
+        warnings: vec![],
+        scopes: vec![],
+    };
+    linter.lint_statements(&program.statements);
+
+    for diagnostic in resolver::resolve(program).diagnostics {
+        if let Diagnostic::UnusedBinding { name, position } = diagnostic {
+            linter.warn(
+                Rule::UnusedVariable,
+                position,
+                format!("{} is never used", name),
+            );
+        }
+    }
+
+    let allowed = allowed_rules(source);
+    let mut warnings: Vec<Warning> = linter
+        .warnings
+        .into_iter()
+        .filter(|w| config.is_enabled(w.rule))
+        .filter(|w| {
+            !allowed
+                .get(&w.position.line)
+                .is_some_and(|rules| rules.contains(&w.rule))
+        })
+        .collect();
+    warnings.sort_by_key(|w| w.position);
+    warnings
+}
+
+/// The rules silenced by `lint:allow` comments, keyed by line. Comments are
+/// taken from the lexer, so text inside strings is never mistaken for one.
+/// A comment without the closing `)` is ignored rather than guessed at.
+fn allowed_rules(source: &str) -> HashMap<usize, Vec<Rule>> {
+    let mut lexer = Lexer::new(source);
+    while lexer.next_token() != Token::EOF {}
+
+    let lines: Vec<&str> = source.lines().collect();
+    let mut allowed: HashMap<usize, Vec<Rule>> = HashMap::new();
+    for (start, text) in lexer.comments() {
+        let names = match text
+            .trim_start()
+            .strip_prefix("lint:allow(")
+            .and_then(|rest| rest.split_once(')'))
+        {
+            Some((names, _)) => names,
+            None => continue,
+        };
+        let before = lines
+            .get(start.line - 1)
+            .map_or("", |line| &line[..start.column - 1]);
+        let target = if before.trim().is_empty() {
+            start.line + 1
+        } else {
+            start.line
+        };
+        allowed
+            .entry(target)
+            .or_default()
+            .extend(names.split(',').filter_map(|n| Rule::from_name(n.trim())));
+    }
+    allowed
+}
+
+struct Linter {
+    warnings: Vec<Warning>,
+    /// The names bound in each enclosing scope, innermost last.
+    scopes: Vec<Vec<ast::Identifier>>,
+}
+
+impl Linter {
+    fn warn(&mut self, rule: Rule, position: Position, message: String) {
+        self.warnings.push(Warning {
+            rule,
+            position,
+            message,
+        });
+    }
+
+    fn declare(&mut self, identifier: &ast::Identifier) {
+        self.scopes
+            .last_mut()
+            .expect("linter has no scope")
+            .push(identifier.clone());
+    }
+
+    fn in_scope(&mut self, lint: impl FnOnce(&mut Linter)) {
+        self.scopes.push(vec![]);
+        lint(self);
+        self.scopes.pop();
+    }
+
+    fn lint_statements(&mut self, statements: &[ast::Statement]) {
+        self.in_scope(|linter| {
+            // Named functions are hoisted.
+            for statement in statements {
+                match statement {
+                    ast::Statement::FnDecl { name, .. } => linter.declare(name),
+                    ast::Statement::Export(declaration) => {
+                        if let ast::Statement::FnDecl { name, .. } = declaration.as_ref() {
+                            linter.declare(name);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            // Dead code is still linted; only the first terminator is
+            // reported, at the statement it makes unreachable.
+            let mut terminated = false;
+            for (i, statement) in statements.iter().enumerate() {
+                linter.lint_statement(statement);
+                if terminated {
+                    continue;
+                }
+                if let (Some((keyword, span)), Some(next)) =
+                    (terminator(statement), statements.get(i + 1))
+                {
+                    linter.warn(
+                        Rule::UnreachableCode,
+                        next.position().unwrap_or(span.start),
+                        format!("code after {} is unreachable", keyword),
+                    );
+                    terminated = true;
+                }
+            }
+        });
+    }
+
+    fn lint_statement(&mut self, statement: &ast::Statement) {
+        match statement {
+            ast::Statement::Let(pattern, _, value) => {
+                self.lint_expression(value);
+                for identifier in pattern.identifiers() {
+                    let shadowed = self
+                        .scopes
+                        .iter()
+                        .flatten()
+                        .rev()
+                        .find(|declared| declared.0 == identifier.0);
+                    if let Some(shadowed) = shadowed {
+                        let message = format!(
+                            "let {} shadows the binding declared at {}",
+                            identifier, shadowed.1.start
+                        );
+                        self.warn(Rule::ShadowedLet, identifier.1.start, message);
+                    }
+                    self.declare(identifier);
+                }
+            }
+            ast::Statement::Const(pattern, _, value) => {
+                self.lint_expression(value);
+                for identifier in pattern.identifiers() {
+                    self.declare(identifier);
+                }
+            }
+            ast::Statement::Return(value, _)
+            | ast::Statement::Expr(value)
+            | ast::Statement::Throw(value, _) => self.lint_expression(value),
+            ast::Statement::While { condition, body } => {
+                self.lint_expression(condition);
+                self.lint_statements(&body.statements);
+            }
+            ast::Statement::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                self.lint_expression(iterable);
+                self.in_scope(|linter| {
+                    linter.declare(variable);
+                    linter.lint_statements(&body.statements);
+                });
+            }
+            ast::Statement::Break(_) | ast::Statement::Continue(_) => {}
+            ast::Statement::FnDecl {
+                parameters, body, ..
+            } => self.lint_function(parameters, |linter| {
+                linter.lint_statements(&body.statements)
+            }),
+            ast::Statement::Import { alias, .. } => self.declare(alias),
+            ast::Statement::Export(declaration) => self.lint_statement(declaration),
+            ast::Statement::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.lint_statements(&body.statements);
+                if let Some(catch) = catch {
+                    self.in_scope(|linter| {
+                        linter.declare(&catch.parameter);
+                        linter.lint_statements(&catch.body.statements);
+                    });
+                }
+                if let Some(finally) = finally {
+                    self.lint_statements(&finally.statements);
+                }
+            }
+        }
+    }
+
+    fn lint_function(&mut self, parameters: &[ast::Parameter], body: impl FnOnce(&mut Linter)) {
+        self.in_scope(|linter| {
+            for parameter in parameters {
+                if let Some(default) = &parameter.default {
+                    linter.lint_expression(default);
+                }
+                linter.declare(&parameter.name);
+            }
+            body(linter);
+        });
+    }
+
+    fn lint_expression(&mut self, expression: &ast::Expression) {
+        match expression {
+            ast::Expression::Ident(_)
+            | ast::Expression::IntegerLiteral { .. }
+            | ast::Expression::BooleanLiteral(_)
+            | ast::Expression::NullLiteral
+            | ast::Expression::StringLiteral { .. }
+            | ast::Expression::CharLiteral(_)
+            | ast::Expression::ByteStringLiteral(_) => {}
+            ast::Expression::Template(segments) => {
+                for segment in segments {
+                    if let ast::TemplateSegment::Interpolation { expression, .. } = segment {
+                        self.lint_expression(expression);
+                    }
+                }
+            }
+            ast::Expression::Prefix {
+                operator,
+                right,
+                span,
+                ..
+            } => {
+                if let (
+                    ast::Operator::Bang,
+                    ast::Expression::Prefix {
+                        operator: ast::Operator::Bang,
+                        right: operand,
+                        ..
+                    },
+                ) = (operator, right.as_ref())
+                {
+                    let message = format!("!!{} is a redundant double negation", operand);
+                    self.warn(Rule::DoubleNegation, span.start, message);
+                }
+                self.lint_expression(right);
+            }
+            ast::Expression::Infix {
+                left,
+                operator,
+                right,
+                span,
+                ..
+            } => {
+                self.lint_infix(left, operator, right, span.start);
+                self.lint_expression(left);
+                self.lint_expression(right);
+            }
+            ast::Expression::Assign { target, value, .. } => {
+                self.lint_expression(target);
+                self.lint_expression(value);
+            }
+            ast::Expression::Index { left, index, .. } => {
+                self.lint_expression(left);
+                self.lint_expression(index);
+            }
+            ast::Expression::Member { object, .. } => self.lint_expression(object),
+            ast::Expression::Function {
+                parameters, body, ..
+            } => self.lint_function(parameters, |linter| {
+                linter.lint_statements(&body.statements)
+            }),
+            ast::Expression::Lambda {
+                parameters, body, ..
+            } => self.lint_function(parameters, |linter| linter.lint_expression(body)),
+            ast::Expression::Call {
+                function,
+                arguments,
+            } => {
+                self.lint_expression(function);
+                for argument in arguments {
+                    self.lint_expression(argument);
+                }
+            }
+            ast::Expression::Spread(value) | ast::Expression::Propagate(value) => {
+                self.lint_expression(value)
+            }
+            ast::Expression::Pipe { value, function } => {
+                self.lint_expression(value);
+                self.lint_expression(function);
+            }
+            ast::Expression::Range { start, end, .. } => {
+                self.lint_expression(start);
+                self.lint_expression(end);
+            }
+            ast::Expression::If {
+                condition,
+                consequence,
+                alternative,
+                span,
+            } => {
+                if is_literal(condition) {
+                    let message = format!("if condition {} is constant", condition);
+                    self.warn(Rule::ConstantCondition, span.start, message);
+                }
+                self.lint_expression(condition);
+                self.lint_statements(&consequence.statements);
+                if let Some(alternative) = alternative {
+                    self.lint_statements(&alternative.statements);
+                }
+            }
+            ast::Expression::Match { value, arms } => {
+                self.lint_expression(value);
+                for arm in arms {
+                    self.in_scope(|linter| {
+                        for identifier in arm.pattern.identifiers() {
+                            linter.declare(identifier);
+                        }
+                        if let Some(guard) = &arm.guard {
+                            linter.lint_expression(guard);
+                        }
+                        linter.lint_expression(&arm.body);
+                    });
+                }
+            }
+        }
+    }
+
+    fn lint_infix(
+        &mut self,
+        left: &ast::Expression,
+        operator: &ast::Operator,
+        right: &ast::Expression,
+        position: Position,
+    ) {
+        if !is_comparison(operator) {
+            return;
+        }
+
+        // Expressions are compared by their source form: the derived
+        // equality would also compare operator spans.
+        if is_pure(left) && left.to_string() == right.to_string() {
+            let message = format!("{} is compared with itself", left);
+            self.warn(Rule::SelfComparison, position, message);
+        }
+
+        // Parentheses aren't kept in the AST, so `(!a) == b` is reported too.
+        if let ast::Expression::Prefix {
+            operator: ast::Operator::Bang,
+            right: operand,
+            ..
+        } = left
+        {
+            let message = format!(
+                "!{0} {1} {2} negates {0} before comparing; write !({0} {1} {2}) to negate the comparison",
+                operand, operator, right
+            );
+            self.warn(Rule::SuspiciousPrecedence, position, message);
+        }
+    }
+}
+
+/// The keyword and span of a statement that never falls through to the
+/// next one.
+fn terminator(statement: &ast::Statement) -> Option<(&'static str, Span)> {
+    match statement {
+        ast::Statement::Return(_, span) => Some(("return", *span)),
+        ast::Statement::Break(span) => Some(("break", *span)),
+        ast::Statement::Continue(span) => Some(("continue", *span)),
+        ast::Statement::Throw(_, span) => Some(("throw", *span)),
+        _ => None,
+    }
+}
+
+fn is_comparison(operator: &ast::Operator) -> bool {
+    matches!(
+        operator,
+        ast::Operator::Eq
+            | ast::Operator::Neq
+            | ast::Operator::Lt
+            | ast::Operator::Gt
+            | ast::Operator::LtEq
+            | ast::Operator::GtEq
+            | ast::Operator::In
+    )
+}
+
+fn is_literal(expression: &ast::Expression) -> bool {
+    matches!(
+        expression,
+        ast::Expression::IntegerLiteral { .. }
+            | ast::Expression::BooleanLiteral(_)
+            | ast::Expression::NullLiteral
+            | ast::Expression::StringLiteral { .. }
+            | ast::Expression::CharLiteral(_)
+            | ast::Expression::ByteStringLiteral(_)
+    )
+}
+
+/// Whether evaluating `expression` twice is sure to give the same value:
+/// it calls nothing and assigns nothing.
+fn is_pure(expression: &ast::Expression) -> bool {
+    match expression {
+        ast::Expression::Ident(_) => true,
+        ast::Expression::Prefix { right, .. } => is_pure(right),
+        ast::Expression::Infix { left, right, .. } => is_pure(left) && is_pure(right),
+        ast::Expression::Index { left, index, .. } => is_pure(left) && is_pure(index),
+        ast::Expression::Member { object, .. } => is_pure(object),
+        expression => is_literal(expression),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn lint_source(input: &str, config: &Config) -> Vec<String> {
+        match parse(input) {
+            Ok(program) => lint(input, &program, config)
+                .iter()
+                .map(|w| w.to_string())
+                .collect(),
+            Err(errors) => panic!("parser errors:\n{}", errors),
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let tests = vec![
+            (
+                "let a = 1; let f = fn(x) { let a = x; a };\nf(a);",
+                vec!["1:32: let a shadows the binding declared at 1:5 [shadowed-let]"],
+            ),
+            (
+                "let n = 1;\nlet n = n + 1;\nputs(n);",
+                vec!["2:5: let n shadows the binding declared at 1:5 [shadowed-let]"],
+            ),
+            (
+                "let x = 1;\nif (true) { puts(x) }",
+                vec!["2:1: if condition true is constant [constant-condition]"],
+            ),
+            (
+                "let x = 1; puts(x == x, x.y < x.y, f() == f(), x == y);",
+                vec![
+                    "1:19: x is compared with itself [self-comparison]",
+                    "1:29: (x.y) is compared with itself [self-comparison]",
+                ],
+            ),
+            (
+                "let x = true; puts(!!x, !x);",
+                vec!["1:20: !!x is a redundant double negation [double-negation]"],
+            ),
+            (
+                "fn f(x) {\n  if (x) { return 1; puts(x); }\n  while (x) { break; x = 2; }\n  2\n}\nf(1);",
+                vec![
+                    "2:22: code after return is unreachable [unreachable-code]",
+                    "3:22: code after break is unreachable [unreachable-code]",
+                ],
+            ),
+            (
+                "fn f(x) {\n  return x;\n  !!x;\n  x == x;\n  return 1;\n  puts(x);\n}\nf(1);",
+                vec![
+                    "3:3: code after return is unreachable [unreachable-code]",
+                    "3:3: !!x is a redundant double negation [double-negation]",
+                    "4:5: x is compared with itself [self-comparison]",
+                ],
+            ),
+            (
+                "let a = 1; let b = 2; puts(!a == b, b == !a, !(a == b));",
+                vec![
+                    "1:31: !a == b negates a before comparing; write !(a == b) to negate the comparison [suspicious-precedence]",
+                ],
+            ),
+            (
+                "let used = 1; let unused = used;",
+                vec!["1:19: unused is never used [unused-variable]"],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                lint_source(input, &Config::default()),
+                expected,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_allow_comments_and_config() {
+        let input = "let x = 1;\n// lint:allow(constant-condition)\nif (true) { puts(x == x) }\nif (false) { puts(!!x) } // lint:allow(double-negation, self-comparison)\nlet y = 2;";
+        assert_eq!(
+            lint_source(input, &Config::default()),
+            vec![
+                "3:20: x is compared with itself [self-comparison]",
+                "4:1: if condition false is constant [constant-condition]",
+                "5:5: y is never used [unused-variable]",
+            ]
+        );
+
+        let config =
+            Config::parse("# quieter\nunused-variable = off\nself-comparison=off\n").unwrap();
+        assert_eq!(
+            lint_source(input, &config),
+            vec!["4:1: if condition false is constant [constant-condition]"]
+        );
+    }
+
+    #[test]
+    fn test_allow_comment_syntax() {
+        let tests = vec![
+            (
+                "let s = \"// lint:allow(constant-condition)\"; if (true) { puts(s) }",
+                vec!["1:46: if condition true is constant [constant-condition]"],
+            ),
+            (
+                "// lint:allow(unused-variable\nlet x = 1;",
+                vec!["2:5: x is never used [unused-variable]"],
+            ),
+            ("//lint:allow(unused-variable)\nlet x = 1;", vec![]),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                lint_source(input, &Config::default()),
+                expected,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let tests = vec![
+            ("unused = off", "line 1: unknown lint rule unused"),
+            (
+                "\nshadowed-let off",
+                "line 2: expect `<rule> = on` or `<rule> = off`, found \"shadowed-let off\"",
+            ),
+            (
+                "shadowed-let = no",
+                "line 1: expect `<rule> = on` or `<rule> = off`, found \"shadowed-let = no\"",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match Config::parse(input) {
+                Ok(config) => panic!("expected error, got {:?}", config),
+                Err(e) => assert_eq!(e.to_string(), expected),
+            }
+        }
+    }
+}
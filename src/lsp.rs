@@ -0,0 +1,6 @@
+mod document;
+mod format;
+mod server;
+mod transport;
+
+pub use self::server::run;
@@ -0,0 +1,676 @@
+use super::format;
+use crate::lexer::{Position, Span, KEYWORDS};
+use crate::parser::{self, ast, ParserErrors};
+use crate::resolver::{self, Diagnostic, Resolution, BUILTINS};
+use crate::typeck::{self, Inference};
+use serde_json::{json, Value};
+
+// Values of the LSP enums used below.
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+const TAG_UNNECESSARY: u8 = 1;
+const SYMBOL_MODULE: u8 = 2;
+const SYMBOL_FUNCTION: u8 = 12;
+const SYMBOL_VARIABLE: u8 = 13;
+const SYMBOL_CONSTANT: u8 = 14;
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_KEYWORD: u8 = 14;
+
+/// An open document and what is known about its program.
+pub struct Document {
+    text: String,
+    analysis: Result<Analysis, ParserErrors>,
+}
+
+struct Analysis {
+    program: ast::Program,
+    resolution: Resolution,
+    inference: Inference,
+}
+
+impl Document {
+    pub fn new(text: String) -> Document {
+        let analysis = parser::parse(&text).map(|program| Analysis {
+            resolution: resolver::resolve(&program),
+            inference: typeck::infer(&program),
+            program,
+        });
+        Document { text, analysis }
+    }
+
+    /// Converts an LSP position, a 0-based line and a count of UTF-16 code
+    /// units, to a `Position`.
+    pub fn position(&self, line: u64, character: u64) -> Position {
+        let line = line as usize + 1;
+        let text = self.line(line);
+        let mut units = 0;
+        let column = text
+            .char_indices()
+            .find(|(_, ch)| {
+                let found = units >= character as usize;
+                units += ch.len_utf16();
+                found
+            })
+            .map_or(text.len(), |(i, _)| i);
+        Position {
+            line,
+            column: column + 1,
+        }
+    }
+
+    fn lsp_position(&self, position: Position) -> Value {
+        let text = self.line(position.line);
+        let prefix = text.get(..position.column - 1).unwrap_or(text);
+        json!({
+            "line": position.line - 1,
+            "character": prefix.encode_utf16().count(),
+        })
+    }
+
+    fn range(&self, span: Span) -> Value {
+        json!({
+            "start": self.lsp_position(span.start),
+            "end": self.lsp_position(span.end),
+        })
+    }
+
+    fn line(&self, line: usize) -> &str {
+        self.text.lines().nth(line - 1).unwrap_or_default()
+    }
+
+    /// The identifier touching `position`, with its span.
+    fn identifier_at(&self, position: Position) -> Option<(&str, Span)> {
+        let text = self.line(position.line);
+        let is_identifier = |b: &u8| b.is_ascii_alphabetic() || *b == b'_';
+        let at = (position.column - 1).min(text.len());
+        let start = at
+            - text.as_bytes()[..at]
+                .iter()
+                .rev()
+                .take_while(|b| is_identifier(b))
+                .count();
+        let end = at
+            + text.as_bytes()[at..]
+                .iter()
+                .take_while(|b| is_identifier(b))
+                .count();
+        if start == end {
+            return None;
+        }
+        let span = Span {
+            start: Position {
+                line: position.line,
+                column: start + 1,
+            },
+            end: Position {
+                line: position.line,
+                column: end + 1,
+            },
+        };
+        Some((&text[start..end], span))
+    }
+
+    /// Parser errors, or, for a program that parses, the resolver's
+    /// diagnostics and type errors.
+    pub fn diagnostics(&self) -> Vec<Value> {
+        let analysis = match &self.analysis {
+            Ok(analysis) => analysis,
+            Err(errors) => {
+                return errors
+                    .iter()
+                    .map(|(error, span)| self.diagnostic(span, SEVERITY_ERROR, error))
+                    .collect()
+            }
+        };
+
+        analysis
+            .resolution
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let (name, position) = match diagnostic {
+                    Diagnostic::UnknownIdentifier { name, position, .. }
+                    | Diagnostic::UnusedBinding { name, position } => (name, *position),
+                    Diagnostic::UseBeforeDefinition { name, used, .. } => (name, *used),
+                };
+                let span = Span {
+                    start: position,
+                    end: Position {
+                        column: position.column + name.len(),
+                        ..position
+                    },
+                };
+                if diagnostic.is_warning() {
+                    let mut value = self.diagnostic(span, SEVERITY_WARNING, diagnostic);
+                    value["tags"] = json!([TAG_UNNECESSARY]);
+                    value
+                } else {
+                    self.diagnostic(span, SEVERITY_ERROR, diagnostic)
+                }
+            })
+            .chain(analysis.inference.errors.iter().map(|error| {
+                let position = error.position();
+                let span = Span {
+                    start: position,
+                    end: position,
+                };
+                self.diagnostic(span, SEVERITY_ERROR, error)
+            }))
+            .collect()
+    }
+
+    fn diagnostic(&self, span: Span, severity: u8, message: &dyn ToString) -> Value {
+        json!({
+            "range": self.range(span),
+            "severity": severity,
+            "source": "interpreter-rs",
+            "message": message.to_string(),
+        })
+    }
+
+    /// The inferred type of the identifier at `position`.
+    pub fn hover(&self, position: Position) -> Option<Value> {
+        let analysis = self.analysis.as_ref().ok()?;
+        let (name, span) = self.identifier_at(position)?;
+        let builtin = analysis
+            .resolution
+            .bindings
+            .get(&span.start)
+            .is_some_and(|binding| binding.declaration.is_none());
+        let contents = if builtin {
+            format!("builtin {}", name)
+        } else {
+            format!("{}: {}", name, analysis.inference.types.get(&span.start)?)
+        };
+        Some(json!({
+            "contents": {"kind": "markdown", "value": format!("```\n{}\n```", contents)},
+            "range": self.range(span),
+        }))
+    }
+
+    /// The range of the declaration bound to the identifier at `position`.
+    pub fn definition(&self, position: Position) -> Option<Value> {
+        let analysis = self.analysis.as_ref().ok()?;
+        let (_, span) = self.identifier_at(position)?;
+        let binding = analysis.resolution.bindings.get(&span.start)?;
+        binding
+            .declaration
+            .map(|declaration| self.range(declaration))
+    }
+
+    /// The declarations in the document, with those inside function bodies
+    /// nested under the function.
+    pub fn symbols(&self) -> Vec<Value> {
+        match &self.analysis {
+            Ok(analysis) => self.statement_symbols(analysis, &analysis.program.statements),
+            Err(_) => vec![],
+        }
+    }
+
+    fn statement_symbols(&self, analysis: &Analysis, statements: &[ast::Statement]) -> Vec<Value> {
+        let mut symbols = vec![];
+        for statement in statements {
+            match statement {
+                ast::Statement::Let(pattern, _, value)
+                | ast::Statement::Const(pattern, _, value) => match (pattern, value) {
+                    (ast::Pattern::Binding(name), ast::Expression::Function { body, .. }) => {
+                        symbols.push(self.symbol(analysis, name, SYMBOL_FUNCTION, Some(body)))
+                    }
+                    _ => {
+                        let kind = match statement {
+                            ast::Statement::Const(..) => SYMBOL_CONSTANT,
+                            _ => SYMBOL_VARIABLE,
+                        };
+                        for name in pattern.identifiers() {
+                            symbols.push(self.symbol(analysis, name, kind, None));
+                        }
+                    }
+                },
+                ast::Statement::FnDecl { name, body, .. } => {
+                    symbols.push(self.symbol(analysis, name, SYMBOL_FUNCTION, Some(body)))
+                }
+                ast::Statement::Import { alias, .. } => {
+                    symbols.push(self.symbol(analysis, alias, SYMBOL_MODULE, None))
+                }
+                ast::Statement::Export(declaration) => symbols
+                    .extend(self.statement_symbols(analysis, std::slice::from_ref(declaration))),
+                _ => {}
+            }
+        }
+        symbols
+    }
+
+    fn symbol(
+        &self,
+        analysis: &Analysis,
+        name: &ast::Identifier,
+        kind: u8,
+        body: Option<&ast::BlockStatement>,
+    ) -> Value {
+        let range = match body {
+            Some(body) => Span {
+                start: name.1.start,
+                end: body.span.end,
+            },
+            None => name.1,
+        };
+        let mut symbol = json!({
+            "name": name.0,
+            "kind": kind,
+            "range": self.range(range),
+            "selectionRange": self.range(name.1),
+        });
+        if let Some(ty) = analysis.inference.types.get(&name.1.start) {
+            symbol["detail"] = json!(ty.to_string());
+        }
+        if let Some(body) = body {
+            symbol["children"] = json!(self.statement_symbols(analysis, &body.statements));
+        }
+        symbol
+    }
+
+    /// Names in scope at `position`, then builtins and keywords.
+    pub fn completions(&self, position: Position) -> Vec<Value> {
+        let mut names = vec![];
+        if let Ok(analysis) = &self.analysis {
+            visible_in_statements(&analysis.program.statements, position, &mut names);
+        }
+        names.reverse();
+
+        let mut items: Vec<Value> = vec![];
+        let mut seen = vec![];
+        for (name, kind) in names.into_iter().chain(
+            BUILTINS
+                .iter()
+                .map(|b| (b.to_string(), COMPLETION_FUNCTION)),
+        ) {
+            if !seen.contains(&name) {
+                items.push(json!({"label": name, "kind": kind}));
+                seen.push(name);
+            }
+        }
+        items.extend(
+            KEYWORDS
+                .iter()
+                .map(|k| json!({"label": k, "kind": COMPLETION_KEYWORD})),
+        );
+        items
+    }
+
+    /// Edits that re-indent the whole document, or none if it doesn't parse
+    /// or is already formatted.
+    pub fn format(&self, tab_size: usize, insert_spaces: bool) -> Vec<Value> {
+        if self.analysis.is_err() {
+            return vec![];
+        }
+        let indent = if insert_spaces {
+            " ".repeat(tab_size)
+        } else {
+            "\t".to_string()
+        };
+        let formatted = format::format(&self.text, &indent);
+        if formatted == self.text {
+            return vec![];
+        }
+
+        let lines: Vec<&str> = self.text.split('\n').collect();
+        let range = json!({
+            "start": {"line": 0, "character": 0},
+            "end": {
+                "line": lines.len() - 1,
+                "character": lines[lines.len() - 1].encode_utf16().count(),
+            },
+        });
+        vec![json!({"range": range, "newText": formatted})]
+    }
+}
+
+fn contains(block: &ast::BlockStatement, position: Position) -> bool {
+    block.span.start < position && position < block.span.end
+}
+
+/// Whether `position` is inside `span` or right at its end, where a name
+/// being typed at the end of an expression is.
+fn within(span: Span, position: Position) -> bool {
+    span.start < position && position <= span.end
+}
+
+fn completion_kind(value: &ast::Expression) -> u8 {
+    match value {
+        ast::Expression::Function { .. } | ast::Expression::Lambda { .. } => COMPLETION_FUNCTION,
+        _ => COMPLETION_VARIABLE,
+    }
+}
+
+/// Collects the names declared in `statements` that are visible at
+/// `position`, outermost first. Named functions are hoisted; other
+/// declarations only count once they are written.
+fn visible_in_statements(
+    statements: &[ast::Statement],
+    position: Position,
+    names: &mut Vec<(String, u8)>,
+) {
+    for statement in statements {
+        let statement = match statement {
+            ast::Statement::Export(declaration) => declaration.as_ref(),
+            statement => statement,
+        };
+        match statement {
+            ast::Statement::FnDecl { name, .. } => {
+                names.push((name.0.clone(), COMPLETION_FUNCTION))
+            }
+            ast::Statement::Import { alias, .. } if alias.1.start < position => {
+                names.push((alias.0.clone(), COMPLETION_VARIABLE))
+            }
+            ast::Statement::Let(pattern, _, value) | ast::Statement::Const(pattern, _, value) => {
+                for name in pattern.identifiers() {
+                    if name.1.start < position {
+                        names.push((name.0.clone(), completion_kind(value)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for statement in statements {
+        visible_in_statement(statement, position, names);
+    }
+}
+
+fn visible_in_block(
+    block: &ast::BlockStatement,
+    declared: &[&ast::Identifier],
+    position: Position,
+    names: &mut Vec<(String, u8)>,
+) {
+    if contains(block, position) {
+        names.extend(declared.iter().map(|d| (d.0.clone(), COMPLETION_VARIABLE)));
+        visible_in_statements(&block.statements, position, names);
+    }
+}
+
+fn visible_in_statement(
+    statement: &ast::Statement,
+    position: Position,
+    names: &mut Vec<(String, u8)>,
+) {
+    match statement {
+        ast::Statement::Let(_, _, value)
+        | ast::Statement::Const(_, _, value)
+        | ast::Statement::Return(value, _)
+        | ast::Statement::Expr(value)
+        | ast::Statement::Throw(value, _) => visible_in_expression(value, position, names),
+        ast::Statement::While { condition, body } => {
+            visible_in_expression(condition, position, names);
+            visible_in_block(body, &[], position, names);
+        }
+        ast::Statement::For {
+            variable,
+            iterable,
+            body,
+        } => {
+            visible_in_expression(iterable, position, names);
+            visible_in_block(body, &[variable], position, names);
+        }
+        ast::Statement::FnDecl {
+            parameters, body, ..
+        } => {
+            let parameters: Vec<_> = parameters.iter().map(|p| &p.name).collect();
+            visible_in_block(body, &parameters, position, names);
+        }
+        ast::Statement::Export(declaration) => visible_in_statement(declaration, position, names),
+        ast::Statement::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            visible_in_block(body, &[], position, names);
+            if let Some(catch) = catch {
+                visible_in_block(&catch.body, &[&catch.parameter], position, names);
+            }
+            if let Some(finally) = finally {
+                visible_in_block(finally, &[], position, names);
+            }
+        }
+        ast::Statement::Import { .. } | ast::Statement::Break(_) | ast::Statement::Continue(_) => {}
+    }
+}
+
+fn visible_in_expression(
+    expression: &ast::Expression,
+    position: Position,
+    names: &mut Vec<(String, u8)>,
+) {
+    match expression {
+        ast::Expression::Function {
+            parameters, body, ..
+        } => {
+            let parameters: Vec<_> = parameters.iter().map(|p| &p.name).collect();
+            visible_in_block(body, &parameters, position, names);
+        }
+        ast::Expression::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } => {
+            visible_in_expression(condition, position, names);
+            visible_in_block(consequence, &[], position, names);
+            if let Some(alternative) = alternative {
+                visible_in_block(alternative, &[], position, names);
+            }
+        }
+        ast::Expression::Prefix { right: value, .. }
+        | ast::Expression::Member { object: value, .. }
+        | ast::Expression::Spread(value)
+        | ast::Expression::Propagate(value) => visible_in_expression(value, position, names),
+        ast::Expression::Lambda {
+            parameters,
+            body,
+            span,
+        } => {
+            if within(*span, position) {
+                names.extend(
+                    parameters
+                        .iter()
+                        .map(|p| (p.name.0.clone(), COMPLETION_VARIABLE)),
+                );
+                visible_in_expression(body, position, names);
+            }
+        }
+        ast::Expression::Infix { left, right, .. }
+        | ast::Expression::Assign {
+            target: left,
+            value: right,
+            ..
+        }
+        | ast::Expression::Index {
+            left, index: right, ..
+        }
+        | ast::Expression::Pipe {
+            value: left,
+            function: right,
+        }
+        | ast::Expression::Range {
+            start: left,
+            end: right,
+            ..
+        } => {
+            visible_in_expression(left, position, names);
+            visible_in_expression(right, position, names);
+        }
+        ast::Expression::Call {
+            function,
+            arguments,
+        } => {
+            visible_in_expression(function, position, names);
+            for argument in arguments {
+                visible_in_expression(argument, position, names);
+            }
+        }
+        ast::Expression::Match { value, arms } => {
+            visible_in_expression(value, position, names);
+            for arm in arms.iter().filter(|arm| within(arm.span, position)) {
+                for name in arm.pattern.identifiers() {
+                    names.push((name.0.clone(), COMPLETION_VARIABLE));
+                }
+                if let Some(guard) = &arm.guard {
+                    visible_in_expression(guard, position, names);
+                }
+                visible_in_expression(&arm.body, position, names);
+            }
+        }
+        ast::Expression::Ident(_)
+        | ast::Expression::IntegerLiteral { .. }
+        | ast::Expression::BooleanLiteral(_)
+        | ast::Expression::NullLiteral
+        | ast::Expression::StringLiteral { .. }
+        | ast::Expression::Template(_)
+        | ast::Expression::CharLiteral(_)
+        | ast::Expression::ByteStringLiteral(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        "let total = 0;\nfn add(n) {\n  let next = total + n;\n  next\n}\nadd(len(\"é\") + total);";
+
+    fn at(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    #[test]
+    fn test_positions() {
+        let document = Document::new(SOURCE.to_string());
+        // `é` is two bytes but one UTF-16 code unit.
+        assert_eq!(document.position(5, 9), at(6, 10));
+        assert_eq!(document.position(5, 10), at(6, 12));
+        assert_eq!(
+            document.lsp_position(at(6, 12)),
+            json!({"line": 5, "character": 10})
+        );
+        assert_eq!(document.position(9, 0), at(10, 1));
+    }
+
+    #[test]
+    fn test_hover_and_definition() {
+        let document = Document::new(SOURCE.to_string());
+        let hover = |line, column| {
+            document
+                .hover(at(line, column))
+                .map(|h| h["contents"]["value"].as_str().unwrap().to_string())
+        };
+        assert_eq!(hover(3, 15).as_deref(), Some("```\ntotal: int\n```"));
+        assert_eq!(
+            hover(6, 2).as_deref(),
+            Some("```\nadd: fn(int) -> int\n```")
+        );
+        assert_eq!(hover(6, 5).as_deref(), Some("```\nbuiltin len\n```"));
+        assert_eq!(hover(2, 11), None);
+
+        assert_eq!(
+            document.definition(at(3, 22)),
+            Some(json!({
+                "start": {"line": 1, "character": 7},
+                "end": {"line": 1, "character": 8},
+            }))
+        );
+        assert_eq!(
+            document.definition(at(6, 18)).unwrap()["start"],
+            json!({"line": 0, "character": 4})
+        );
+        assert_eq!(document.definition(at(6, 5)), None);
+    }
+
+    #[test]
+    fn test_symbols_and_completions() {
+        let document = Document::new(SOURCE.to_string());
+        let symbols = document.symbols();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0]["name"], "total");
+        assert_eq!(symbols[0]["detail"], "int");
+        assert_eq!(symbols[1]["name"], "add");
+        assert_eq!(symbols[1]["kind"], SYMBOL_FUNCTION);
+        assert_eq!(
+            symbols[1]["range"]["end"],
+            json!({"line": 4, "character": 1})
+        );
+        assert_eq!(symbols[1]["children"][0]["name"], "next");
+
+        let labels = |line, column| -> Vec<String> {
+            document
+                .completions(at(line, column))
+                .iter()
+                .map(|c| c["label"].as_str().unwrap().to_string())
+                .filter(|l| !BUILTINS.contains(&l.as_str()) && !KEYWORDS.contains(&l.as_str()))
+                .collect()
+        };
+        assert_eq!(labels(4, 3), vec!["next", "n", "add", "total"]);
+        assert_eq!(labels(3, 3), vec!["n", "add", "total"]);
+        assert_eq!(labels(1, 1), vec!["add"]);
+
+        let document = Document::new(
+            "let xs = 1;\nlet f = |item| item + xs;\nmatch (xs) { [head, ..tail] if head => tail, n => n }"
+                .to_string(),
+        );
+        let labels = |line, column| -> Vec<String> {
+            document
+                .completions(at(line, column))
+                .iter()
+                .map(|c| c["label"].as_str().unwrap().to_string())
+                .filter(|l| !BUILTINS.contains(&l.as_str()) && !KEYWORDS.contains(&l.as_str()))
+                .collect()
+        };
+        assert_eq!(labels(2, 20), vec!["item", "f", "xs"]);
+        assert_eq!(labels(3, 44), vec!["tail", "head", "f", "xs"]);
+        assert_eq!(labels(3, 36), vec!["tail", "head", "f", "xs"]);
+        assert_eq!(labels(3, 52), vec!["n", "f", "xs"]);
+        assert_eq!(labels(3, 13), vec!["f", "xs"]);
+        assert!(document
+            .completions(at(1, 1))
+            .contains(&json!({"label": "while", "kind": COMPLETION_KEYWORD})));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let document = Document::new("let x = ;\nlet y = 1;".to_string());
+        let diagnostics = document.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["severity"], SEVERITY_ERROR);
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({"line": 0, "character": 8})
+        );
+
+        let document = Document::new("let unused = 1;\nputs(missing);".to_string());
+        let diagnostics = document.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .map(|d| d["message"].as_str().unwrap())
+            .collect();
+        assert!(messages.contains(&"unknown identifier missing at 2:6"));
+        assert!(messages.contains(&"unused is declared at 1:5 but never used"));
+
+        let document = Document::new(
+            "puts(1);
+puts(1 + true);"
+                .to_string(),
+        );
+        let diagnostics = document.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0]["message"],
+            "mismatched types in (1 + true) at 2:8: expected int, found bool"
+        );
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({"line": 1, "character": 7})
+        );
+    }
+}
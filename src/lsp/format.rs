@@ -0,0 +1,82 @@
+use crate::lexer::{Lexer, Token};
+
+/// Re-indents `source` by how deeply each line is nested in brackets,
+/// trimming trailing whitespace and blank lines at the end. Works on tokens
+/// rather than the AST, so comments are kept; lines inside a multi-line
+/// string are left as they are.
+pub fn format(source: &str, indent: &str) -> String {
+    let mut lexer = Lexer::new(source);
+    let mut tokens = vec![];
+    loop {
+        let token = lexer.next_token();
+        if token == Token::EOF {
+            break;
+        }
+        tokens.push((token, lexer.span()));
+    }
+    let mut tokens = tokens.into_iter().peekable();
+
+    let mut formatted = String::new();
+    let mut depth: usize = 0;
+    let mut verbatim_until = 0;
+    for (i, line) in source.lines().enumerate() {
+        let number = i + 1;
+        let closes = matches!(
+            tokens.peek(),
+            Some((Token::Rbrace | Token::Rparen | Token::Rbracket, span)) if span.start.line == number
+        );
+
+        if number <= verbatim_until {
+            formatted.push_str(line);
+        } else if !line.trim().is_empty() {
+            let level = if closes {
+                depth.saturating_sub(1)
+            } else {
+                depth
+            };
+            formatted.push_str(&indent.repeat(level));
+            formatted.push_str(line.trim());
+        }
+        formatted.push('\n');
+
+        while let Some((token, span)) = tokens.next_if(|(_, span)| span.start.line == number) {
+            match token {
+                Token::Lbrace | Token::Lparen | Token::Lbracket => depth += 1,
+                Token::Rbrace | Token::Rparen | Token::Rbracket => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            verbatim_until = verbatim_until.max(span.end.line);
+        }
+    }
+
+    let trimmed = formatted.trim_end().len();
+    formatted.truncate(trimmed);
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let tests = vec![
+            (
+                "fn f(x) {\nif (x) {   \n// comment\nreturn [\n1,\n2\n];\n} else { 0 }\n}\n\n\n",
+                "fn f(x) {\n  if (x) {\n    // comment\n    return [\n      1,\n      2\n    ];\n  } else { 0 }\n}\n",
+            ),
+            (
+                "let s = \"\"\"\n  keep\n\"\"\";\n  let t = {\n\n\"k\": s };",
+                "let s = \"\"\"\n  keep\n\"\"\";\nlet t = {\n\n  \"k\": s };\n",
+            ),
+            ("", ""),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(format(input, "  "), expected);
+        }
+    }
+}
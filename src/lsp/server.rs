@@ -0,0 +1,340 @@
+use super::document::Document;
+use super::transport::{read_message, write_message};
+use crate::lexer::Position;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+enum RequestError {
+    #[error("cannot parse message: {0}")]
+    Parse(String),
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("unknown method {0}")]
+    MethodNotFound(String),
+    #[error("invalid params: {0}")]
+    InvalidParams(String),
+    #[error("server is not initialized")]
+    NotInitialized,
+}
+
+impl RequestError {
+    /// The JSON-RPC error code.
+    fn code(&self) -> i64 {
+        match self {
+            RequestError::Parse(_) => -32700,
+            RequestError::InvalidRequest(_) => -32600,
+            RequestError::MethodNotFound(_) => -32601,
+            RequestError::InvalidParams(_) => -32602,
+            RequestError::NotInitialized => -32002,
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, RequestError>;
+
+/// Serves the Language Server Protocol over `input` and `output` until the
+/// client sends `exit` or closes the input. Returns the exit code: 0 if the
+/// client asked to shut down first, 1 otherwise.
+pub fn run(mut input: impl BufRead, output: impl Write) -> io::Result<i32> {
+    let mut server = Server {
+        output,
+        documents: HashMap::new(),
+        initialized: false,
+        shut_down: false,
+    };
+
+    while let Some(body) = read_message(&mut input)? {
+        let message: Value = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                server.respond(Value::Null, Err(RequestError::Parse(e.to_string())))?;
+                continue;
+            }
+        };
+
+        if !message.is_object() {
+            let error = RequestError::InvalidRequest("expect a JSON-RPC message".to_string());
+            server.respond(Value::Null, Err(error))?;
+            continue;
+        }
+
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        match message.get("id") {
+            // A response to a request of ours; we send none.
+            Some(_) if method.is_empty() => {}
+            Some(id) => {
+                let result = server.request(method, params);
+                server.respond(id.clone(), result)?;
+            }
+            None if method == "exit" => break,
+            None => server.notification(method, params)?,
+        }
+    }
+    Ok(if server.shut_down { 0 } else { 1 })
+}
+
+struct Server<W> {
+    output: W,
+    documents: HashMap<String, Document>,
+    initialized: bool,
+    shut_down: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn respond(&mut self, id: Value, result: Result<Value>) -> io::Result<()> {
+        let response = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": e.code(), "message": e.to_string()},
+            }),
+        };
+        write_message(&mut self.output, &response)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        let notification = json!({"jsonrpc": "2.0", "method": method, "params": params});
+        write_message(&mut self.output, &notification)
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value> {
+        if self.shut_down {
+            return Err(RequestError::InvalidRequest(format!(
+                "{} after shutdown",
+                method
+            )));
+        }
+        if !self.initialized && method != "initialize" {
+            return Err(RequestError::NotInitialized);
+        }
+
+        match method {
+            "initialize" => {
+                self.initialized = true;
+                Ok(json!({
+                    "capabilities": {
+                        // Full document sync.
+                        "textDocumentSync": 1,
+                        "hoverProvider": true,
+                        "definitionProvider": true,
+                        "documentSymbolProvider": true,
+                        "completionProvider": {},
+                        "documentFormattingProvider": true,
+                    },
+                    "serverInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }))
+            }
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let (document, position) = self.document_position(params)?;
+                Ok(document.hover(position).unwrap_or(Value::Null))
+            }
+            "textDocument/definition" => {
+                let (document, position) = self.document_position(params)?;
+                Ok(match document.definition(position) {
+                    Some(range) => json!({"uri": params["textDocument"]["uri"], "range": range}),
+                    None => Value::Null,
+                })
+            }
+            "textDocument/documentSymbol" => Ok(json!(self.document(params)?.symbols())),
+            "textDocument/completion" => {
+                let (document, position) = self.document_position(params)?;
+                Ok(json!(document.completions(position)))
+            }
+            "textDocument/formatting" => {
+                let options = &params["options"];
+                let tab_size = options["tabSize"].as_u64().unwrap_or(4) as usize;
+                let insert_spaces = options["insertSpaces"].as_bool().unwrap_or(true);
+                Ok(json!(self
+                    .document(params)?
+                    .format(tab_size, insert_spaces)))
+            }
+            method => Err(RequestError::MethodNotFound(method.to_string())),
+        }
+    }
+
+    /// Handles a notification. Malformed and unknown notifications are
+    /// ignored, as there is no way to report them.
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let uri = match params["textDocument"]["uri"].as_str() {
+            Some(uri) => uri.to_string(),
+            None => return Ok(()),
+        };
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // With full sync, the last change holds the whole text.
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return self.notify(
+                    "textDocument/publishDiagnostics",
+                    json!({"uri": uri, "diagnostics": []}),
+                );
+            }
+            _ => None,
+        };
+
+        if let Some(text) = text {
+            let document = Document::new(text.to_string());
+            let diagnostics = document.diagnostics();
+            self.documents.insert(uri.clone(), document);
+            self.notify(
+                "textDocument/publishDiagnostics",
+                json!({"uri": uri, "diagnostics": diagnostics}),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn document(&self, params: &Value) -> Result<&Document> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or_else(|| RequestError::InvalidParams("missing textDocument.uri".to_string()))?;
+        self.documents
+            .get(uri)
+            .ok_or_else(|| RequestError::InvalidParams(format!("unknown document {}", uri)))
+    }
+
+    fn document_position(&self, params: &Value) -> Result<(&Document, Position)> {
+        let document = self.document(params)?;
+        let position = &params["position"];
+        match (position["line"].as_u64(), position["character"].as_u64()) {
+            (Some(line), Some(character)) => Ok((document, document.position(line, character))),
+            _ => Err(RequestError::InvalidParams("missing position".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the server over `messages` and returns the exit code and every
+    /// message it sent.
+    fn transcript(messages: &[Value]) -> (i32, Vec<Value>) {
+        let mut input = vec![];
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = vec![];
+        let code = run(&input[..], &mut output).unwrap();
+
+        let mut sent = vec![];
+        let mut output = &output[..];
+        while let Some(body) = read_message(&mut output).unwrap() {
+            sent.push(serde_json::from_slice(&body).unwrap());
+        }
+        (code, sent)
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "method": method, "params": params})
+    }
+
+    fn at(line: u64, character: u64) -> Value {
+        json!({"textDocument": {"uri": "file:///a.monkey"}, "position": {"line": line, "character": character}})
+    }
+
+    #[test]
+    fn test_session() {
+        let (code, sent) = transcript(&[
+            request(1, "initialize", json!({"capabilities": {}})),
+            notification("initialized", json!({})),
+            notification(
+                "textDocument/didOpen",
+                json!({"textDocument": {"uri": "file:///a.monkey", "languageId": "monkey", "version": 1, "text": "let x = ;"}}),
+            ),
+            notification(
+                "textDocument/didChange",
+                json!({"textDocument": {"uri": "file:///a.monkey", "version": 2}, "contentChanges": [{"text": "let x = 1;\nif (x) {\nputs(x)\n}"}]}),
+            ),
+            request(2, "textDocument/hover", at(2, 5)),
+            request(3, "textDocument/definition", at(2, 5)),
+            request(
+                4,
+                "textDocument/formatting",
+                json!({"textDocument": {"uri": "file:///a.monkey"}, "options": {"tabSize": 2, "insertSpaces": true}}),
+            ),
+            request(5, "textDocument/rename", at(0, 4)),
+            request(6, "shutdown", Value::Null),
+            request(7, "textDocument/hover", at(0, 4)),
+            notification("exit", Value::Null),
+        ]);
+        assert_eq!(code, 0);
+
+        assert_eq!(sent[0]["id"], 1);
+        assert_eq!(sent[0]["result"]["capabilities"]["hoverProvider"], true);
+
+        assert_eq!(sent[1]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(
+            sent[1]["params"]["diagnostics"][0]["message"],
+            "expect expression token. ;"
+        );
+        assert_eq!(sent[2]["params"]["diagnostics"], json!([]));
+
+        assert_eq!(sent[3]["result"]["contents"]["value"], "```\nx: int\n```");
+        assert_eq!(
+            sent[4]["result"],
+            json!({
+                "uri": "file:///a.monkey",
+                "range": {"start": {"line": 0, "character": 4}, "end": {"line": 0, "character": 5}},
+            })
+        );
+        assert_eq!(
+            sent[5]["result"][0]["newText"],
+            "let x = 1;\nif (x) {\n  puts(x)\n}\n"
+        );
+        assert_eq!(
+            sent[5]["result"][0]["range"]["end"],
+            json!({"line": 3, "character": 1})
+        );
+        assert_eq!(sent[6]["error"]["code"], -32601);
+        assert_eq!(sent[7]["result"], Value::Null);
+        assert_eq!(sent[8]["error"]["code"], -32600);
+        assert_eq!(sent.len(), 9);
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let (code, sent) = transcript(&[
+            request(1, "textDocument/hover", at(0, 0)),
+            request(2, "initialize", json!({})),
+            request(3, "textDocument/hover", at(0, 0)),
+            json!("not a message"),
+        ]);
+        assert_eq!(code, 1);
+        assert_eq!(sent[0]["error"]["code"], -32002);
+        assert_eq!(
+            sent[2]["error"]["message"],
+            "invalid params: unknown document file:///a.monkey"
+        );
+        assert_eq!(sent[3]["error"]["code"], -32600);
+        assert_eq!(sent.len(), 4);
+
+        let mut output = vec![];
+        let code = run(&b"Content-Length: 2\r\n\r\n{]"[..], &mut output).unwrap();
+        assert_eq!(code, 1);
+        let body = read_message(&mut &output[..]).unwrap().unwrap();
+        let response: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response["error"]["code"], -32700);
+        assert_eq!(response["id"], Value::Null);
+    }
+}
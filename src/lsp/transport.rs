@@ -0,0 +1,67 @@
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+/// Reads the body of one message framed by a `Content-Length` header.
+/// Returns `None` at the end of the input.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let value = value.trim().parse().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid Content-Length {:?}", value.trim()),
+                    )
+                })?;
+                length = Some(value);
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_framing() {
+        let mut output = vec![];
+        write_message(&mut output, &json!({"jsonrpc": "2.0", "method": "exit"})).unwrap();
+        write_message(&mut output, &json!({"text": "é"})).unwrap();
+        assert!(output.starts_with(b"Content-Length: 33\r\n\r\n{"));
+
+        let mut input = &output[..];
+        let first = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(first, br#"{"jsonrpc":"2.0","method":"exit"}"#);
+        let second = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(second, r#"{"text":"é"}"#.as_bytes());
+        assert_eq!(read_message(&mut input).unwrap(), None);
+
+        let mut input = &b"Content-Type: json\r\n\r\n{}"[..];
+        let error = read_message(&mut input).unwrap_err();
+        assert_eq!(error.to_string(), "missing Content-Length header");
+    }
+}
@@ -1,5 +1,236 @@
-use interpreter_rs::repl;
+use interpreter_rs::eval::Evaluator;
+use interpreter_rs::lint::{self, Config};
+use interpreter_rs::module::ModuleLoader;
+use interpreter_rs::{lsp, parser, repl, resolver, typeck};
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::thread;
+
+const USAGE: &str = "usage: interpreter-rs [run [--path <dir>]... <file> | check [--path <dir>]... <file> | lint [--json] [--config <file>] <file> | lsp]";
 
 fn main() {
-    repl::start();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let code = match args.as_slice() {
+        [] => {
+            repl::start();
+            0
+        }
+        ["run", options @ ..] => run(options),
+        ["check", options @ ..] => check(options),
+        ["lint", options @ ..] => lint(options),
+        ["lsp"] => lsp(),
+        _ => usage(),
+    };
+    process::exit(code);
+}
+
+/// Runs a file and the modules it imports, looking for imports as `check`
+/// does. Unknown names stop it before it starts; an error that no `catch`
+/// handles stops it with its location. Returns the exit code.
+fn run(options: &[&str]) -> i32 {
+    let options: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+
+    // Modules are loaded on the evaluator's thread, since they can't be sent
+    // to it. The evaluator recurses once per nested call, so the thread has
+    // room for the deepest recursion it allows.
+    let runner = thread::Builder::new()
+        .stack_size(Evaluator::STACK_SIZE)
+        .spawn(move || {
+            let options: Vec<&str> = options.iter().map(String::as_str).collect();
+            let (mut loader, file) = match module_options(&options) {
+                Some(options) => options,
+                None => return usage(),
+            };
+            let module = match loader.load(file) {
+                Ok(module) => module,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 1;
+                }
+            };
+
+            let mut failed = false;
+            for module in module.with_imports() {
+                for diagnostic in resolver::resolve(&module.program).diagnostics {
+                    if !diagnostic.is_warning() {
+                        eprintln!("{}: {}", module.id, diagnostic);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                return 1;
+            }
+
+            match Evaluator::new().run_module(&module) {
+                Ok(_) => 0,
+                Err(e) => {
+                    let id = e.module.as_ref().unwrap_or(&module.id);
+                    eprintln!("{}:{}", id, e);
+                    1
+                }
+            }
+        });
+    match runner {
+        Ok(runner) => runner
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+        Err(e) => {
+            eprintln!("cannot start the evaluator: {}", e);
+            1
+        }
+    }
+}
+
+/// Reads `[--path <dir>]... <file>`, returning a loader that searches each
+/// `--path` directory and the file to load, or `None` if the options are
+/// malformed.
+fn module_options<'a>(options: &[&'a str]) -> Option<(ModuleLoader, &'a str)> {
+    let mut loader = ModuleLoader::new();
+    let mut file = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match *option {
+            "--path" => loader.add_search_path(options.next()?),
+            path if file.is_none() && !path.starts_with("--") => file = Some(path),
+            _ => return None,
+        }
+    }
+    Some((loader, file?))
+}
+
+/// Resolves and type-checks a file and every module it imports, looking for
+/// imports in each `--path` directory after the importing file's own. Unused
+/// bindings are reported as warnings. Returns the exit code.
+fn check(options: &[&str]) -> i32 {
+    let (mut loader, file) = match module_options(options) {
+        Some(options) => options,
+        None => return usage(),
+    };
+
+    let module = match loader.load(file) {
+        Ok(module) => module,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    let mut failed = false;
+    for module in module.with_imports() {
+        for diagnostic in resolver::resolve(&module.program).diagnostics {
+            if diagnostic.is_warning() {
+                println!("{}: warning: {}", module.id, diagnostic);
+            } else {
+                println!("{}: {}", module.id, diagnostic);
+                failed = true;
+            }
+        }
+        for error in typeck::check(&module.program) {
+            println!("{}: {}", module.id, error);
+            failed = true;
+        }
+    }
+    i32::from(failed)
+}
+
+/// Lints a single file with the rules enabled in `--config`, or in
+/// `lint.conf` when that exists. Returns the exit code: 1 if anything was
+/// reported.
+fn lint(options: &[&str]) -> i32 {
+    let mut json = false;
+    let mut config_file = None;
+    let mut file = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match *option {
+            "--json" => json = true,
+            "--config" => match options.next() {
+                Some(path) => config_file = Some(*path),
+                None => return usage(),
+            },
+            path if file.is_none() && !path.starts_with("--") => file = Some(path),
+            _ => return usage(),
+        }
+    }
+    let file = match file {
+        Some(file) => file,
+        None => return usage(),
+    };
+
+    let config_file =
+        config_file.or_else(|| Some(lint::CONFIG_FILE).filter(|f| Path::new(f).is_file()));
+    let config = match config_file.map(|path| (path, fs::read_to_string(path))) {
+        None => Config::default(),
+        Some((path, Err(e))) => {
+            eprintln!("cannot read {}: {}", path, e);
+            return 1;
+        }
+        Some((path, Ok(source))) => match Config::parse(&source) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                return 1;
+            }
+        },
+    };
+
+    let source = match fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("cannot read {}: {}", file, e);
+            return 1;
+        }
+    };
+    let program = match parser::parse(&source) {
+        Ok(program) => program,
+        Err(errors) => {
+            eprintln!("cannot parse {}:\n{}", file, errors);
+            return 1;
+        }
+    };
+
+    let warnings = lint::lint(&source, &program, &config);
+    if json {
+        let warnings: Vec<_> = warnings
+            .iter()
+            .map(|w| {
+                json!({
+                    "file": file,
+                    "line": w.position.line,
+                    "column": w.position.column,
+                    "rule": w.rule.name(),
+                    "message": w.message,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::from(warnings));
+    } else {
+        for warning in &warnings {
+            println!("{}:{}", file, warning);
+        }
+    }
+    i32::from(!warnings.is_empty())
+}
+
+/// Serves the Language Server Protocol over stdin and stdout.
+fn lsp() -> i32 {
+    match lsp::run(io::stdin().lock(), io::stdout().lock()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("lsp: {}", e);
+            1
+        }
+    }
+}
+
+fn usage() -> i32 {
+    eprintln!("{}", USAGE);
+    2
 }
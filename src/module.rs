@@ -0,0 +1,351 @@
+use crate::parser::{self, ast, ParserErrors};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use thiserror::Error;
+
+/// Extension appended to import paths that don't already end in it.
+pub const EXTENSION: &str = "monkey";
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum ModuleId {
+    File(PathBuf),
+    Virtual(String),
+}
+
+impl fmt::Display for ModuleId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModuleId::File(path) => write!(f, "{}", path.display()),
+            ModuleId::Virtual(name) => write!(f, "<{}>", name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Module {
+    pub id: ModuleId,
+    pub program: ast::Program,
+    /// Names declared with `export`, in source order.
+    pub exports: Vec<String>,
+    /// Modules bound by the `import` statements, keyed by alias.
+    pub imports: Vec<(String, Rc<Module>)>,
+}
+
+impl Module {
+    /// This module followed by every module it imports, directly or not,
+    /// each listed once.
+    pub fn with_imports(&self) -> Vec<&Module> {
+        let mut modules = vec![self];
+        let mut i = 0;
+        while i < modules.len() {
+            let module = modules[i];
+            for (_, import) in &module.imports {
+                if !modules.iter().any(|m| m.id == import.id) {
+                    modules.push(import);
+                }
+            }
+            i += 1;
+        }
+        modules
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ModuleError {
+    #[error("cannot find module {path:?}{} (searched {})",
+        .importer.as_ref().map(|i| format!(" imported from {}", i)).unwrap_or_default(),
+        .searched.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "))]
+    NotFound {
+        path: String,
+        importer: Option<ModuleId>,
+        searched: Vec<PathBuf>,
+    },
+    #[error("circular import: {}", (.0).iter().map(|id| id.to_string()).collect::<Vec<_>>().join(" -> "))]
+    Cycle(Vec<ModuleId>),
+    #[error("cannot read module {id}: {source}")]
+    Io { id: ModuleId, source: io::Error },
+    #[error("cannot parse module {id}:\n{errors}")]
+    Parse { id: ModuleId, errors: ParserErrors },
+}
+
+type Result<T> = std::result::Result<T, ModuleError>;
+
+/// Resolves, parses and caches modules. Each module is loaded at most once,
+/// however many times it is imported.
+#[derive(Default)]
+pub struct ModuleLoader {
+    search_path: Vec<PathBuf>,
+    virtual_modules: HashMap<String, String>,
+    cache: HashMap<ModuleId, Rc<Module>>,
+    loading: Vec<ModuleId>,
+}
+
+impl ModuleLoader {
+    pub fn new() -> ModuleLoader {
+        ModuleLoader::default()
+    }
+
+    /// Adds a directory to search for imports not found next to the importing
+    /// file. Directories are searched in the order they were added.
+    pub fn add_search_path(&mut self, dir: impl Into<PathBuf>) {
+        self.search_path.push(dir.into());
+    }
+
+    /// Registers in-memory source for an import path. Virtual modules take
+    /// precedence over files.
+    pub fn add_virtual_module(&mut self, path: impl Into<String>, source: impl Into<String>) {
+        self.virtual_modules.insert(path.into(), source.into());
+    }
+
+    /// Loads the module at `path`, relative to the working directory, along
+    /// with everything it imports.
+    pub fn load(&mut self, path: &str) -> Result<Rc<Module>> {
+        let id = self.resolve(path, None)?;
+        self.load_module(id)
+    }
+
+    fn resolve(&self, path: &str, importer: Option<&ModuleId>) -> Result<ModuleId> {
+        if self.virtual_modules.contains_key(path) {
+            return Ok(ModuleId::Virtual(path.to_string()));
+        }
+
+        let relative_to = match importer {
+            Some(ModuleId::File(file)) => file.parent().map(Path::to_path_buf),
+            Some(ModuleId::Virtual(_)) => None,
+            None => Some(PathBuf::from(".")),
+        };
+        let mut searched = Vec::new();
+        for dir in relative_to.iter().chain(&self.search_path) {
+            // `lib/v1.2` names `lib/v1.2.monkey`, so any other extension is
+            // kept and added to rather than replaced.
+            let mut candidate = dir.join(path);
+            if candidate.extension() != Some(OsStr::new(EXTENSION)) {
+                candidate.as_mut_os_string().push(format!(".{}", EXTENSION));
+            }
+            if candidate.is_file() {
+                return fs::canonicalize(&candidate)
+                    .map(ModuleId::File)
+                    .map_err(|source| ModuleError::Io {
+                        id: ModuleId::File(candidate),
+                        source,
+                    });
+            }
+            searched.push(candidate);
+        }
+
+        Err(ModuleError::NotFound {
+            path: path.to_string(),
+            importer: importer.cloned(),
+            searched,
+        })
+    }
+
+    fn load_module(&mut self, id: ModuleId) -> Result<Rc<Module>> {
+        if let Some(module) = self.cache.get(&id) {
+            return Ok(Rc::clone(module));
+        }
+        if let Some(start) = self.loading.iter().position(|loading| *loading == id) {
+            let mut cycle = self.loading[start..].to_vec();
+            cycle.push(id);
+            return Err(ModuleError::Cycle(cycle));
+        }
+
+        let source = match &id {
+            ModuleId::File(path) => fs::read_to_string(path).map_err(|source| ModuleError::Io {
+                id: id.clone(),
+                source,
+            })?,
+            ModuleId::Virtual(path) => self.virtual_modules[path].clone(),
+        };
+        let program = parser::parse(&source).map_err(|errors| ModuleError::Parse {
+            id: id.clone(),
+            errors,
+        })?;
+
+        self.loading.push(id.clone());
+        let imports = self.load_imports(&id, &program);
+        self.loading.pop();
+
+        let module = Rc::new(Module {
+            id: id.clone(),
+            exports: exports(&program),
+            imports: imports?,
+            program,
+        });
+        self.cache.insert(id, Rc::clone(&module));
+        Ok(module)
+    }
+
+    fn load_imports(
+        &mut self,
+        importer: &ModuleId,
+        program: &ast::Program,
+    ) -> Result<Vec<(String, Rc<Module>)>> {
+        let mut imports = Vec::new();
+        for statement in &program.statements {
+            if let ast::Statement::Import { path, alias } = statement {
+                let id = self.resolve(path, Some(importer))?;
+                imports.push((alias.0.clone(), self.load_module(id)?));
+            }
+        }
+        Ok(imports)
+    }
+}
+
+fn exports(program: &ast::Program) -> Vec<String> {
+    let mut names = Vec::new();
+    for statement in &program.statements {
+        if let ast::Statement::Export(declaration) = statement {
+            match declaration.as_ref() {
+                ast::Statement::Let(pattern, _, _) | ast::Statement::Const(pattern, _, _) => {
+                    names.extend(pattern.identifiers().into_iter().map(|i| i.0.clone()))
+                }
+                ast::Statement::FnDecl { name, .. } => names.push(name.0.clone()),
+                _ => {}
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loader(modules: &[(&str, &str)]) -> ModuleLoader {
+        let mut loader = ModuleLoader::new();
+        for (path, source) in modules {
+            loader.add_virtual_module(*path, *source);
+        }
+        loader
+    }
+
+    /// A scratch directory under the system temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!(
+                "interpreter-rs-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn write(&self, path: &str, source: &str) -> PathBuf {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, source).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_virtual_modules_are_cached() {
+        let mut loader = loader(&[
+            ("main", "import \"a\" as a; import \"b\" as b;"),
+            ("a", "import \"util\" as u; export let x = 1;"),
+            ("b", "import \"util\" as u; export fn f() { 1 }"),
+            ("util", "export let {k, v} = pair; let hidden = 2;"),
+        ]);
+        let main = loader.load("main").unwrap();
+
+        let (alias, a) = &main.imports[0];
+        let (_, b) = &main.imports[1];
+        assert_eq!(alias, "a");
+        assert_eq!(a.exports, vec!["x"]);
+        assert_eq!(b.exports, vec!["f"]);
+        assert_eq!(a.imports[0].1.exports, vec!["k", "v"]);
+        assert!(Rc::ptr_eq(&a.imports[0].1, &b.imports[0].1));
+
+        let ids: Vec<String> = main
+            .with_imports()
+            .iter()
+            .map(|m| m.id.to_string())
+            .collect();
+        assert_eq!(ids, vec!["<main>", "<a>", "<b>", "<util>"]);
+    }
+
+    #[test]
+    fn test_module_errors() {
+        let tests = vec![
+            (
+                vec![("a", "import \"b\" as b;"), ("b", "import \"a\" as a;")],
+                "circular import: <a> -> <b> -> <a>",
+            ),
+            (
+                vec![("a", "import \"a\" as a;")],
+                "circular import: <a> -> <a>",
+            ),
+            (
+                vec![("a", "import \"missing\" as m;")],
+                "cannot find module \"missing\" imported from <a> (searched )",
+            ),
+            (
+                vec![("a", "export 1")],
+                "cannot parse module <a>:\n\texpect let, const or fn declaration after export. 1",
+            ),
+        ];
+
+        for (modules, expected) in tests {
+            match loader(&modules).load("a") {
+                Ok(module) => panic!("expected error, got {:?}", module),
+                Err(e) => assert_eq!(e.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_file_modules() {
+        let dir = TempDir::new("modules");
+        let main = dir.write(
+            "app/main.monkey",
+            "import \"lib/strings\" as s; import \"lib/v1.2\" as v; import \"utils.strings\" as u;",
+        );
+        dir.write("app/lib/v1.2.monkey", "export let v = 1;");
+        dir.write(
+            "app/utils.strings.monkey",
+            "import \"lib/v1.2.monkey\" as v;",
+        );
+        dir.write(
+            "app/lib/strings.monkey",
+            "import \"shared\" as shared; export let trim = 1;",
+        );
+        dir.write("vendor/shared.monkey", "export const version = 1;");
+
+        let mut loader = ModuleLoader::new();
+        match loader.load(main.to_str().unwrap()) {
+            Err(e) => assert!(e.to_string().contains("cannot find module \"shared\"")),
+            Ok(_) => panic!("expected shared to be missing"),
+        }
+
+        loader.add_search_path(dir.0.join("vendor"));
+        let module = loader.load(main.to_str().unwrap()).unwrap();
+        let strings = &module.imports[0].1;
+        assert_eq!(strings.exports, vec!["trim"]);
+        assert_eq!(strings.imports[0].1.exports, vec!["version"]);
+        assert_eq!(
+            strings.imports[0].1.id,
+            ModuleId::File(fs::canonicalize(dir.0.join("vendor/shared.monkey")).unwrap())
+        );
+
+        let (_, version) = &module.imports[1];
+        let (_, utils) = &module.imports[2];
+        assert_eq!(version.exports, vec!["v"]);
+        assert!(Rc::ptr_eq(version, &utils.imports[0].1));
+    }
+}
@@ -1,4 +1,6 @@
-mod ast;
-#[allow(dead_code)]
+pub mod ast;
+#[allow(dead_code, clippy::module_inception)]
 mod parser;
 mod precedence;
+
+pub use self::parser::{parse, ParserError, ParserErrors};
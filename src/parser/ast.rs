@@ -1,4 +1,4 @@
-use crate::lexer::Token;
+use crate::lexer::{Position, Span, Token};
 use std::fmt;
 
 #[derive(Debug, PartialEq, Clone, Default)]
@@ -22,29 +22,192 @@ impl fmt::Display for Program {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
-    Let(Identifier, Expression),
-    Return(Expression),
+    Let(Pattern, Option<TypeAnnotation>, Expression),
+    Const(Pattern, Option<TypeAnnotation>, Expression),
+    /// `return value;`, with the span of the `return` keyword.
+    Return(Expression, Span),
     Expr(Expression),
+    While {
+        condition: Expression,
+        body: BlockStatement,
+    },
+    For {
+        variable: Identifier,
+        iterable: Expression,
+        body: BlockStatement,
+    },
+    Break(Span),
+    Continue(Span),
+    /// `fn name(params) { body }`. The name is hoisted: it is bound before
+    /// any statement of the enclosing block runs, so declarations in the same
+    /// block can call each other regardless of their order.
+    FnDecl {
+        name: Identifier,
+        parameters: Vec<Parameter>,
+        return_type: Option<TypeAnnotation>,
+        body: BlockStatement,
+    },
+    /// `import "path" as alias;`. The path is resolved relative to the
+    /// importing file, then against the loader's search path.
+    Import {
+        path: String,
+        alias: Identifier,
+    },
+    /// An exported `let`, `const` or `fn` declaration.
+    Export(Box<Statement>),
+    Throw(Expression, Span),
+    /// `try { } catch (e) { } finally { }`. At least one of `catch` and
+    /// `finally` is present.
+    Try {
+        body: BlockStatement,
+        catch: Option<CatchClause>,
+        finally: Option<BlockStatement>,
+    },
+}
+
+impl Statement {
+    /// Where the statement starts, or its most telling position, if the AST
+    /// records one.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Statement::Let(pattern, _, value) | Statement::Const(pattern, _, value) => {
+                match pattern.identifiers().first() {
+                    Some(name) => Some(name.1.start),
+                    None => value.position(),
+                }
+            }
+            Statement::Return(_, span)
+            | Statement::Throw(_, span)
+            | Statement::Break(span)
+            | Statement::Continue(span) => Some(span.start),
+            Statement::Expr(expression) => expression.position(),
+            Statement::While { condition, body } => condition.position().or(Some(body.span.start)),
+            Statement::For { variable, .. } => Some(variable.1.start),
+            Statement::FnDecl { name, .. } => Some(name.1.start),
+            Statement::Import { alias, .. } => Some(alias.1.start),
+            Statement::Export(declaration) => declaration.position(),
+            Statement::Try { body, .. } => Some(body.span.start),
+        }
+    }
 }
 
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Statement::Let(identifier, expression) => {
-                write!(f, "let {} = {};", identifier, expression)
+            Statement::Let(pattern, annotation, expression) => {
+                write!(
+                    f,
+                    "let {}{} = {};",
+                    pattern,
+                    annotated(annotation),
+                    expression
+                )
             }
-            Statement::Return(expression) => write!(f, "return {};", expression),
+            Statement::Const(pattern, annotation, expression) => {
+                write!(
+                    f,
+                    "const {}{} = {};",
+                    pattern,
+                    annotated(annotation),
+                    expression
+                )
+            }
+            Statement::Return(expression, _) => write!(f, "return {};", expression),
             Statement::Expr(expression) => write!(f, "{}", expression),
+            Statement::While { condition, body } => write!(f, "while ({}) {}", condition, body),
+            Statement::For {
+                variable,
+                iterable,
+                body,
+            } => write!(f, "for ({} in {}) {}", variable, iterable, body),
+            Statement::Break(_) => write!(f, "break;"),
+            Statement::FnDecl {
+                name,
+                parameters,
+                return_type,
+                body,
+            } => write!(
+                f,
+                "fn {}({}){} {}",
+                name,
+                parameters
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                returning(return_type),
+                body
+            ),
+            Statement::Continue(_) => write!(f, "continue;"),
+            Statement::Import { path, alias } => {
+                write!(f, "import \"{}\" as {};", escape(path), alias)
+            }
+            Statement::Export(statement) => write!(f, "export {}", statement),
+            Statement::Throw(expression, _) => write!(f, "throw {};", expression),
+            Statement::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                write!(f, "try {}", body)?;
+                if let Some(catch) = catch {
+                    write!(f, " {}", catch)?;
+                }
+                if let Some(finally) = finally {
+                    write!(f, " finally {}", finally)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct Identifier(pub String);
+pub struct CatchClause {
+    pub parameter: Identifier,
+    pub body: BlockStatement,
+}
+
+impl fmt::Display for CatchClause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "catch ({}) {}", self.parameter, self.body)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct BlockStatement {
+    pub statements: Vec<Statement>,
+    /// From the opening `{` to just past the closing `}`.
+    pub span: Span,
+}
+
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{{}}}",
+            self.statements
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>()
+                .join("")
+        )
+    }
+}
+
+/// A name and the span it was written at. Identifiers compare by name only.
+#[derive(Debug, Clone)]
+pub struct Identifier(pub String, pub Span);
+
+impl PartialEq for Identifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
 
 impl fmt::Display for Identifier {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let Identifier(ident) = self;
+        let Identifier(ident, _) = self;
         write!(f, "{}", &ident)
     }
 }
@@ -52,27 +215,168 @@ impl fmt::Display for Identifier {
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     Ident(Identifier),
-    IntegerLiteral(i32),
+    IntegerLiteral {
+        token: Token,
+        value: i64,
+    },
     BooleanLiteral(bool),
+    NullLiteral,
+    StringLiteral {
+        value: String,
+        style: StringStyle,
+    },
+    Template(Vec<TemplateSegment>),
+    CharLiteral(char),
+    ByteStringLiteral(Vec<u8>),
+    /// `span` is the operator's, here and in `Infix`.
     Prefix {
         token: Token,
         operator: Operator,
         right: Box<Expression>,
+        span: Span,
     },
     Infix {
         token: Token,
         left: Box<Expression>,
         operator: Operator,
         right: Box<Expression>,
+        span: Span,
+    },
+    Assign {
+        token: Token,
+        target: Box<Expression>,
+        operator: Operator,
+        value: Box<Expression>,
+    },
+    Index {
+        left: Box<Expression>,
+        index: Box<Expression>,
+        optional: bool,
+    },
+    Member {
+        object: Box<Expression>,
+        property: Identifier,
+        optional: bool,
+    },
+    Function {
+        parameters: Vec<Parameter>,
+        return_type: Option<TypeAnnotation>,
+        body: BlockStatement,
     },
+    /// `|x, y| x + y`, a function whose body is a single expression.
+    Lambda {
+        parameters: Vec<Parameter>,
+        body: Box<Expression>,
+        /// From the opening `|` or `(` to just past the body.
+        span: Span,
+    },
+    Call {
+        function: Box<Expression>,
+        arguments: Vec<Expression>,
+    },
+    /// `...args`, only valid as a call argument.
+    Spread(Box<Expression>),
+    /// `value?`: unwraps an `ok` value, or returns an `err` from the
+    /// enclosing function.
+    Propagate(Box<Expression>),
+    /// `value |> function`, which calls `function` with `value` prepended to
+    /// its arguments: `x |> f(a)` means `f(x, a)` and `x |> f` means `f(x)`.
+    Pipe {
+        value: Box<Expression>,
+        function: Box<Expression>,
+    },
+    Range {
+        start: Box<Expression>,
+        end: Box<Expression>,
+        inclusive: bool,
+    },
+    /// `span` is the `if` keyword's.
+    If {
+        condition: Box<Expression>,
+        consequence: BlockStatement,
+        alternative: Option<BlockStatement>,
+        span: Span,
+    },
+    Match {
+        value: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+impl Expression {
+    /// The position of the expression's operator, or where it starts, if the
+    /// AST records one. Literals carry no position.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Expression::Ident(identifier) => Some(identifier.1.start),
+            Expression::Prefix { span, .. }
+            | Expression::Infix { span, .. }
+            | Expression::If { span, .. } => Some(span.start),
+            Expression::IntegerLiteral { .. }
+            | Expression::BooleanLiteral(_)
+            | Expression::NullLiteral
+            | Expression::StringLiteral { .. }
+            | Expression::CharLiteral(_)
+            | Expression::ByteStringLiteral(_) => None,
+            Expression::Template(segments) => segments.iter().find_map(|segment| match segment {
+                TemplateSegment::Interpolation { expression, .. } => expression.position(),
+                TemplateSegment::Literal(_) => None,
+            }),
+            Expression::Assign { target: value, .. }
+            | Expression::Index { left: value, .. }
+            | Expression::Call {
+                function: value, ..
+            }
+            | Expression::Spread(value)
+            | Expression::Propagate(value)
+            | Expression::Match { value, .. } => value.position(),
+            Expression::Member {
+                object, property, ..
+            } => object.position().or(Some(property.1.start)),
+            Expression::Pipe { value, function } => {
+                value.position().or_else(|| function.position())
+            }
+            Expression::Range { start, end, .. } => start.position().or_else(|| end.position()),
+            Expression::Function { body, .. } => Some(body.span.start),
+            Expression::Lambda {
+                parameters, body, ..
+            } => match parameters.first() {
+                Some(parameter) => Some(parameter.name.1.start),
+                None => body.position(),
+            },
+        }
+    }
 }
 
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Expression::Ident(indentifier) => write!(f, "{}", indentifier),
-            Expression::IntegerLiteral(val) => write!(f, "{}", val),
+            Expression::IntegerLiteral { token, .. } => write!(f, "{}", token),
             Expression::BooleanLiteral(bool) => write!(f, "{}", bool),
+            Expression::NullLiteral => write!(f, "null"),
+            Expression::StringLiteral { value, style } => match style {
+                StringStyle::Quoted => write!(f, "\"{}\"", escape(value)),
+                StringStyle::Raw { hashes } => {
+                    let hashes = "#".repeat(*hashes);
+                    write!(f, "r{}\"{}\"{}", hashes, value, hashes)
+                }
+                StringStyle::MultiLine => write!(f, "\"\"\"\n{}\n\"\"\"", value),
+            },
+            Expression::CharLiteral(val) => write!(f, "'{}'", val.escape_default()),
+            Expression::ByteStringLiteral(val) => write!(f, "b\"{}\"", val.escape_ascii()),
+            Expression::Template(segments) => {
+                write!(f, "\"")?;
+                for segment in segments {
+                    match segment {
+                        TemplateSegment::Literal(val) => write!(f, "{}", escape(val))?,
+                        TemplateSegment::Interpolation { source, .. } => {
+                            write!(f, "${{{}}}", source)?
+                        }
+                    }
+                }
+                write!(f, "\"")
+            }
             Expression::Prefix {
                 operator, right, ..
             } => write!(f, "({}{})", operator, right),
@@ -82,6 +386,304 @@ impl fmt::Display for Expression {
                 right,
                 ..
             } => write!(f, "({} {} {})", left, operator, right),
+            Expression::Assign {
+                target,
+                operator,
+                value,
+                ..
+            } => write!(f, "({} {} {})", target, operator, value),
+            Expression::Propagate(value) => write!(f, "({}?)", value),
+            Expression::Index {
+                left,
+                index,
+                optional,
+            } => {
+                let operator = if *optional { "?." } else { "" };
+                write!(f, "({}{}[{}])", left, operator, index)
+            }
+            Expression::Member {
+                object,
+                property,
+                optional,
+            } => {
+                let operator = if *optional { "?." } else { "." };
+                write!(f, "({}{}{})", object, operator, property)
+            }
+            Expression::Function {
+                parameters,
+                return_type,
+                body,
+            } => write!(
+                f,
+                "fn({}){} {}",
+                parameters
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                returning(return_type),
+                body
+            ),
+            Expression::Lambda {
+                parameters, body, ..
+            } => write!(
+                f,
+                "|{}| {}",
+                parameters
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                body
+            ),
+            Expression::Spread(value) => write!(f, "...{}", value),
+            Expression::Call {
+                function,
+                arguments,
+            } => write!(
+                f,
+                "{}({})",
+                function,
+                arguments
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Expression::Pipe { value, function } => write!(f, "({} |> {})", value, function),
+            Expression::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let operator = if *inclusive { "..=" } else { ".." };
+                write!(f, "({}{}{})", start, operator, end)
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                write!(f, "if ({}) {}", condition, consequence)?;
+                if let Some(alternative) = alternative {
+                    write!(f, " else {}", alternative)?;
+                }
+                Ok(())
+            }
+            Expression::Match { value, arms } => write!(
+                f,
+                "match ({}) {{{}}}",
+                value,
+                arms.iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Parameter {
+    pub name: Identifier,
+    pub annotation: Option<TypeAnnotation>,
+    pub default: Option<Expression>,
+    pub rest: bool,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let annotation = annotated(&self.annotation);
+        match &self.default {
+            _ if self.rest => write!(f, "...{}{}", self.name, annotation),
+            Some(default) => write!(f, "{}{} = {}", self.name, annotation, default),
+            None => write!(f, "{}{}", self.name, annotation),
+        }
+    }
+}
+
+/// A type written in the source, as in `let x: int` or `fn(a: [string]) -> bool`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeAnnotation {
+    Named(Identifier),
+    Array(Box<TypeAnnotation>),
+    Function {
+        parameters: Vec<TypeAnnotation>,
+        return_type: Box<TypeAnnotation>,
+    },
+}
+
+impl fmt::Display for TypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeAnnotation::Named(name) => write!(f, "{}", name),
+            TypeAnnotation::Array(element) => write!(f, "[{}]", element),
+            TypeAnnotation::Function {
+                parameters,
+                return_type,
+            } => write!(
+                f,
+                "fn({}) -> {}",
+                parameters
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                return_type
+            ),
+        }
+    }
+}
+
+fn annotated(annotation: &Option<TypeAnnotation>) -> String {
+    match annotation {
+        Some(annotation) => format!(": {}", annotation),
+        None => String::new(),
+    }
+}
+
+fn returning(return_type: &Option<TypeAnnotation>) -> String {
+    match return_type {
+        Some(return_type) => format!(" -> {}", return_type),
+        None => String::new(),
+    }
+}
+
+/// How a string literal was written, so that `Display` can write it back the
+/// same way.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StringStyle {
+    Quoted,
+    Raw { hashes: usize },
+    MultiLine,
+}
+
+/// A piece of an interpolated string. Interpolations keep their source text so
+/// that `Display` reproduces what was written.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplateSegment {
+    Literal(String),
+    Interpolation {
+        source: String,
+        expression: Expression,
+    },
+}
+
+fn escape(val: &str) -> String {
+    let mut escaped = String::with_capacity(val.len());
+    let mut chars = val.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '$' if chars.peek() == Some(&'{') => escaped.push_str("\\$"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Expression,
+    /// From the start of the pattern to just past the body.
+    pub span: Span,
+}
+
+impl fmt::Display for MatchArm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.guard {
+            Some(guard) => write!(f, "{} if {} => {}", self.pattern, guard, self.body),
+            None => write!(f, "{} => {}", self.pattern, self.body),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(Identifier),
+    Literal(Expression),
+    Range {
+        start: Expression,
+        end: Expression,
+        inclusive: bool,
+    },
+    Array(Vec<Pattern>),
+    Rest(Option<Identifier>),
+    Hash(Vec<(Identifier, Pattern)>),
+    Default {
+        pattern: Box<Pattern>,
+        default: Expression,
+    },
+}
+
+impl Pattern {
+    /// The names this pattern binds, in source order.
+    pub fn identifiers(&self) -> Vec<&Identifier> {
+        match self {
+            Pattern::Binding(identifier) | Pattern::Rest(Some(identifier)) => vec![identifier],
+            Pattern::Array(elements) => elements.iter().flat_map(|e| e.identifiers()).collect(),
+            Pattern::Hash(fields) => fields.iter().flat_map(|(_, p)| p.identifiers()).collect(),
+            Pattern::Default { pattern, .. } => pattern.identifiers(),
+            Pattern::Wildcard
+            | Pattern::Literal(_)
+            | Pattern::Range { .. }
+            | Pattern::Rest(None) => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding(identifier) => write!(f, "{}", identifier),
+            Pattern::Literal(literal) => write!(f, "{}", literal),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let operator = if *inclusive { "..=" } else { ".." };
+                write!(f, "{}{}{}", start, operator, end)
+            }
+            Pattern::Array(elements) => write!(
+                f,
+                "[{}]",
+                elements
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Pattern::Rest(Some(identifier)) => write!(f, "..{}", identifier),
+            Pattern::Rest(None) => write!(f, ".."),
+            Pattern::Default { pattern, default } => write!(f, "{} = {}", pattern, default),
+            Pattern::Hash(fields) => write!(
+                f,
+                "{{{}}}",
+                fields
+                    .iter()
+                    .map(|(key, pattern)| match pattern {
+                        Pattern::Binding(binding) if binding == key => key.to_string(),
+                        Pattern::Default { pattern, default }
+                            if **pattern == Pattern::Binding(key.clone()) =>
+                        {
+                            format!("{} = {}", key, default)
+                        }
+                        pattern => format!("{}: {}", key, pattern),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
         }
     }
 }
@@ -89,30 +691,66 @@ impl fmt::Display for Expression {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Operator {
     Assign,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
     Plus,
     Minus,
     Bang,
     Asterisk,
+    Power,
     Slash,
+    Percent,
     Lt,
     Gt,
+    LtEq,
+    GtEq,
     Eq,
     Neq,
+    And,
+    Or,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
+    In,
+    Coalesce,
 }
 
 impl fmt::Display for Operator {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let val = match self {
             Operator::Assign => "=",
+            Operator::PlusAssign => "+=",
+            Operator::MinusAssign => "-=",
+            Operator::AsteriskAssign => "*=",
+            Operator::SlashAssign => "/=",
             Operator::Plus => "+",
             Operator::Minus => "-",
             Operator::Bang => "!",
             Operator::Asterisk => "*",
+            Operator::Power => "**",
             Operator::Slash => "/",
+            Operator::Percent => "%",
             Operator::Lt => "<",
             Operator::Gt => ">",
+            Operator::LtEq => "<=",
+            Operator::GtEq => ">=",
             Operator::Eq => "==",
             Operator::Neq => "!=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Ampersand => "&",
+            Operator::Pipe => "|",
+            Operator::Caret => "^",
+            Operator::Tilde => "~",
+            Operator::ShiftLeft => "<<",
+            Operator::ShiftRight => ">>",
+            Operator::In => "in",
+            Operator::Coalesce => "??",
         };
 
         write!(f, "{}", val)
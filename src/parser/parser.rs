@@ -2,18 +2,44 @@ use super::ast;
 use super::precedence::Precedence;
 use crate::lexer::Lexer;
 use crate::lexer::Token;
+use crate::lexer::{Position, Span, TemplatePart};
+use std::collections::HashMap;
 use thiserror::Error;
 
 struct Parser {
     lexer: Lexer,
     current_token: Token,
     peek_token: Token,
+    current_span: Span,
+    peek_span: Span,
     errors: ParserErrors,
+    loop_depth: usize,
+    function_depth: usize,
+    /// Set while parsing a match guard outside any brackets, where `=>`
+    /// ends the guard rather than following a lambda's parameters.
+    in_guard: bool,
+    scopes: Vec<HashMap<String, Declaration>>,
 }
 
-#[derive(Error, Debug, Clone)]
-#[error("{}", (.0).iter().map(|e| format!("\x32{}", e)).collect::<Vec<_>>().join("\n"))]
-pub struct ParserErrors(Vec<ParserError>);
+#[derive(Clone, Copy)]
+enum Declaration {
+    Let(Position),
+    Const(Position),
+    Function(Position),
+    Parameter,
+}
+
+/// The errors found in a program, each with the span of the token the
+/// parser had reached when it gave up on the statement.
+#[derive(Error, Debug, Clone, Default)]
+#[error("{}", (.0).iter().map(|e| format!("\t{}", e)).collect::<Vec<_>>().join("\n"))]
+pub struct ParserErrors(Vec<ParserError>, Vec<Span>);
+
+impl ParserErrors {
+    pub fn iter(&self) -> impl Iterator<Item = (&ParserError, Span)> {
+        self.0.iter().zip(self.1.iter().copied())
+    }
+}
 
 #[derive(Error, Debug, Clone)]
 pub enum ParserError {
@@ -21,10 +47,87 @@ pub enum ParserError {
     ExpectToken { expected: String, found: String },
     #[error("expect expression token. {0}")]
     ExpectExpression(String),
-    #[error("unable to parse integer. {0}")]
-    UnableToParseInteger(String),
+    #[error("invalid literal. {0}")]
+    InvalidLiteral(String),
+    #[error("integer literal out of range for {range}. {literal}")]
+    IntegerOutOfRange {
+        literal: String,
+        range: &'static str,
+    },
+    #[error("missing digits in integer literal. {0}")]
+    MissingDigits(String),
+    #[error("`_` must separate two digits in integer literal. {0}")]
+    MisplacedSeparator(String),
+    #[error("invalid digit {digit:?} for base {radix} in integer literal. {literal}")]
+    InvalidDigit {
+        literal: String,
+        digit: char,
+        radix: u32,
+    },
     #[error("unable to parse operator. {0}")]
     UnableToParseOperator(String),
+    #[error("invalid assignment target. {0}")]
+    InvalidAssignmentTarget(String),
+    #[error("range cannot be an operand of another range. {0}")]
+    ChainedRange(String),
+    #[error("{0} outside of a loop")]
+    OutsideLoop(String),
+    #[error("{0} outside of a function")]
+    OutsideFunction(String),
+    #[error("expect type. {0}")]
+    ExpectType(String),
+    #[error("expect pattern token. {0}")]
+    ExpectPattern(String),
+    #[error("more than one rest pattern in array pattern. {0}")]
+    MultipleRestPatterns(String),
+    #[error("invalid binding pattern. {0}")]
+    InvalidBindingPattern(String),
+    #[error("invalid string interpolation. ${{{0}}}")]
+    InvalidInterpolation(String),
+    #[error(
+        "{name} is declared twice in the same scope at {redeclared} (first declared at {declared})"
+    )]
+    DuplicateDeclaration {
+        name: String,
+        declared: Position,
+        redeclared: Position,
+    },
+    #[error("{0} is only allowed at the top level of a module")]
+    NotTopLevel(String),
+    #[error("expect let, const or fn declaration after export. {0}")]
+    ExpectExportDeclaration(String),
+    #[error("try block without catch or finally. {0}")]
+    MissingCatchOrFinally(String),
+    #[error("duplicate parameter {0}")]
+    DuplicateParameter(String),
+    #[error("parameter {0} without a default follows a parameter with a default")]
+    RequiredParameterAfterDefault(String),
+    #[error("rest parameter ...{0} must be the last parameter")]
+    RestParameterNotLast(String),
+    #[error("too many arguments in {call}: expected at most {expected}, found {found}")]
+    TooManyArguments {
+        call: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("cannot assign to const {name} at {assigned} (declared at {declared})")]
+    ConstAssignment {
+        name: String,
+        declared: Position,
+        assigned: Position,
+    },
+    #[error("cannot redeclare const {name} at {redeclared} (declared at {declared})")]
+    ConstRedeclaration {
+        name: String,
+        declared: Position,
+        redeclared: Position,
+    },
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum PatternKind {
+    Match,
+    Binding,
 }
 
 type ParserResult<T> = std::result::Result<T, ParserErrors>;
@@ -36,7 +139,13 @@ impl Parser {
             lexer,
             current_token: Token::Illegal,
             peek_token: Token::Illegal,
-            errors: ParserErrors(Vec::new()),
+            current_span: Span::default(),
+            peek_span: Span::default(),
+            errors: ParserErrors::default(),
+            loop_depth: 0,
+            function_depth: 0,
+            in_guard: false,
+            scopes: vec![HashMap::new()],
         };
 
         parser.next_token();
@@ -47,7 +156,9 @@ impl Parser {
 
     fn next_token(&mut self) {
         self.current_token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token()
+        self.current_span = self.peek_span;
+        self.peek_token = self.lexer.next_token();
+        self.peek_span = self.lexer.span();
     }
 
     fn expect_peek(&mut self, token: Token) -> Result<()> {
@@ -64,14 +175,8 @@ impl Parser {
 
     fn peek_token_is(&mut self, t: &Token) -> bool {
         match t {
-            Token::Ident(_) => match self.peek_token {
-                Token::Ident(_) => true,
-                _ => false,
-            },
-            Token::Int(_) => match self.peek_token {
-                Token::Int(_) => true,
-                _ => false,
-            },
+            Token::Ident(_) => matches!(self.peek_token, Token::Ident(_)),
+            Token::Int(_) => matches!(self.peek_token, Token::Int(_)),
             t => self.peek_token == *t,
         }
     }
@@ -84,6 +189,7 @@ impl Parser {
                 Ok(statement) => program.statements.push(statement),
                 Err(e) => {
                     self.errors.0.push(e);
+                    self.errors.1.push(self.current_span);
                 }
             }
             self.next_token();
@@ -98,19 +204,172 @@ impl Parser {
 
     fn parse_statement(&mut self) -> Result<ast::Statement> {
         match self.current_token {
-            Token::Let => self.parse_let_statement(),
+            Token::Let | Token::Const => self.parse_let_statement(),
+            Token::Function if matches!(self.peek_token, Token::Ident(_)) => {
+                self.parse_function_declaration()
+            }
             Token::Return => self.parse_return_statement(),
+            Token::While => self.parse_while_statement(),
+            Token::For => self.parse_for_statement(),
+            Token::Break | Token::Continue => self.parse_loop_control_statement(),
+            Token::Import => self.parse_import_statement(),
+            Token::Export => self.parse_export_statement(),
+            Token::Throw => self.parse_throw_statement(),
+            Token::Try => self.parse_try_statement(),
             _ => self.parse_expression_statement(),
         }
     }
 
-    fn parse_let_statement(&mut self) -> Result<ast::Statement> {
+    fn parse_import_statement(&mut self) -> Result<ast::Statement> {
+        let position = self.current_span.start;
+        self.expect_top_level()?;
+        self.next_token();
+        let path = match &self.current_token {
+            Token::String(path) => path.clone(),
+            token => {
+                return Err(ParserError::ExpectToken {
+                    expected: "module path string".to_string(),
+                    found: format!("{}", token),
+                })
+            }
+        };
+        self.expect_peek(Token::As)?;
         self.expect_peek(Token::Ident("_".to_string()))?;
+        let alias = self.current_identifier();
+        while self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
+        }
 
-        let name = ast::Identifier(match &self.current_token {
-            Token::Ident(ident) => ident.clone(),
-            _ => unreachable!(),
-        });
+        // Module namespaces cannot be reassigned.
+        self.declare(&alias, Declaration::Const(position));
+
+        Ok(ast::Statement::Import { path, alias })
+    }
+
+    fn parse_export_statement(&mut self) -> Result<ast::Statement> {
+        self.expect_top_level()?;
+        self.next_token();
+        let statement = match self.current_token {
+            Token::Let | Token::Const => self.parse_let_statement()?,
+            Token::Function if matches!(self.peek_token, Token::Ident(_)) => {
+                self.parse_function_declaration()?
+            }
+            _ => {
+                return Err(ParserError::ExpectExportDeclaration(format!(
+                    "{}",
+                    self.current_token
+                )))
+            }
+        };
+
+        Ok(ast::Statement::Export(Box::new(statement)))
+    }
+
+    fn expect_top_level(&self) -> Result<()> {
+        if self.scopes.len() > 1 {
+            return Err(ParserError::NotTopLevel(format!("{}", self.current_token)));
+        }
+        Ok(())
+    }
+
+    fn parse_while_statement(&mut self) -> Result<ast::Statement> {
+        self.expect_peek(Token::Lparen)?;
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(Token::Rparen)?;
+        self.expect_peek(Token::Lbrace)?;
+        let body = self.parse_loop_body()?;
+
+        Ok(ast::Statement::While { condition, body })
+    }
+
+    fn parse_for_statement(&mut self) -> Result<ast::Statement> {
+        self.expect_peek(Token::Lparen)?;
+        self.expect_peek(Token::Ident("_".to_string()))?;
+        let variable = self.current_identifier();
+        let declaration = Declaration::Let(self.current_span.start);
+        // The variable lives in the loop's own scope, but may still not
+        // shadow a const declared in the scope the loop is written in.
+        let scope = self.scopes.last().expect("parser has no scope");
+        if let Some(Declaration::Const(declared)) = scope.get(&variable.0) {
+            let error = ParserError::ConstRedeclaration {
+                name: variable.0.clone(),
+                declared: *declared,
+                redeclared: self.current_span.start,
+            };
+            self.report(error, variable.1);
+        }
+        self.expect_peek(Token::In)?;
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(Token::Rparen)?;
+        self.expect_peek(Token::Lbrace)?;
+        let scope = HashMap::from([(variable.0.clone(), declaration)]);
+        let body = self.in_scope(scope, |parser| parser.parse_loop_body())?;
+
+        Ok(ast::Statement::For {
+            variable,
+            iterable,
+            body,
+        })
+    }
+
+    fn parse_loop_body(&mut self) -> Result<ast::BlockStatement> {
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn parse_loop_control_statement(&mut self) -> Result<ast::Statement> {
+        if self.loop_depth == 0 {
+            return Err(ParserError::OutsideLoop(format!("{}", self.current_token)));
+        }
+
+        let statement = match self.current_token {
+            Token::Break => ast::Statement::Break(self.current_span),
+            _ => ast::Statement::Continue(self.current_span),
+        };
+        if self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
+        }
+
+        Ok(statement)
+    }
+
+    fn parse_block_statement(&mut self) -> Result<ast::BlockStatement> {
+        self.in_scope(HashMap::new(), |parser| parser.parse_block_contents())
+    }
+
+    fn parse_block_contents(&mut self) -> Result<ast::BlockStatement> {
+        let mut block = ast::BlockStatement::default();
+        block.span.start = self.current_span.start;
+        self.next_token();
+
+        while self.current_token != Token::Rbrace {
+            if self.current_token == Token::EOF {
+                return Err(ParserError::ExpectToken {
+                    expected: format!("{}", Token::Rbrace),
+                    found: format!("{}", Token::EOF),
+                });
+            }
+            block.statements.push(self.parse_statement()?);
+            self.next_token();
+        }
+
+        block.span.end = self.current_span.end;
+        Ok(block)
+    }
+
+    fn parse_let_statement(&mut self) -> Result<ast::Statement> {
+        let position = self.current_span.start;
+        let declaration = match self.current_token {
+            Token::Const => Declaration::Const(position),
+            _ => Declaration::Let(position),
+        };
+        self.next_token();
+        let pattern = self.parse_binding_pattern()?;
+        let annotation = self.parse_optional_annotation()?;
 
         self.expect_peek(Token::Assign)?;
 
@@ -120,17 +379,127 @@ impl Parser {
             self.next_token();
         }
 
-        Ok(ast::Statement::Let(name, literal))
+        for identifier in pattern.identifiers() {
+            self.declare(identifier, declaration);
+        }
+
+        Ok(match declaration {
+            Declaration::Const(_) => ast::Statement::Const(pattern, annotation, literal),
+            _ => ast::Statement::Let(pattern, annotation, literal),
+        })
+    }
+
+    /// Records an error that does not stop the rest of the statement from
+    /// being parsed.
+    fn report(&mut self, error: ParserError, span: Span) {
+        self.errors.0.push(error);
+        self.errors.1.push(span);
+    }
+
+    /// Binds `identifier` in the current scope. A clash with an earlier
+    /// declaration is reported, and the earlier one is kept.
+    fn declare(&mut self, identifier: &ast::Identifier, declaration: Declaration) {
+        let ast::Identifier(name, span) = identifier;
+        let scope = self.scopes.last_mut().expect("parser has no scope");
+        let position = match declaration {
+            Declaration::Let(position)
+            | Declaration::Const(position)
+            | Declaration::Function(position) => position,
+            Declaration::Parameter => Position::default(),
+        };
+
+        let error = match (scope.get(name), declaration) {
+            (Some(Declaration::Const(declared)), _) => ParserError::ConstRedeclaration {
+                name: name.clone(),
+                declared: *declared,
+                redeclared: position,
+            },
+            // Named functions are hoisted, so they may not share their scope
+            // with any other declaration of the same name.
+            (Some(Declaration::Function(declared)), _)
+            | (Some(Declaration::Let(declared)), Declaration::Function(_)) => {
+                ParserError::DuplicateDeclaration {
+                    name: name.clone(),
+                    declared: *declared,
+                    redeclared: position,
+                }
+            }
+            _ => {
+                scope.insert(name.clone(), declaration);
+                return;
+            }
+        };
+        self.report(error, *span);
+    }
+
+    fn lookup(&self, name: &str) -> Option<Declaration> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
     }
 
     fn parse_return_statement(&mut self) -> Result<ast::Statement> {
+        let span = self.current_span;
         self.next_token();
         let literal = self.parse_expression(Precedence::Lowest)?;
         while self.peek_token_is(&Token::Semicolon) {
             self.next_token();
         }
 
-        Ok(ast::Statement::Return(literal))
+        Ok(ast::Statement::Return(literal, span))
+    }
+
+    fn parse_throw_statement(&mut self) -> Result<ast::Statement> {
+        let span = self.current_span;
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+        while self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
+        }
+
+        Ok(ast::Statement::Throw(value, span))
+    }
+
+    fn parse_try_statement(&mut self) -> Result<ast::Statement> {
+        self.expect_peek(Token::Lbrace)?;
+        let body = self.parse_block_statement()?;
+
+        let catch = if self.peek_token_is(&Token::Catch) {
+            self.next_token();
+            self.expect_peek(Token::Lparen)?;
+            self.expect_peek(Token::Ident("_".to_string()))?;
+            let parameter = self.current_identifier();
+            let declaration = Declaration::Let(self.current_span.start);
+            self.expect_peek(Token::Rparen)?;
+            self.expect_peek(Token::Lbrace)?;
+            let scope = HashMap::from([(parameter.0.clone(), declaration)]);
+            let body = self.in_scope(scope, |parser| parser.parse_block_statement())?;
+            Some(ast::CatchClause { parameter, body })
+        } else {
+            None
+        };
+
+        let finally = if self.peek_token_is(&Token::Finally) {
+            self.next_token();
+            self.expect_peek(Token::Lbrace)?;
+            Some(self.parse_block_statement()?)
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(ParserError::MissingCatchOrFinally(format!(
+                "{}",
+                self.peek_token
+            )));
+        }
+
+        Ok(ast::Statement::Try {
+            body,
+            catch,
+            finally,
+        })
     }
 
     fn parse_expression_statement(&mut self) -> Result<ast::Statement> {
@@ -148,8 +517,21 @@ impl Parser {
             Token::Ident(_) => self.parse_identifier()?,
             Token::Int(_) => self.parse_integer_literal()?,
             Token::True | Token::False => self.parse_boolean_literal()?,
-            Token::Bang | Token::Minus => self.parse_prefix_expression()?,
+            Token::Null => ast::Expression::NullLiteral,
+            Token::String(_) | Token::RawString { .. } | Token::MultiLineString(_) => {
+                self.parse_string_literal()?
+            }
+            Token::Template(_) => self.parse_template_literal()?,
+            Token::Char(val) => ast::Expression::CharLiteral(*val),
+            Token::ByteString(val) => ast::Expression::ByteStringLiteral(val.clone()),
+            Token::Bang | Token::Minus | Token::Tilde => self.parse_prefix_expression()?,
+            Token::Lparen if !self.in_guard && self.arrow_follows() => self.parse_arrow_lambda()?,
             Token::Lparen => self.parse_group_expression()?,
+            Token::If => self.parse_if_expression()?,
+            Token::Match => self.parse_match_expression()?,
+            Token::Function => self.parse_function_literal()?,
+            Token::Pipe | Token::Or => self.parse_lambda_literal()?,
+            Token::Invalid(reason) => return Err(ParserError::InvalidLiteral(reason.clone())),
             t => return Err(ParserError::ExpectExpression(format!("{}", t))),
         };
 
@@ -161,13 +543,62 @@ impl Parser {
                 | Token::Minus
                 | Token::Slash
                 | Token::Asterisk
+                | Token::Power
+                | Token::Percent
                 | Token::Eq
                 | Token::Neq
                 | Token::Lt
-                | Token::Gt => {
+                | Token::Gt
+                | Token::LtEq
+                | Token::GtEq
+                | Token::And
+                | Token::Or
+                | Token::Ampersand
+                | Token::Pipe
+                | Token::Caret
+                | Token::ShiftLeft
+                | Token::ShiftRight
+                | Token::In
+                | Token::Coalesce => {
                     self.next_token();
                     left = self.parse_infix_expression(Box::new(left))?;
                 }
+                Token::Assign
+                | Token::PlusAssign
+                | Token::MinusAssign
+                | Token::AsteriskAssign
+                | Token::SlashAssign => {
+                    self.next_token();
+                    left = self.parse_assign_expression(Box::new(left))?;
+                }
+                Token::Pipeline => {
+                    self.next_token();
+                    left = self.parse_pipe_expression(Box::new(left))?;
+                }
+                Token::DotDot | Token::DotDotEq => {
+                    self.next_token();
+                    left = self.parse_range_expression(Box::new(left))?;
+                }
+                Token::Lparen => {
+                    self.next_token();
+                    left = self.parse_call_expression(Box::new(left))?;
+                }
+                Token::Lbracket => {
+                    self.next_token();
+                    left = self.parse_index_expression(Box::new(left), false)?;
+                }
+                Token::Dot => {
+                    self.next_token();
+                    left = self.parse_member_expression(Box::new(left), false)?;
+                }
+                Token::QuestionDot => {
+                    self.next_token();
+                    left = self.parse_optional_chain_expression(Box::new(left))?;
+                }
+                Token::Question => {
+                    self.next_token();
+                    left = self.parse_propagate_expression(Box::new(left))?;
+                }
                 _ => break,
             };
         }
@@ -183,20 +614,83 @@ impl Parser {
             })?,
         };
 
-        Ok(ast::Expression::Ident(ast::Identifier(val)))
+        Ok(ast::Expression::Ident(ast::Identifier(
+            val,
+            self.current_span,
+        )))
     }
 
     fn parse_integer_literal(&mut self) -> Result<ast::Expression> {
-        let parsed = match &self.current_token {
-            Token::Int(val) => val.clone().parse::<i32>().map_err(|_| {
-                ParserError::UnableToParseInteger(format!("{}", self.current_token))
-            })?,
+        let value = match &self.current_token {
+            Token::Int(val) => parse_to_integer(val)?,
             t => Err(ParserError::ExpectToken {
                 expected: "Int".to_string(),
                 found: format!("{}", t),
             })?,
         };
-        Ok(ast::Expression::IntegerLiteral(parsed))
+        Ok(ast::Expression::IntegerLiteral {
+            token: self.current_token.clone(),
+            value,
+        })
+    }
+
+    fn parse_string_literal(&mut self) -> Result<ast::Expression> {
+        let (value, style) = match &self.current_token {
+            Token::String(val) => (val.clone(), ast::StringStyle::Quoted),
+            Token::RawString { value, hashes } => {
+                (value.clone(), ast::StringStyle::Raw { hashes: *hashes })
+            }
+            Token::MultiLineString(val) => (val.clone(), ast::StringStyle::MultiLine),
+            t => Err(ParserError::ExpectToken {
+                expected: "String".to_string(),
+                found: format!("{}", t),
+            })?,
+        };
+        Ok(ast::Expression::StringLiteral { value, style })
+    }
+
+    fn parse_template_literal(&mut self) -> Result<ast::Expression> {
+        let parts = match &self.current_token {
+            Token::Template(parts) => parts.clone(),
+            t => Err(ParserError::ExpectToken {
+                expected: "Template".to_string(),
+                found: format!("{}", t),
+            })?,
+        };
+
+        let mut segments = vec![];
+        for part in parts {
+            segments.push(match part {
+                TemplatePart::Literal(val) => ast::TemplateSegment::Literal(val),
+                TemplatePart::Interpolation { source, start } => {
+                    let expression = self.parse_interpolation(&source, start)?;
+                    ast::TemplateSegment::Interpolation { source, expression }
+                }
+            });
+        }
+
+        Ok(ast::Expression::Template(segments))
+    }
+
+    /// Parses the source of a `${...}` with a parser of its own that borrows
+    /// this one's scopes and loop and function depths, so the interpolation
+    /// is checked in the context it appears in.
+    fn parse_interpolation(&mut self, source: &str, start: Position) -> Result<ast::Expression> {
+        let mut parser = Parser::new(Lexer::starting_at(source, start));
+        parser.scopes = std::mem::take(&mut self.scopes);
+        parser.loop_depth = self.loop_depth;
+        parser.function_depth = self.function_depth;
+        let expression = parser.parse_expression(Precedence::Lowest);
+        self.scopes = std::mem::take(&mut parser.scopes);
+        for (error, span) in parser.errors.iter() {
+            self.report(error.clone(), span);
+        }
+
+        let expression = expression?;
+        if !parser.peek_token_is(&Token::EOF) {
+            return Err(ParserError::InvalidInterpolation(source.to_string()));
+        }
+        Ok(expression)
     }
 
     fn parse_boolean_literal(&mut self) -> Result<ast::Expression> {
@@ -212,6 +706,7 @@ impl Parser {
 
     fn parse_prefix_expression(&mut self) -> Result<ast::Expression> {
         let token = self.current_token.clone();
+        let span = self.current_span;
         let operator = parse_to_operator(&self.current_token)?;
         self.next_token();
         let right = Box::new(self.parse_expression(Precedence::Prefix)?);
@@ -219,13 +714,15 @@ impl Parser {
             token,
             operator,
             right,
+            span,
         })
     }
 
     fn parse_infix_expression(&mut self, left: Box<ast::Expression>) -> Result<ast::Expression> {
         let token = self.current_token.clone();
+        let span = self.current_span;
         let operator = parse_to_operator(&self.current_token)?;
-        let precedence = Precedence::from(&self.current_token);
+        let precedence = Precedence::right_operand(&self.current_token);
         self.next_token();
         let right = Box::new(self.parse_expression(precedence)?);
         Ok(ast::Expression::Infix {
@@ -233,114 +730,1444 @@ impl Parser {
             operator,
             left,
             right,
+            span,
         })
     }
 
-    fn parse_group_expression(&mut self) -> Result<ast::Expression> {
-        self.next_token();
-        let expression = self.parse_expression(Precedence::Lowest)?;
-        self.expect_peek(Token::Rparen)?;
-        Ok(expression)
-    }
-}
+    fn parse_assign_expression(&mut self, target: Box<ast::Expression>) -> Result<ast::Expression> {
+        match *target {
+            ast::Expression::Ident(_)
+            | ast::Expression::Index {
+                optional: false, ..
+            }
+            | ast::Expression::Member {
+                optional: false, ..
+            } => {}
+            _ => return Err(ParserError::InvalidAssignmentTarget(format!("{}", target))),
+        }
 
-fn parse_to_operator(token: &Token) -> Result<ast::Operator> {
-    Ok(match token {
-        Token::Assign => ast::Operator::Assign,
-        Token::Plus => ast::Operator::Plus,
-        Token::Minus => ast::Operator::Minus,
-        Token::Bang => ast::Operator::Bang,
-        Token::Asterisk => ast::Operator::Asterisk,
-        Token::Slash => ast::Operator::Slash,
-        Token::Lt => ast::Operator::Lt,
-        Token::Gt => ast::Operator::Gt,
-        Token::Eq => ast::Operator::Eq,
-        Token::Neq => ast::Operator::Neq,
-        _ => return Err(ParserError::UnableToParseOperator(format!("{}", token))),
-    })
-}
+        if let ast::Expression::Ident(ast::Identifier(name, _)) = &*target {
+            if let Some(Declaration::Const(declared)) = self.lookup(name) {
+                return Err(ParserError::ConstAssignment {
+                    name: name.clone(),
+                    declared,
+                    assigned: self.current_span.start,
+                });
+            }
+        }
 
-#[cfg(test)]
-mod tests {
-    use core::panic;
+        let token = self.current_token.clone();
+        let operator = parse_to_operator(&self.current_token)?;
+        let precedence = Precedence::right_operand(&self.current_token);
+        self.next_token();
+        let value = Box::new(self.parse_expression(precedence)?);
+        Ok(ast::Expression::Assign {
+            token,
+            target,
+            operator,
+            value,
+        })
+    }
 
-    use super::*;
+    fn parse_pipe_expression(&mut self, value: Box<ast::Expression>) -> Result<ast::Expression> {
+        let precedence = Precedence::right_operand(&self.current_token);
+        self.next_token();
+        let function = Box::new(self.parse_expression(precedence)?);
+        Ok(ast::Expression::Pipe { value, function })
+    }
 
-    enum V<'a> {
-        Bool(bool),
-        Ident(&'a str),
-        Int(i32),
-        Infix(Box<V<'a>>, &'a str, Box<V<'a>>),
+    fn parse_range_expression(&mut self, start: Box<ast::Expression>) -> Result<ast::Expression> {
+        let inclusive = self.current_token == Token::DotDotEq;
+        let precedence = Precedence::right_operand(&self.current_token);
+        self.next_token();
+        let end = Box::new(self.parse_expression(precedence)?);
+        let range = ast::Expression::Range {
+            start,
+            end,
+            inclusive,
+        };
+        if matches!(self.peek_token, Token::DotDot | Token::DotDotEq) {
+            return Err(ParserError::ChainedRange(format!("{}", range)));
+        }
+        Ok(range)
     }
 
-    fn parse(input: &str) -> super::ParserResult<ast::Program> {
-        let lexer = Lexer::new(&input);
-        let mut parser = Parser::new(lexer);
-        parser.parse_program()
+    fn parse_function_literal(&mut self) -> Result<ast::Expression> {
+        self.expect_peek(Token::Lparen)?;
+        let parameters = self.parse_parameters(Token::Rparen, Precedence::Lowest)?;
+        let return_type = self.parse_return_type()?;
+        self.expect_peek(Token::Lbrace)?;
+        let body = self.in_function_scope(&parameters, |parser| parser.parse_block_statement())?;
+
+        Ok(ast::Expression::Function {
+            parameters,
+            return_type,
+            body,
+        })
     }
 
-    #[test]
-    fn test_let_statement() {
-        let inputs = vec![
-            ("let x = 5;", String::from("x"), V::Int(5)),
-            ("let y = 10;", String::from("y"), V::Int(10)),
-            (
-                "let foobar = 838383;",
-                String::from("foobar"),
-                V::Int(838383),
-            ),
-        ];
+    fn parse_function_declaration(&mut self) -> Result<ast::Statement> {
+        self.next_token();
+        let name = self.current_identifier();
+        self.declare(&name, Declaration::Function(self.current_span.start));
 
-        for (input, expect_ident_value, expect_literal_value) in inputs {
-            match parse(input) {
-                Err(errors) => test_print_errors(errors),
-                Ok(program) => {
-                    assert!(program.statements.len() > 0);
-                    let target = program.statements.get(0);
-                    if let Some(ast::Statement::Let(ident, exp)) = target {
-                        test_identifier(ident, expect_ident_value);
-                        test_integer_literal(exp, &expect_literal_value);
-                    }
-                }
-            };
+        self.expect_peek(Token::Lparen)?;
+        let parameters = self.parse_parameters(Token::Rparen, Precedence::Lowest)?;
+        let return_type = self.parse_return_type()?;
+        self.expect_peek(Token::Lbrace)?;
+        let body = self.in_function_scope(&parameters, |parser| parser.parse_block_statement())?;
+        if self.peek_token_is(&Token::Semicolon) {
+            self.next_token();
         }
+
+        Ok(ast::Statement::FnDecl {
+            name,
+            parameters,
+            return_type,
+            body,
+        })
     }
 
-    #[test]
-    fn test_return_statement() {
-        let inputs = vec![
-            ("return 5;", V::Int(5)),
-            ("return 10;", V::Int(10)),
-            ("return 993322;", V::Int(993322)),
-        ];
+    fn parse_lambda_literal(&mut self) -> Result<ast::Expression> {
+        let start = self.current_span.start;
+        // `||` is lexed as a single token, which here means no parameters.
+        let parameters = match self.current_token {
+            Token::Or => vec![],
+            // Defaults stop before `|` so it can close the parameter list.
+            _ => self.parse_parameters(Token::Pipe, Precedence::BitOr)?,
+        };
+        self.next_token();
+        let body = self.in_function_scope(&parameters, |parser| {
+            parser.parse_expression(Precedence::Lowest)
+        })?;
 
-        for (input, expect_literal_value) in inputs {
-            match parse(input) {
-                Err(errors) => test_print_errors(errors),
-                Ok(program) => {
-                    assert!(program.statements.len() > 0);
-                    let target = program.statements.get(0);
-                    if let Some(ast::Statement::Return(exp)) = target {
-                        test_expression(exp, &expect_literal_value);
-                    }
-                }
-            };
-        }
+        Ok(ast::Expression::Lambda {
+            parameters,
+            body: Box::new(body),
+            span: Span {
+                start,
+                end: self.current_span.end,
+            },
+        })
     }
 
-    #[test]
-    fn test_identifier_expression() {
-        let input = ("foobar;", "foobar".to_string());
-        match parse(input.0) {
-            Err(errors) => test_print_errors(errors),
-            Ok(program) => {
-                assert!(program.statements.len() > 0);
-                let target = program.statements.get(0);
-                if let Some(ast::Statement::Expr(ast::Expression::Ident(identifier))) = target {
-                    test_identifier(identifier, input.1);
-                }
+    /// Whether the `(` at the current token opens the parameters of a
+    /// `(x) => body` lambda, found by scanning ahead to the matching `)`.
+    fn arrow_follows(&self) -> bool {
+        let mut lexer = self.lexer.fork();
+        let mut token = self.peek_token.clone();
+        let mut depth = 1;
+        loop {
+            match token {
+                Token::Lparen => depth += 1,
+                Token::Rparen if depth == 1 => return lexer.next_token() == Token::FatArrow,
+                Token::Rparen => depth -= 1,
+                Token::EOF => return false,
+                _ => {}
             }
-        };
+            token = lexer.next_token();
+        }
+    }
+
+    /// Parses `(x, y) => x + y`, the same lambda as `|x, y| x + y`.
+    fn parse_arrow_lambda(&mut self) -> Result<ast::Expression> {
+        let start = self.current_span.start;
+        let parameters = self.parse_parameters(Token::Rparen, Precedence::Lowest)?;
+        self.expect_peek(Token::FatArrow)?;
+        self.next_token();
+        let body = self.in_function_scope(&parameters, |parser| {
+            parser.parse_expression(Precedence::Lowest)
+        })?;
+
+        Ok(ast::Expression::Lambda {
+            parameters,
+            body: Box::new(body),
+            span: Span {
+                start,
+                end: self.current_span.end,
+            },
+        })
+    }
+
+    fn parse_parameters(
+        &mut self,
+        end: Token,
+        default_precedence: Precedence,
+    ) -> Result<Vec<ast::Parameter>> {
+        let mut parameters: Vec<ast::Parameter> = vec![];
+
+        while !self.peek_token_is(&end) {
+            if let Some(rest) = parameters.iter().find(|p| p.rest) {
+                return Err(ParserError::RestParameterNotLast(rest.name.to_string()));
+            }
+
+            let rest = self.peek_token_is(&Token::Ellipsis);
+            if rest {
+                self.next_token();
+            }
+            self.expect_peek(Token::Ident("_".to_string()))?;
+            let name = self.current_identifier();
+            if parameters.iter().any(|p| p.name == name) {
+                return Err(ParserError::DuplicateParameter(name.to_string()));
+            }
+            let annotation = self.parse_optional_annotation()?;
+
+            let default = if !rest && self.peek_token_is(&Token::Assign) {
+                self.next_token();
+                self.next_token();
+                Some(self.parse_expression(default_precedence)?)
+            } else {
+                None
+            };
+            if !rest && default.is_none() && parameters.iter().any(|p| p.default.is_some()) {
+                return Err(ParserError::RequiredParameterAfterDefault(name.to_string()));
+            }
+            parameters.push(ast::Parameter {
+                name,
+                annotation,
+                default,
+                rest,
+            });
+
+            if !self.peek_token_is(&end) {
+                self.expect_peek(Token::Comma)?;
+            }
+        }
+        self.next_token();
+
+        Ok(parameters)
+    }
+
+    /// Parses `: type` if it follows the current token.
+    fn parse_optional_annotation(&mut self) -> Result<Option<ast::TypeAnnotation>> {
+        if !self.peek_token_is(&Token::Colon) {
+            return Ok(None);
+        }
+        self.next_token();
+        self.next_token();
+        self.parse_type_annotation().map(Some)
+    }
+
+    /// Parses `-> type` if it follows a parameter list.
+    fn parse_return_type(&mut self) -> Result<Option<ast::TypeAnnotation>> {
+        if !self.peek_token_is(&Token::Arrow) {
+            return Ok(None);
+        }
+        self.next_token();
+        self.next_token();
+        self.parse_type_annotation().map(Some)
+    }
+
+    fn parse_type_annotation(&mut self) -> Result<ast::TypeAnnotation> {
+        match &self.current_token {
+            Token::Ident(name) => Ok(ast::TypeAnnotation::Named(ast::Identifier(
+                name.clone(),
+                self.current_span,
+            ))),
+            Token::Null => Ok(ast::TypeAnnotation::Named(ast::Identifier(
+                "null".to_string(),
+                self.current_span,
+            ))),
+            Token::Lbracket => {
+                self.next_token();
+                let element = self.parse_type_annotation()?;
+                self.expect_peek(Token::Rbracket)?;
+                Ok(ast::TypeAnnotation::Array(Box::new(element)))
+            }
+            Token::Function => {
+                self.expect_peek(Token::Lparen)?;
+                let mut parameters = vec![];
+                while !self.peek_token_is(&Token::Rparen) {
+                    self.next_token();
+                    parameters.push(self.parse_type_annotation()?);
+                    if !self.peek_token_is(&Token::Rparen) {
+                        self.expect_peek(Token::Comma)?;
+                    }
+                }
+                self.next_token();
+                self.expect_peek(Token::Arrow)?;
+                self.next_token();
+                let return_type = Box::new(self.parse_type_annotation()?);
+                Ok(ast::TypeAnnotation::Function {
+                    parameters,
+                    return_type,
+                })
+            }
+            token => Err(ParserError::ExpectType(format!("{}", token))),
+        }
+    }
+
+    fn current_identifier(&self) -> ast::Identifier {
+        match &self.current_token {
+            Token::Ident(ident) => ast::Identifier(ident.clone(), self.current_span),
+            _ => unreachable!(),
+        }
+    }
+
+    fn in_scope<T>(
+        &mut self,
+        scope: HashMap<String, Declaration>,
+        parse: impl FnOnce(&mut Parser) -> Result<T>,
+    ) -> Result<T> {
+        self.scopes.push(scope);
+        let result = parse(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Runs `parse` on a function body, which gets its own scope for the
+    /// parameters and cannot `break` out of an enclosing loop.
+    fn in_function_scope<T>(
+        &mut self,
+        parameters: &[ast::Parameter],
+        parse: impl FnOnce(&mut Parser) -> Result<T>,
+    ) -> Result<T> {
+        let scope = parameters
+            .iter()
+            .map(|p| (p.name.0.clone(), Declaration::Parameter))
+            .collect();
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        let result = self.in_scope(scope, parse);
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        result
+    }
+
+    fn parse_propagate_expression(
+        &mut self,
+        value: Box<ast::Expression>,
+    ) -> Result<ast::Expression> {
+        if self.function_depth == 0 {
+            return Err(ParserError::OutsideFunction(format!(
+                "{}",
+                self.current_token
+            )));
+        }
+
+        Ok(ast::Expression::Propagate(value))
+    }
+
+    fn parse_call_expression(&mut self, function: Box<ast::Expression>) -> Result<ast::Expression> {
+        let arguments = self.in_brackets(|parser| {
+            let mut arguments = vec![];
+            while !parser.peek_token_is(&Token::Rparen) {
+                parser.next_token();
+                arguments.push(if parser.current_token == Token::Ellipsis {
+                    parser.next_token();
+                    ast::Expression::Spread(Box::new(parser.parse_expression(Precedence::Lowest)?))
+                } else {
+                    parser.parse_expression(Precedence::Lowest)?
+                });
+                if !parser.peek_token_is(&Token::Rparen) {
+                    parser.expect_peek(Token::Comma)?;
+                }
+            }
+            Ok(arguments)
+        })?;
+        self.next_token();
+
+        // A function literal called on the spot has a known number of
+        // parameters. Calls through a name are left to the type checker.
+        if let ast::Expression::Function { parameters, .. }
+        | ast::Expression::Lambda { parameters, .. } = function.as_ref()
+        {
+            let variadic = parameters.iter().any(|p| p.rest)
+                || arguments
+                    .iter()
+                    .any(|a| matches!(a, ast::Expression::Spread(_)));
+            if !variadic && arguments.len() > parameters.len() {
+                return Err(ParserError::TooManyArguments {
+                    call: function.to_string(),
+                    expected: parameters.len(),
+                    found: arguments.len(),
+                });
+            }
+        }
+
+        Ok(ast::Expression::Call {
+            function,
+            arguments,
+        })
+    }
+
+    fn parse_index_expression(
+        &mut self,
+        left: Box<ast::Expression>,
+        optional: bool,
+    ) -> Result<ast::Expression> {
+        self.next_token();
+        let index = Box::new(self.parse_expression(Precedence::Lowest)?);
+        self.expect_peek(Token::Rbracket)?;
+        Ok(ast::Expression::Index {
+            left,
+            index,
+            optional,
+        })
+    }
+
+    fn parse_member_expression(
+        &mut self,
+        object: Box<ast::Expression>,
+        optional: bool,
+    ) -> Result<ast::Expression> {
+        self.expect_peek(Token::Ident("_".to_string()))?;
+        let property = self.current_identifier();
+        Ok(ast::Expression::Member {
+            object,
+            property,
+            optional,
+        })
+    }
+
+    fn parse_optional_chain_expression(
+        &mut self,
+        left: Box<ast::Expression>,
+    ) -> Result<ast::Expression> {
+        if self.peek_token_is(&Token::Lbracket) {
+            self.next_token();
+            self.parse_index_expression(left, true)
+        } else {
+            self.parse_member_expression(left, true)
+        }
+    }
+
+    fn parse_if_expression(&mut self) -> Result<ast::Expression> {
+        let span = self.current_span;
+        self.expect_peek(Token::Lparen)?;
+        self.next_token();
+        let condition = Box::new(self.parse_expression(Precedence::Lowest)?);
+        self.expect_peek(Token::Rparen)?;
+        self.expect_peek(Token::Lbrace)?;
+        let consequence = self.parse_block_statement()?;
+
+        let alternative = if self.peek_token_is(&Token::Else) {
+            self.next_token();
+            self.expect_peek(Token::Lbrace)?;
+            Some(self.parse_block_statement()?)
+        } else {
+            None
+        };
+
+        Ok(ast::Expression::If {
+            condition,
+            consequence,
+            alternative,
+            span,
+        })
+    }
+
+    fn parse_match_expression(&mut self) -> Result<ast::Expression> {
+        self.expect_peek(Token::Lparen)?;
+        self.next_token();
+        let value = Box::new(self.parse_expression(Precedence::Lowest)?);
+        self.expect_peek(Token::Rparen)?;
+        self.expect_peek(Token::Lbrace)?;
+
+        let mut arms = vec![];
+        while !self.peek_token_is(&Token::Rbrace) {
+            self.next_token();
+            arms.push(self.parse_match_arm()?);
+            if !self.peek_token_is(&Token::Rbrace) {
+                self.expect_peek(Token::Comma)?;
+            }
+        }
+        self.next_token();
+
+        Ok(ast::Expression::Match { value, arms })
+    }
+
+    fn parse_match_arm(&mut self) -> Result<ast::MatchArm> {
+        let start = self.current_span.start;
+        let pattern = self.parse_pattern()?;
+
+        let guard = if self.peek_token_is(&Token::If) {
+            self.next_token();
+            self.next_token();
+            let in_guard = std::mem::replace(&mut self.in_guard, true);
+            let guard = self.parse_expression(Precedence::Lowest);
+            self.in_guard = in_guard;
+            Some(guard?)
+        } else {
+            None
+        };
+
+        self.expect_peek(Token::FatArrow)?;
+        self.next_token();
+        let body = self.parse_expression(Precedence::Lowest)?;
+
+        Ok(ast::MatchArm {
+            pattern,
+            guard,
+            body,
+            span: Span {
+                start,
+                end: self.current_span.end,
+            },
+        })
+    }
+
+    fn parse_pattern(&mut self) -> Result<ast::Pattern> {
+        match &self.current_token {
+            Token::Ident(ident) if ident == "_" => Ok(ast::Pattern::Wildcard),
+            Token::Ident(_) => Ok(ast::Pattern::Binding(self.current_identifier())),
+            Token::Int(_)
+            | Token::Minus
+            | Token::Char(_)
+            | Token::True
+            | Token::False
+            | Token::Null => self.parse_literal_pattern(),
+            Token::Lbracket => self.parse_array_pattern(PatternKind::Match),
+            Token::Lbrace => self.parse_hash_pattern(PatternKind::Match),
+            t => Err(ParserError::ExpectPattern(format!("{}", t))),
+        }
+    }
+
+    /// Parses the irrefutable patterns accepted by `let`. Literals and ranges
+    /// are rejected, while elements and fields may carry `= default` values.
+    fn parse_binding_pattern(&mut self) -> Result<ast::Pattern> {
+        match &self.current_token {
+            Token::Ident(ident) if ident == "_" => Ok(ast::Pattern::Wildcard),
+            Token::Ident(_) => Ok(ast::Pattern::Binding(self.current_identifier())),
+            Token::Lbracket => self.parse_array_pattern(PatternKind::Binding),
+            Token::Lbrace => self.parse_hash_pattern(PatternKind::Binding),
+            t => Err(ParserError::InvalidBindingPattern(format!("{}", t))),
+        }
+    }
+
+    fn parse_nested_pattern(&mut self, kind: PatternKind) -> Result<ast::Pattern> {
+        match kind {
+            PatternKind::Match => self.parse_pattern(),
+            PatternKind::Binding => {
+                let pattern = self.parse_binding_pattern()?;
+                self.parse_pattern_default(pattern)
+            }
+        }
+    }
+
+    fn parse_pattern_default(&mut self, pattern: ast::Pattern) -> Result<ast::Pattern> {
+        if !self.peek_token_is(&Token::Assign) {
+            return Ok(pattern);
+        }
+
+        self.next_token();
+        self.next_token();
+        let default = self.parse_expression(Precedence::Assign)?;
+        Ok(ast::Pattern::Default {
+            pattern: Box::new(pattern),
+            default,
+        })
+    }
+
+    fn parse_literal_pattern(&mut self) -> Result<ast::Pattern> {
+        let start = self.parse_pattern_literal()?;
+        if !self.peek_token_is(&Token::DotDot) && !self.peek_token_is(&Token::DotDotEq) {
+            return Ok(ast::Pattern::Literal(start));
+        }
+
+        self.next_token();
+        let inclusive = self.current_token == Token::DotDotEq;
+        self.next_token();
+        let end = self.parse_pattern_literal()?;
+        Ok(ast::Pattern::Range {
+            start,
+            end,
+            inclusive,
+        })
+    }
+
+    fn parse_pattern_literal(&mut self) -> Result<ast::Expression> {
+        match &self.current_token {
+            Token::Int(_) => self.parse_integer_literal(),
+            Token::Char(val) => Ok(ast::Expression::CharLiteral(*val)),
+            Token::True | Token::False => self.parse_boolean_literal(),
+            Token::Null => Ok(ast::Expression::NullLiteral),
+            Token::Minus => {
+                self.expect_peek(Token::Int("_".to_string()))?;
+                match self.parse_integer_literal()? {
+                    ast::Expression::IntegerLiteral { token, value } => {
+                        Ok(ast::Expression::IntegerLiteral {
+                            token: Token::Int(format!("-{}", token)),
+                            value: -value,
+                        })
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            t => Err(ParserError::ExpectPattern(format!("{}", t))),
+        }
+    }
+
+    fn parse_array_pattern(&mut self, kind: PatternKind) -> Result<ast::Pattern> {
+        let mut elements = vec![];
+        let mut has_rest = false;
+
+        while !self.peek_token_is(&Token::Rbracket) {
+            self.next_token();
+            let element = if self.current_token == Token::DotDot {
+                if has_rest {
+                    return Err(ParserError::MultipleRestPatterns(format!(
+                        "{}",
+                        ast::Pattern::Array(elements)
+                    )));
+                }
+                has_rest = true;
+                if self.peek_token_is(&Token::Ident("_".to_string())) {
+                    self.next_token();
+                    match self.parse_pattern()? {
+                        ast::Pattern::Binding(identifier) => ast::Pattern::Rest(Some(identifier)),
+                        _ => ast::Pattern::Rest(None),
+                    }
+                } else {
+                    ast::Pattern::Rest(None)
+                }
+            } else {
+                self.parse_nested_pattern(kind)?
+            };
+            elements.push(element);
+
+            if !self.peek_token_is(&Token::Rbracket) {
+                self.expect_peek(Token::Comma)?;
+            }
+        }
+        self.next_token();
+
+        Ok(ast::Pattern::Array(elements))
+    }
+
+    fn parse_hash_pattern(&mut self, kind: PatternKind) -> Result<ast::Pattern> {
+        let mut fields = vec![];
+
+        while !self.peek_token_is(&Token::Rbrace) {
+            self.expect_peek(Token::Ident("_".to_string()))?;
+            let key = self.current_identifier();
+
+            let pattern = if self.peek_token_is(&Token::Colon) {
+                self.next_token();
+                self.next_token();
+                self.parse_nested_pattern(kind)?
+            } else if kind == PatternKind::Binding {
+                self.parse_pattern_default(ast::Pattern::Binding(key.clone()))?
+            } else {
+                ast::Pattern::Binding(key.clone())
+            };
+            fields.push((key, pattern));
+
+            if !self.peek_token_is(&Token::Rbrace) {
+                self.expect_peek(Token::Comma)?;
+            }
+        }
+        self.next_token();
+
+        Ok(ast::Pattern::Hash(fields))
+    }
+
+    fn parse_group_expression(&mut self) -> Result<ast::Expression> {
+        self.next_token();
+        let expression = self.in_brackets(|parser| parser.parse_expression(Precedence::Lowest))?;
+        self.expect_peek(Token::Rparen)?;
+        Ok(expression)
+    }
+
+    /// Runs `parse` inside brackets, where `=>` can no longer end a match
+    /// guard.
+    fn in_brackets<T>(&mut self, parse: impl FnOnce(&mut Parser) -> Result<T>) -> Result<T> {
+        let in_guard = std::mem::replace(&mut self.in_guard, false);
+        let result = parse(self);
+        self.in_guard = in_guard;
+        result
+    }
+}
+
+fn parse_to_operator(token: &Token) -> Result<ast::Operator> {
+    Ok(match token {
+        Token::Assign => ast::Operator::Assign,
+        Token::PlusAssign => ast::Operator::PlusAssign,
+        Token::MinusAssign => ast::Operator::MinusAssign,
+        Token::AsteriskAssign => ast::Operator::AsteriskAssign,
+        Token::SlashAssign => ast::Operator::SlashAssign,
+        Token::Plus => ast::Operator::Plus,
+        Token::Minus => ast::Operator::Minus,
+        Token::Bang => ast::Operator::Bang,
+        Token::Asterisk => ast::Operator::Asterisk,
+        Token::Power => ast::Operator::Power,
+        Token::Slash => ast::Operator::Slash,
+        Token::Percent => ast::Operator::Percent,
+        Token::Lt => ast::Operator::Lt,
+        Token::Gt => ast::Operator::Gt,
+        Token::LtEq => ast::Operator::LtEq,
+        Token::GtEq => ast::Operator::GtEq,
+        Token::Eq => ast::Operator::Eq,
+        Token::Neq => ast::Operator::Neq,
+        Token::And => ast::Operator::And,
+        Token::Or => ast::Operator::Or,
+        Token::Ampersand => ast::Operator::Ampersand,
+        Token::Pipe => ast::Operator::Pipe,
+        Token::Caret => ast::Operator::Caret,
+        Token::Tilde => ast::Operator::Tilde,
+        Token::ShiftLeft => ast::Operator::ShiftLeft,
+        Token::ShiftRight => ast::Operator::ShiftRight,
+        Token::In => ast::Operator::In,
+        Token::Coalesce => ast::Operator::Coalesce,
+        _ => return Err(ParserError::UnableToParseOperator(format!("{}", token))),
+    })
+}
+
+fn parse_to_integer(literal: &str) -> Result<i64> {
+    let (radix, body) = match literal.get(..2) {
+        Some("0x") | Some("0X") => (16, &literal[2..]),
+        Some("0o") | Some("0O") => (8, &literal[2..]),
+        Some("0b") | Some("0B") => (2, &literal[2..]),
+        _ => (10, literal),
+    };
+
+    let digits = body.chars().filter(|&c| c != '_').collect::<String>();
+    if digits.is_empty() {
+        return Err(ParserError::MissingDigits(literal.to_string()));
+    }
+    if body.starts_with('_') || body.ends_with('_') || body.contains("__") {
+        return Err(ParserError::MisplacedSeparator(literal.to_string()));
+    }
+    if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+        return Err(ParserError::InvalidDigit {
+            literal: literal.to_string(),
+            digit,
+            radix,
+        });
+    }
+
+    // Radix literals spell out bits, so all 64 may be set; the value is their
+    // two's complement reading, making 0xFFFF_FFFF_FFFF_FFFF equal to -1.
+    let value = match radix {
+        10 => digits.parse::<i64>().map_err(|_| "i64"),
+        _ => u64::from_str_radix(&digits, radix)
+            .map(|value| value as i64)
+            .map_err(|_| "u64"),
+    };
+    value.map_err(|range| ParserError::IntegerOutOfRange {
+        literal: literal.to_string(),
+        range,
+    })
+}
+
+/// Parses a complete program, collecting every statement-level error.
+pub fn parse(input: &str) -> ParserResult<ast::Program> {
+    Parser::new(Lexer::new(input)).parse_program()
+}
+
+#[cfg(test)]
+mod tests {
+    use core::panic;
+
+    use super::*;
+
+    enum V<'a> {
+        Bool(bool),
+        Ident(&'a str),
+        Int(i64),
+        Infix(Box<V<'a>>, &'a str, Box<V<'a>>),
+    }
+
+    #[test]
+    fn test_let_statement() {
+        let inputs = vec![
+            ("let x = 5;", String::from("x"), V::Int(5)),
+            ("let y = 10;", String::from("y"), V::Int(10)),
+            (
+                "let foobar = 838383;",
+                String::from("foobar"),
+                V::Int(838383),
+            ),
+        ];
+
+        for (input, expect_ident_value, expect_literal_value) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => {
+                    assert!(!program.statements.is_empty());
+                    let target = program.statements.first();
+                    if let Some(ast::Statement::Let(ast::Pattern::Binding(ident), None, exp)) =
+                        target
+                    {
+                        test_identifier(ident, expect_ident_value);
+                        test_integer_literal(exp, &expect_literal_value);
+                    }
+                }
+            };
+        }
+    }
+
+    #[test]
+    fn test_let_destructuring() {
+        let inputs = vec![
+            ("let [a, b] = pair;", "let [a, b] = pair;"),
+            ("let {name, age} = person;", "let {name, age} = person;"),
+            ("let {port = 8080} = cfg;", "let {port = 8080} = cfg;"),
+            (
+                "let {server: {host, port = 80}, tags: [first, ..rest]} = cfg;",
+                "let {server: {host, port = 80}, tags: [first, ..rest]} = cfg;",
+            ),
+            (
+                "let [x = 1, _, y = x + 1] = xs;",
+                "let [x = 1, _, y = (x + 1)] = xs;",
+            ),
+            ("let {id: key = 0} = row;", "let {id: key = 0} = row;"),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_let_patterns() {
+        let inputs = vec![
+            ("let 5 = x;", "invalid binding pattern. 5"),
+            ("let [a, 1] = x;", "invalid binding pattern. 1"),
+            ("let {kind: true} = x;", "invalid binding pattern. true"),
+            ("let {a: 1..2} = x;", "invalid binding pattern. 1"),
+            (
+                "let [..a, ..b] = x;",
+                "more than one rest pattern in array pattern. [..a]",
+            ),
+            (
+                "let x + 1 = x;",
+                "expect token (expected \"=\", found \"+\")",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_const_statement() {
+        let inputs = vec![
+            ("const x = 5;", "const x = 5;"),
+            ("const {port = 80} = cfg;", "const {port = 80} = cfg;"),
+            ("const x = 1; cfg.x = 2;", "const x = 1;((cfg.x) = 2)"),
+            ("let x = 1; const x = x;", "let x = 1;const x = x;"),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_const_reassignment() {
+        let inputs = vec![
+            (
+                "const x = 5;\nx = 6;",
+                "cannot assign to const x at 2:3 (declared at 1:1)",
+            ),
+            (
+                "const [a, b] = pair;\n  b += 1;",
+                "cannot assign to const b at 2:5 (declared at 1:1)",
+            ),
+            (
+                "const x = 5;\nwhile (true) { x *= 2; }",
+                "cannot assign to const x at 2:18 (declared at 1:1)",
+            ),
+            (
+                "const x = 5;\nlet x = 6;",
+                "cannot redeclare const x at 2:1 (declared at 1:1)",
+            ),
+            (
+                "const x = 5;\nconst x = 6;",
+                "cannot redeclare const x at 2:1 (declared at 1:1)",
+            ),
+            (
+                "const x = 0;\nfor (x in xs) {}",
+                "cannot redeclare const x at 2:6 (declared at 1:1)",
+            ),
+            (
+                "const x = 0;\nfor (i in xs) { x += i; }",
+                "cannot assign to const x at 2:19 (declared at 1:1)",
+            ),
+            (
+                "const x = 0;\nif (c) { x = 1; }",
+                "cannot assign to const x at 2:12 (declared at 1:1)",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_string_literals() {
+        let inputs = vec![
+            (r#""hello world""#, r#""hello world""#),
+            (r#""tab\there \"q\" \${x}""#, r#""tab\there \"q\" \${x}""#),
+            (r#"let s = "a" + "b";"#, r#"let s = ("a" + "b");"#),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_raw_and_multi_line_strings() {
+        let inputs = vec![
+            r#"r"C:\path""#,
+            r###"r#"contains "quotes""#"###,
+            "\"\"\"\nSELECT *\n  FROM t\n\"\"\"",
+        ];
+
+        for input in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), input),
+            }
+        }
+
+        match parse("let q = \"\"\"\n    a\n      b\n    \"\"\";") {
+            Err(errors) => test_print_errors(errors),
+            Ok(program) => match program.statements.first() {
+                Some(ast::Statement::Let(
+                    _,
+                    _,
+                    ast::Expression::StringLiteral { value, style },
+                )) => {
+                    assert_eq!(value, "a\n  b");
+                    assert_eq!(*style, ast::StringStyle::MultiLine);
+                }
+                statement => panic!("expected string literal, got {:?}", statement),
+            },
+        }
+    }
+
+    #[test]
+    fn test_char_and_byte_string_literals() {
+        let inputs = vec![
+            ("'a'", "'a'"),
+            (r"'\n'", r"'\n'"),
+            (r"'\x41'", "'A'"),
+            (r"'\''", r"'\''"),
+            (r#"b"GET /\r\n""#, r#"b"GET /\r\n""#),
+            (r#"b"\x00\xff""#, r#"b"\x00\xff""#),
+            (
+                r#"let buf = b"\x01" + b"ok";"#,
+                r#"let buf = (b"\x01" + b"ok");"#,
+            ),
+            (
+                "match (c) { 'a'..='z' => lower, '_' => under }",
+                "match (c) {'a'..='z' => lower, '_' => under}",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_char_and_byte_string_literals() {
+        let inputs = vec![
+            (r#""a\qb""#, r"invalid literal. unknown escape \q"),
+            (r"'\q'", r"invalid literal. unknown escape \q"),
+            (
+                r#"b"é""#,
+                "invalid literal. non-ASCII character 'é' in byte string",
+            ),
+            (
+                "'ab'",
+                "invalid literal. more than one character in char literal",
+            ),
+            ("''", "invalid literal. empty char literal"),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error, "{}", input),
+            }
+        }
+    }
+
+    #[test]
+    fn test_template_literals() {
+        let inputs = vec![
+            r#""Hello, ${user.name}! You have ${count + 1} messages""#,
+            r#""${ match (x) { {kind} => kind } }""#,
+            r#""${row["}"]}""#,
+            r#""outer ${"inner ${x ?? "none"}"} done""#,
+        ];
+
+        for input in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), input),
+            }
+        }
+
+        match parse(r#""You have ${count + 1} messages""#) {
+            Err(errors) => test_print_errors(errors),
+            Ok(program) => match program.statements.first() {
+                Some(ast::Statement::Expr(ast::Expression::Template(segments))) => {
+                    assert_eq!(segments.len(), 3);
+                    match &segments[1] {
+                        ast::TemplateSegment::Interpolation { expression, .. } => {
+                            assert_eq!(expression.to_string(), "(count + 1)")
+                        }
+                        segment => panic!("expected interpolation, got {:?}", segment),
+                    }
+                }
+                statement => panic!("expected template, got {:?}", statement),
+            },
+        }
+    }
+
+    #[test]
+    fn test_invalid_template_literals() {
+        let inputs = vec![
+            (r#""${}""#, "expect expression token. EOF"),
+            (r#""${a b}""#, "invalid string interpolation. ${a b}"),
+            (r#""${1 +}""#, "expect expression token. EOF"),
+            (
+                "const x = 1;\nlet s = \"${x = 2}\";",
+                "cannot assign to const x at 2:14 (declared at 1:1)",
+            ),
+            (
+                "const x = 1;\nlet f = fn() { \"${|y| x += y}\" };",
+                "cannot assign to const x at 2:25 (declared at 1:1)",
+            ),
+            (r#""${ok(1)?}""#, "? outside of a function"),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_function_literals() {
+        let inputs = vec![
+            ("fn(x, y) { x + y; }", "fn(x, y) {(x + y)}"),
+            ("fn() {}", "fn() {}"),
+            ("fn(a, b = 10) { a * b }", "fn(a, b = 10) {(a * b)}"),
+            ("fn(head, ...tail) { tail }", "fn(head, ...tail) {tail}"),
+            ("fn(a = 1, ...rest) { rest }", "fn(a = 1, ...rest) {rest}"),
+            ("|x| x * 2", "|x| (x * 2)"),
+            ("|| 42", "|| 42"),
+            ("|a, b = a | 1| a + b", "|a, b = a| (1 | (a + b))"),
+            ("|a, b = (a | 1)| a + b", "|a, b = (a | 1)| (a + b)"),
+            ("|...xs| xs.len()", "|...xs| (xs.len)()"),
+            ("xs.map(|x| x + 1, 0)", "(xs.map)(|x| (x + 1), 0)"),
+            ("f(...args)", "f(...args)"),
+            ("f(a, ...rest, b)", "f(a, ...rest, b)"),
+            ("f(...xs.slice(1))", "f(...(xs.slice)(1))"),
+            ("(x) => x * 2", "|x| (x * 2)"),
+            ("() => 42", "|| 42"),
+            ("(a, b = (1)) => a + b", "|a, b = 1| (a + b)"),
+            ("xs.map((x) => (x + 1))", "(xs.map)(|x| (x + 1))"),
+            ("((x) => x)(1)", "|x| x(1)"),
+            ("(fn(a, b = 1) { a })(1, 2)", "fn(a, b = 1) {a}(1, 2)"),
+            ("(|...xs| xs)(1, 2, 3)", "|...xs| xs(1, 2, 3)"),
+            (
+                "match (n) { x if (x > 0) => (y) => x + y, _ => 0 }",
+                "match (n) {x if (x > 0) => |y| (x + y), _ => 0}",
+            ),
+            (
+                "match (xs) { x if any(x, (y) => y > 0) => x, _ => null }",
+                "match (xs) {x if any(x, |y| (y > 0)) => x, _ => null}",
+            ),
+            (
+                "let add = fn(x, y) { return x + y; }; add(1, 2)",
+                "let add = fn(x, y) {return (x + y);};add(1, 2)",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        let inputs = vec![
+            ("fn(x, x) {}", "duplicate parameter x"),
+            ("|a, ...a| a", "duplicate parameter a"),
+            (
+                "fn(a = 1, b) {}",
+                "parameter b without a default follows a parameter with a default",
+            ),
+            (
+                "fn(...a, b) {}",
+                "rest parameter ...a must be the last parameter",
+            ),
+            (
+                "fn(...a = 1) {}",
+                "expect token (expected \",\", found \"=\")",
+            ),
+            ("fn(1) {}", "expect token (expected \"_\", found \"1\")"),
+            ("...xs", "expect expression token. ..."),
+            ("(x, 1) => x", "expect token (expected \"_\", found \"1\")"),
+            (
+                "(|x| x)(1, 2)",
+                "too many arguments in |x| x: expected at most 1, found 2",
+            ),
+            (
+                "fn(a, b = 1) { a }(1, 2, 3)",
+                "too many arguments in fn(a, b = 1) {a}: expected at most 2, found 3",
+            ),
+            ("while (x) { fn() { break; } }", "break outside of a loop"),
+            (
+                "const x = 1; fn() { x = 2; }",
+                "cannot assign to const x at 1:23 (declared at 1:1)",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+
+        match parse("const x = 1; fn(x) { x = 2; }") {
+            Err(errors) => test_print_errors(errors),
+            Ok(program) => assert_eq!(program.to_string(), "const x = 1;fn(x) {(x = 2)}"),
+        }
+    }
+
+    #[test]
+    fn test_function_declarations() {
+        let inputs = vec![
+            (
+                "fn is_even(n) { if (n == 0) { true } else { is_odd(n - 1) } }\nfn is_odd(n) { if (n == 0) { false } else { is_even(n - 1) } }",
+                "fn is_even(n) {if ((n == 0)) {true} else {is_odd((n - 1))}}fn is_odd(n) {if ((n == 0)) {false} else {is_even((n - 1))}}",
+            ),
+            ("fn f(a, b = 1) { a + b };", "fn f(a, b = 1) {(a + b)}"),
+            ("fn() { 1 }()", "fn() {1}()"),
+            ("fn outer() { fn inner() {} inner() }", "fn outer() {fn inner() {}inner()}"),
+            ("fn f() {} fn g() { fn f() {} }", "fn f() {}fn g() {fn f() {}}"),
+            ("fn f(f) { f }", "fn f(f) {f}"),
+            (
+                "if (a) { fn f() { 1 } } else { fn f() { 2 } }",
+                "if (a) {fn f() {1}} else {fn f() {2}}",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_duplicate_declarations() {
+        let inputs = vec![
+            (
+                "fn f() {}\nfn f() {}",
+                "f is declared twice in the same scope at 2:4 (first declared at 1:4)",
+            ),
+            (
+                "let f = 1;\nfn f() {}",
+                "f is declared twice in the same scope at 2:4 (first declared at 1:1)",
+            ),
+            (
+                "fn f() {}\nlet f = 1;",
+                "f is declared twice in the same scope at 2:1 (first declared at 1:4)",
+            ),
+            (
+                "fn g() { fn h() {} let [h] = xs; }",
+                "h is declared twice in the same scope at 1:20 (first declared at 1:13)",
+            ),
+            (
+                "fn g() {} fn g() {}",
+                "g is declared twice in the same scope at 1:14 (first declared at 1:4)",
+            ),
+            (
+                "let g = 1; fn g(a) { a }",
+                "g is declared twice in the same scope at 1:15 (first declared at 1:1)",
+            ),
+            (
+                "const x = 0;\nfor (x in xs) { puts(x) }",
+                "cannot redeclare const x at 2:6 (declared at 1:1)",
+            ),
+            (
+                "const f = 1;\nfn f() {}",
+                "cannot redeclare const f at 2:4 (declared at 1:1)",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => {
+                    // The rest of the declaration is still parsed, so nothing
+                    // else is reported.
+                    assert_eq!(errors.0.len(), 1, "{}", errors);
+                    assert_eq!(errors.0[0].to_string(), expect_error);
+                }
+            }
+        }
+
+        // Re-declaring with let is how a binding is replaced.
+        match parse("let x = 1;\nlet x = x + 1;") {
+            Err(errors) => test_print_errors(errors),
+            Ok(program) => assert_eq!(program.to_string(), "let x = 1;let x = (x + 1);"),
+        }
+    }
+
+    #[test]
+    fn test_import_export_statements() {
+        let inputs = vec![
+            (
+                "import \"lib/strings\" as s;\ns.trim(x)",
+                "import \"lib/strings\" as s;(s.trim)(x)",
+            ),
+            ("export let helper = 1;", "export let helper = 1;"),
+            ("export const {a, b} = pair;", "export const {a, b} = pair;"),
+            ("export fn f(x) { x }", "export fn f(x) {x}"),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+
+        let inputs = vec![
+            (
+                "import lib as s;",
+                "expect token (expected \"module path string\", found \"lib\")",
+            ),
+            (
+                "import \"lib\";",
+                "expect token (expected \"as\", found \";\")",
+            ),
+            (
+                "fn f() { import \"lib\" as s; }",
+                "import is only allowed at the top level of a module",
+            ),
+            (
+                "if (a) { export let x = 1; }",
+                "export is only allowed at the top level of a module",
+            ),
+            (
+                "export x + 1;",
+                "expect let, const or fn declaration after export. x",
+            ),
+            (
+                "import \"a\" as m;\nm = 1;",
+                "cannot assign to const m at 2:3 (declared at 1:1)",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_statements() {
+        let inputs = vec![
+            ("throw \"bad row\";", "throw \"bad row\";"),
+            (
+                "try { parse(row) } catch (e) { log(e) }",
+                "try {parse(row)} catch (e) {log(e)}",
+            ),
+            (
+                "try { f() } finally { close() }",
+                "try {f()} finally {close()}",
+            ),
+            (
+                "try { f() } catch (e) { throw e; } finally { close() }",
+                "try {f()} catch (e) {throw e;} finally {close()}",
+            ),
+            (
+                "let e = 1; try {} catch (e) { let e = 2; }",
+                "let e = 1;try {} catch (e) {let e = 2;}",
+            ),
+            (
+                "for (row in rows) { try { f(row) } catch (e) { continue; } }",
+                "for (row in rows) {try {f(row)} catch (e) {continue;}}",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+
+        let inputs = vec![
+            ("try { f() } g()", "try block without catch or finally. g"),
+            (
+                "try { f() } catch { g() }",
+                "expect token (expected \"(\", found \"{\")",
+            ),
+            ("throw;", "expect expression token. ;"),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_propagate_expressions() {
+        let inputs = vec![
+            ("fn f() { g()? }", "fn f() {(g()?)}"),
+            ("fn f() { -x? + 1 }", "fn f() {((-(x?)) + 1)}"),
+            ("fn f() { a.b?.c? }", "fn f() {(((a.b)?.c)?)}"),
+            ("fn f() { (a.b)? .c }", "fn f() {(((a.b)?).c)}"),
+            ("fn f() { (xs[0]?)? }", "fn f() {(((xs[0])?)?)}"),
+            ("|x| parse(x)?", "|x| (parse(x)?)"),
+            ("fn f() { a ?? b }", "fn f() {(a ?? b)}"),
+            (
+                r#"fn f(g) { return "${g()?}"; }"#,
+                r#"fn f(g) {return "${g()?}";}"#,
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+
+        let inputs = vec![
+            ("g()?", "? outside of a function"),
+            ("fn f() {} x?", "? outside of a function"),
+            ("while (c) { x? }", "? outside of a function"),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_type_annotations() {
+        let inputs = vec![
+            ("let x: int = 5;", "let x: int = 5;"),
+            (
+                "const [a, b]: [string] = pair;",
+                "const [a, b]: [string] = pair;",
+            ),
+            (
+                "let f = fn(a: string, b: int = 1) -> bool { true };",
+                "let f = fn(a: string, b: int = 1) -> bool {true};",
+            ),
+            (
+                "fn apply(f: fn(int, int) -> int, ...xs: [int]) -> [[int]] { f }",
+                "fn apply(f: fn(int, int) -> int, ...xs: [int]) -> [[int]] {f}",
+            ),
+            ("|x: int| x * 2", "|x: int| (x * 2)"),
+            (
+                "let g: fn() -> null = fn() {};",
+                "let g: fn() -> null = fn() {};",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+
+        let inputs = vec![
+            ("let x: = 5;", "expect type. ="),
+            (
+                "let x: [int = 5;",
+                "expect token (expected \"]\", found \"=\")",
+            ),
+            ("fn f() -> { 1 }", "expect type. {"),
+            (
+                "let f: fn(int) = g;",
+                "expect token (expected \"->\", found \"=\")",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_return_statement() {
+        let inputs = vec![
+            ("return 5;", V::Int(5)),
+            ("return 10;", V::Int(10)),
+            ("return 993322;", V::Int(993322)),
+        ];
+
+        for (input, expect_literal_value) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => {
+                    assert!(!program.statements.is_empty());
+                    let target = program.statements.first();
+                    if let Some(ast::Statement::Return(exp, _)) = target {
+                        test_expression(exp, &expect_literal_value);
+                    }
+                }
+            };
+        }
+    }
+
+    #[test]
+    fn test_identifier_expression() {
+        let input = ("foobar;", "foobar".to_string());
+        match parse(input.0) {
+            Err(errors) => test_print_errors(errors),
+            Ok(program) => {
+                assert!(!program.statements.is_empty());
+                let target = program.statements.first();
+                if let Some(ast::Statement::Expr(ast::Expression::Ident(identifier))) = target {
+                    test_identifier(identifier, input.1);
+                }
+            }
+        };
     }
 
     #[test]
@@ -349,10 +2176,10 @@ mod tests {
         match parse(input.0) {
             Err(errors) => test_print_errors(errors),
             Ok(program) => {
-                assert!(program.statements.len() > 0);
-                let target = program.statements.get(0);
+                assert!(!program.statements.is_empty());
+                let target = program.statements.first();
                 if let Some(ast::Statement::Expr(expression)) = target {
-                    test_integer_literal(&expression, &input.1);
+                    test_integer_literal(expression, &input.1);
                 }
             }
         };
@@ -360,14 +2187,18 @@ mod tests {
 
     #[test]
     fn test_parsing_prefix_expressions() {
-        let inputs = vec![("!5;", "!", V::Int(5)), ("-15;", "-", V::Int(15))];
+        let inputs = vec![
+            ("!5;", "!", V::Int(5)),
+            ("-15;", "-", V::Int(15)),
+            ("~15;", "~", V::Int(15)),
+        ];
 
         for (input, prefix, expect_right_value) in inputs {
             match parse(input) {
                 Err(errors) => test_print_errors(errors),
                 Ok(program) => {
-                    assert!(program.statements.len() > 0);
-                    let target = program.statements.get(0);
+                    assert!(!program.statements.is_empty());
+                    let target = program.statements.first();
                     if let Some(ast::Statement::Expr(ast::Expression::Prefix {
                         operator,
                         right,
@@ -375,7 +2206,7 @@ mod tests {
                     })) = target
                     {
                         test_operator(operator, prefix);
-                        test_integer_literal(&*right, &expect_right_value);
+                        test_integer_literal(right, &expect_right_value);
                     }
                 }
             };
@@ -393,6 +2224,17 @@ mod tests {
             ("5 < 5;", V::Int(5), "<", V::Int(5)),
             ("5 == 5;", V::Int(5), "==", V::Int(5)),
             ("5 != 5;", V::Int(5), "!=", V::Int(5)),
+            ("5 % 5;", V::Int(5), "%", V::Int(5)),
+            ("5 <= 5;", V::Int(5), "<=", V::Int(5)),
+            ("5 >= 5;", V::Int(5), ">=", V::Int(5)),
+            ("true && false", V::Bool(true), "&&", V::Bool(false)),
+            ("true || false", V::Bool(true), "||", V::Bool(false)),
+            ("5 & 5;", V::Int(5), "&", V::Int(5)),
+            ("5 | 5;", V::Int(5), "|", V::Int(5)),
+            ("5 ^ 5;", V::Int(5), "^", V::Int(5)),
+            ("5 << 5;", V::Int(5), "<<", V::Int(5)),
+            ("5 >> 5;", V::Int(5), ">>", V::Int(5)),
+            ("5 ** 5;", V::Int(5), "**", V::Int(5)),
             ("true == true", V::Bool(true), "==", V::Bool(true)),
             ("true != false", V::Bool(true), "!=", V::Bool(false)),
             ("false == false", V::Bool(false), "==", V::Bool(false)),
@@ -402,8 +2244,8 @@ mod tests {
             match parse(input) {
                 Err(errors) => test_print_errors(errors),
                 Ok(program) => {
-                    assert!(program.statements.len() > 0);
-                    let target = program.statements.get(0);
+                    assert!(!program.statements.is_empty());
+                    let target = program.statements.first();
                     if let Some(ast::Statement::Expr(expr)) = target {
                         test_infix_expression(expr, &expect_left_value, infix, &expect_right_value);
                     }
@@ -439,6 +2281,71 @@ mod tests {
             ("2 / (5 + 5)", "(2 / (5 + 5))"),
             ("-(5 + 5)", "(-(5 + 5))"),
             ("!(true == true)", "(!(true == true))"),
+            ("a < b && c >= d || e", "(((a < b) && (c >= d)) || e)"),
+            ("a || b && c", "(a || (b && c))"),
+            ("a == b && c != d", "((a == b) && (c != d))"),
+            ("a + b % c * d", "(a + ((b % c) * d))"),
+            ("a <= b == c >= d", "((a <= b) == (c >= d))"),
+            ("a | b ^ c & d", "(a | (b ^ (c & d)))"),
+            ("a & b == c", "(a & (b == c))"),
+            ("a << 1 + b", "(a << (1 + b))"),
+            ("a >> 1 < b << 2", "((a >> 1) < (b << 2))"),
+            ("~a & 0xFF", "((~a) & 0xFF)"),
+            ("a | b && c ^ d", "((a | b) && (c ^ d))"),
+            ("2 ** 3 ** 2", "(2 ** (3 ** 2))"),
+            ("-2 ** 2", "(-(2 ** 2))"),
+            ("2 ** -1", "(2 ** (-1))"),
+            ("a * b ** c", "(a * (b ** c))"),
+            ("a ** b * c", "((a ** b) * c)"),
+            ("2 ** 3 ** 2 == 512", "((2 ** (3 ** 2)) == 512)"),
+            ("a[1 + 2] * b", "((a[(1 + 2)]) * b)"),
+            ("-a.b", "(-(a.b))"),
+            ("a.b[c].d", "(((a.b)[c]).d)"),
+            ("0..10", "(0..10)"),
+            ("1..=n", "(1..=n)"),
+            ("a + 1..b * 2", "((a + 1)..(b * 2))"),
+            ("arr[1..3]", "(arr[(1..3)])"),
+            ("x in 1..10", "(x in (1..10))"),
+            ("x in xs && y", "((x in xs) && y)"),
+            ("null", "null"),
+            ("a?.b", "(a?.b)"),
+            ("a?.[i]", "(a?.[i])"),
+            ("a?.b.c?.[0]", "(((a?.b).c)?.[0])"),
+            ("a ?? b ?? c", "((a ?? b) ?? c)"),
+            ("a?.b ?? 0", "((a?.b) ?? 0)"),
+            ("a || b ?? c && d", "((a || b) ?? (c && d))"),
+            ("x = a ?? null", "(x = (a ?? null))"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            (
+                "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            ),
+            (
+                "add(a + b + c * d / f + g)",
+                "add((((a + b) + ((c * d) / f)) + g))",
+            ),
+            ("f()", "f()"),
+            ("f(a,)", "f(a)"),
+            ("\"abc\".len()", "(\"abc\".len)()"),
+            ("arr.push(1)", "(arr.push)(1)"),
+            ("-xs.len() * 2", "((-(xs.len)()) * 2)"),
+            (
+                "xs.filter(p).map(f).len()",
+                "(((xs.filter)(p).map)(f).len)()",
+            ),
+            ("f(x)[0].y", "((f(x)[0]).y)"),
+            ("a?.f(x)", "(a?.f)(x)"),
+            ("obj.items[i].name", "(((obj.items)[i]).name)"),
+            ("x |> f", "(x |> f)"),
+            ("x |> f(a)", "(x |> f(a))"),
+            (
+                "rows |> filter(valid) |> map(parse)",
+                "((rows |> filter(valid)) |> map(parse))",
+            ),
+            ("a + b |> f", "((a + b) |> f)"),
+            ("a ?? b |> f", "((a ?? b) |> f)"),
+            ("y = x |> f", "(y = (x |> f))"),
+            ("x |> obj.method(1)", "(x |> (obj.method)(1))"),
         ];
 
         inputs
@@ -451,8 +2358,275 @@ mod tests {
             });
     }
 
+    #[test]
+    fn test_radix_integer_literals() {
+        let inputs = vec![
+            ("0xFF", 255, "0xFF"),
+            ("0o755", 493, "0o755"),
+            ("0b1010", 10, "0b1010"),
+            ("1_000_000", 1_000_000, "1_000_000"),
+            ("0xFFFF_FFFF", 0xFFFF_FFFF, "0xFFFF_FFFF"),
+            ("0xFFFF_FFFF_FFFF_FFFF", -1, "0xFFFF_FFFF_FFFF_FFFF"),
+            ("0x8000_0000_0000_0000", i64::MIN, "0x8000_0000_0000_0000"),
+            ("9223372036854775807", i64::MAX, "9223372036854775807"),
+        ];
+
+        for (input, expect_value, expect_display) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => {
+                    assert_eq!(program.to_string(), expect_display);
+                    if let Some(ast::Statement::Expr(expr)) = program.statements.first() {
+                        test_expression(expr, &V::Int(expect_value));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_malformed_integer_literals() {
+        let inputs = vec![
+            ("0x", "missing digits in integer literal. 0x"),
+            ("0b_", "missing digits in integer literal. 0b_"),
+            (
+                "1__0",
+                "`_` must separate two digits in integer literal. 1__0",
+            ),
+            ("1_", "`_` must separate two digits in integer literal. 1_"),
+            (
+                "0x_FF",
+                "`_` must separate two digits in integer literal. 0x_FF",
+            ),
+            (
+                "0b102",
+                "invalid digit '2' for base 2 in integer literal. 0b102",
+            ),
+            (
+                "0o78",
+                "invalid digit '8' for base 8 in integer literal. 0o78",
+            ),
+            (
+                "12ab",
+                "invalid digit 'a' for base 10 in integer literal. 12ab",
+            ),
+            (
+                "0x1_0000_0000_0000_0000",
+                "integer literal out of range for u64. 0x1_0000_0000_0000_0000",
+            ),
+            (
+                "9223372036854775808",
+                "integer literal out of range for i64. 9223372036854775808",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_assign_expressions() {
+        let inputs = vec![
+            ("x = 5", "(x = 5)"),
+            ("x = y = z + 1", "(x = (y = (z + 1)))"),
+            ("arr[i] = v", "((arr[i]) = v)"),
+            ("obj.k = v", "((obj.k) = v)"),
+            ("x += 1", "(x += 1)"),
+            ("x -= a * b", "(x -= (a * b))"),
+            ("x *= 2", "(x *= 2)"),
+            ("x /= 2", "(x /= 2)"),
+            ("x = a || b", "(x = (a || b))"),
+            ("let x = y = 3;", "let x = (y = 3);"),
+        ];
+
+        inputs
+            .iter()
+            .for_each(|(input, expected)| match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(&program.to_string(), expected),
+            });
+    }
+
+    #[test]
+    fn test_invalid_assign_targets() {
+        let inputs = vec![
+            ("1 = 2", "invalid assignment target. 1"),
+            ("a + b = c", "invalid assignment target. (a + b)"),
+            ("-x += 1", "invalid assignment target. (-x)"),
+            ("x = 1 = 2", "invalid assignment target. 1"),
+            ("a?.b = 1", "invalid assignment target. (a?.b)"),
+            ("a?.[0] += 1", "invalid assignment target. (a?.[0])"),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_chained_ranges() {
+        let inputs = vec![
+            (
+                "1..2..3",
+                "range cannot be an operand of another range. (1..2)",
+            ),
+            (
+                "a..=b..c + 1",
+                "range cannot be an operand of another range. (a..=b)",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+
+        match parse("(1..2)..3") {
+            Err(errors) => test_print_errors(errors),
+            Ok(program) => assert_eq!(program.to_string(), "((1..2)..3)"),
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let inputs = vec![
+            ("if (x < y) { x }", "if ((x < y)) {x}"),
+            (
+                "if (x < y) { x } else { y; z }",
+                "if ((x < y)) {x} else {yz}",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_loop_statements() {
+        let inputs = vec![
+            ("while (i < 10) { i += 1; }", "while ((i < 10)) {(i += 1)}"),
+            (
+                "for (x in xs) { total += x; }",
+                "for (x in xs) {(total += x)}",
+            ),
+            (
+                "while (true) { if (done) { break; } continue; }",
+                "while (true) {if (done) {break;}continue;}",
+            ),
+            ("for (i in 0..n) { }", "for (i in (0..n)) {}"),
+            (
+                "for (k in keys) { for (v in vals) { break } }",
+                "for (k in keys) {for (v in vals) {break;}}",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_loop_control_outside_loop() {
+        let inputs = vec![
+            ("break;", "break outside of a loop"),
+            ("continue;", "continue outside of a loop"),
+            ("while (x) { } if (y) { break; }", "break outside of a loop"),
+            (
+                "while (x) { y",
+                "expect token (expected \"}\", found \"EOF\")",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
+    #[test]
+    fn test_match_expression() {
+        let inputs = vec![
+            (
+                "match (x) { 0 => a, -1 => b, true => c, null => d, _ => e }",
+                "match (x) {0 => a, -1 => b, true => c, null => d, _ => e}",
+            ),
+            (
+                "match (n) { 1..10 => small, 10..=0xFF => byte, other => other, }",
+                "match (n) {1..10 => small, 10..=0xFF => byte, other => other}",
+            ),
+            (
+                "match (msg) { [first, ..rest] => first, [..] => 0, [a, .., z] => a + z }",
+                "match (msg) {[first, ..rest] => first, [..] => 0, [a, .., z] => (a + z)}",
+            ),
+            (
+                "match (msg) { {kind: 1, body} => body, {kind: _, id: [x]} => x }",
+                "match (msg) {{kind: 1, body} => body, {kind: _, id: [x]} => x}",
+            ),
+            (
+                "match (x) { n if n > 0 => n, _ => -x }",
+                "match (x) {n if (n > 0) => n, _ => (-x)}",
+            ),
+            (
+                "y = match (x) { _ => 1 } + 1",
+                "(y = (match (x) {_ => 1} + 1))",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            match parse(input) {
+                Err(errors) => test_print_errors(errors),
+                Ok(program) => assert_eq!(program.to_string(), expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_match_patterns() {
+        let inputs = vec![
+            (
+                "match (x) { a + b => 1 }",
+                "expect token (expected \"=>\", found \"+\")",
+            ),
+            ("match (x) { ..rest => 1 }", "expect pattern token. .."),
+            (
+                "match (x) { [a, ..b, ..c] => 1 }",
+                "more than one rest pattern in array pattern. [a, ..b]",
+            ),
+            ("match (x) { 1..y => 1 }", "expect pattern token. y"),
+            (
+                "match (x) { 1 => a 2 => b }",
+                "expect token (expected \",\", found \"2\")",
+            ),
+        ];
+
+        for (input, expect_error) in inputs {
+            match parse(input) {
+                Ok(program) => panic!("expected error, got {}", program),
+                Err(errors) => assert_eq!(errors.0[0].to_string(), expect_error),
+            }
+        }
+    }
+
     fn test_identifier(identifier: &ast::Identifier, v: String) {
-        assert_eq!(*identifier, ast::Identifier(v));
+        assert_eq!(*identifier, ast::Identifier(v, Span::default()));
     }
 
     fn test_integer_literal(il: &ast::Expression, v: &V) {
@@ -483,9 +2657,9 @@ mod tests {
                 right,
                 ..
             } => {
-                test_integer_literal(&*left, expect_left_value);
+                test_integer_literal(left, expect_left_value);
                 test_operator(operator, infix);
-                test_integer_literal(&*right, expect_right_value);
+                test_integer_literal(right, expect_right_value);
             }
             _ => panic!(),
         }
@@ -496,9 +2670,12 @@ mod tests {
             V::Bool(v) => assert_eq!(*expr, ast::Expression::BooleanLiteral(*v)),
             V::Ident(v) => assert_eq!(
                 *expr,
-                ast::Expression::Ident(ast::Identifier(v.to_string()))
+                ast::Expression::Ident(ast::Identifier(v.to_string(), Span::default()))
             ),
-            V::Int(v) => assert_eq!(*expr, ast::Expression::IntegerLiteral(*v)),
+            V::Int(v) => match expr {
+                ast::Expression::IntegerLiteral { value, .. } => assert_eq!(value, v),
+                _ => panic!("expected integer literal, got {:?}", expr),
+            },
             V::Infix(left, infix, right) => test_infix_expression(expr, left, infix, right),
         }
     }
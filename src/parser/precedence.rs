@@ -1,28 +1,128 @@
 use crate::lexer::Token;
 
-#[derive(PartialOrd, PartialEq, Debug)]
+#[derive(PartialOrd, PartialEq, Debug, Clone, Copy)]
 pub enum Precedence {
     Lowest,
+    Assign,      // = or +=
+    Pipeline,    // |>
+    Coalesce,    // ??
+    LogicalOr,   // ||
+    LogicalAnd,  // &&
+    BitOr,       // |
+    BitXor,      // ^
+    BitAnd,      // &
     Equals,      // ==
-    LessGreater, // > or <
+    LessGreater, // > or < or in
+    Shift,       // << or >>
+    Range,       // .. or ..=
     Sum,         // +
-    Product,     // *
-    Prefix,      // -X or !X
+    Product,     // * or %
+    Prefix,      // -X, !X or ~X
+    Exponent,    // **
     Call,        // myFunction(X)
+    Postfix,     // X?
+    Index,       // array[index], object.field or object?.field
+}
+
+impl Precedence {
+    /// Precedence for the right-hand operand of the infix operator `token`.
+    /// A right-associative operator lets an operator of its own level on the
+    /// right bind first, so `a ** b ** c` parses as `a ** (b ** c)`. A
+    /// non-associative one stops before it, and the parser rejects the chain.
+    pub fn right_operand(token: &Token) -> Self {
+        let precedence = Precedence::from(token);
+        match Associativity::from(token) {
+            Associativity::Left | Associativity::None => precedence,
+            Associativity::Right => precedence.lower(),
+        }
+    }
+
+    fn lower(self) -> Self {
+        match self {
+            Precedence::Lowest | Precedence::Assign => Precedence::Lowest,
+            Precedence::Pipeline => Precedence::Assign,
+            Precedence::Coalesce => Precedence::Pipeline,
+            Precedence::LogicalOr => Precedence::Coalesce,
+            Precedence::LogicalAnd => Precedence::LogicalOr,
+            Precedence::BitOr => Precedence::LogicalAnd,
+            Precedence::BitXor => Precedence::BitOr,
+            Precedence::BitAnd => Precedence::BitXor,
+            Precedence::Equals => Precedence::BitAnd,
+            Precedence::LessGreater => Precedence::Equals,
+            Precedence::Shift => Precedence::LessGreater,
+            Precedence::Range => Precedence::Shift,
+            Precedence::Sum => Precedence::Range,
+            Precedence::Product => Precedence::Sum,
+            Precedence::Prefix => Precedence::Product,
+            Precedence::Exponent => Precedence::Prefix,
+            Precedence::Call => Precedence::Exponent,
+            Precedence::Postfix => Precedence::Call,
+            Precedence::Index => Precedence::Postfix,
+        }
+    }
 }
 
 impl From<&Token> for Precedence {
     fn from(token: &Token) -> Self {
         match token {
+            Token::Assign => Precedence::Assign,
+            Token::PlusAssign => Precedence::Assign,
+            Token::MinusAssign => Precedence::Assign,
+            Token::AsteriskAssign => Precedence::Assign,
+            Token::SlashAssign => Precedence::Assign,
+            Token::Pipeline => Precedence::Pipeline,
+            Token::Coalesce => Precedence::Coalesce,
+            Token::Or => Precedence::LogicalOr,
+            Token::And => Precedence::LogicalAnd,
+            Token::Pipe => Precedence::BitOr,
+            Token::Caret => Precedence::BitXor,
+            Token::Ampersand => Precedence::BitAnd,
             Token::Eq => Precedence::Equals,
             Token::Neq => Precedence::Equals,
             Token::Lt => Precedence::LessGreater,
             Token::Gt => Precedence::LessGreater,
+            Token::LtEq => Precedence::LessGreater,
+            Token::GtEq => Precedence::LessGreater,
+            Token::In => Precedence::LessGreater,
+            Token::ShiftLeft => Precedence::Shift,
+            Token::ShiftRight => Precedence::Shift,
+            Token::DotDot => Precedence::Range,
+            Token::DotDotEq => Precedence::Range,
             Token::Plus => Precedence::Sum,
             Token::Minus => Precedence::Sum,
             Token::Slash => Precedence::Product,
             Token::Asterisk => Precedence::Product,
+            Token::Percent => Precedence::Product,
+            Token::Power => Precedence::Exponent,
+            Token::Lparen => Precedence::Call,
+            Token::Question => Precedence::Postfix,
+            Token::Lbracket => Precedence::Index,
+            Token::Dot => Precedence::Index,
+            Token::QuestionDot => Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
 }
+
+#[derive(PartialEq, Debug)]
+pub enum Associativity {
+    Left,
+    Right,
+    /// Cannot be chained: `1..2..3` is an error.
+    None,
+}
+
+impl From<&Token> for Associativity {
+    fn from(token: &Token) -> Self {
+        match token {
+            Token::Power
+            | Token::Assign
+            | Token::PlusAssign
+            | Token::MinusAssign
+            | Token::AsteriskAssign
+            | Token::SlashAssign => Associativity::Right,
+            Token::DotDot | Token::DotDotEq => Associativity::None,
+            _ => Associativity::Left,
+        }
+    }
+}
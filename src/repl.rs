@@ -11,7 +11,7 @@ pub fn start() {
             .read_line(&mut input)
             .expect("Failed to read line");
 
-        let mut lexer = Lexer::new(&mut input);
+        let mut lexer = Lexer::new(&input);
 
         loop {
             let tok = lexer.next_token();
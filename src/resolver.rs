@@ -0,0 +1,586 @@
+use crate::lexer::{Position, Span};
+use crate::parser::ast;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Names that are in scope in every module without being declared.
+pub const BUILTINS: &[&str] = &[
+    "len", "first", "last", "rest", "push", "puts", "ok", "err", "bytes", "string", "char", "int",
+];
+
+/// Where an identifier's declaration lives: `depth` scopes out from the use,
+/// at index `slot` among that scope's declarations.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Binding {
+    pub depth: usize,
+    pub slot: usize,
+    /// The span of the declaring identifier, or `None` for a builtin.
+    pub declaration: Option<Span>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Diagnostic {
+    #[error("unknown identifier {name} at {position}{}",
+        .suggestion.as_ref().map(|s| format!(", did you mean {}?", s)).unwrap_or_default())]
+    UnknownIdentifier {
+        name: String,
+        position: Position,
+        suggestion: Option<String>,
+    },
+    #[error("{name} is used at {used} before its definition at {defined}")]
+    UseBeforeDefinition {
+        name: String,
+        used: Position,
+        defined: Position,
+    },
+    #[error("{name} is declared at {position} but never used")]
+    UnusedBinding { name: String, position: Position },
+}
+
+impl Diagnostic {
+    /// Unused bindings are reported, but don't stop a program from running.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, Diagnostic::UnusedBinding { .. })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Resolution {
+    /// The binding of every resolved identifier, keyed by where it is used.
+    pub bindings: HashMap<Position, Binding>,
+    /// The slot of every declared name in its scope, keyed by where it is
+    /// declared.
+    pub declarations: HashMap<Position, usize>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Binds every identifier in `program` to its declaration.
+///
+/// Scopes follow the parser's: blocks, function parameter lists, loop
+/// variables, `catch` parameters and `match` arms each open one. Inside a
+/// function body, names declared later in an enclosing scope are allowed,
+/// since the function may only be called once they are defined.
+pub fn resolve(program: &ast::Program) -> Resolution {
+    let builtins = Scope {
+        slots: BUILTINS
+            .iter()
+            .map(|name| Slot {
+                name: name.to_string(),
+                declaration: None,
+                defined: true,
+                used: true,
+                exported: false,
+            })
+            .collect(),
+        function: false,
+    };
+    let mut resolver = Resolver {
+        scopes: vec![builtins],
+        resolution: Resolution::default(),
+    };
+
+    resolver.push_scope(false);
+    resolver.resolve_statements(&program.statements);
+    resolver.pop_scope();
+    resolver.resolution
+}
+
+struct Slot {
+    name: String,
+    declaration: Option<Span>,
+    defined: bool,
+    used: bool,
+    exported: bool,
+}
+
+struct Scope {
+    slots: Vec<Slot>,
+    /// Whether this scope holds a function's parameters.
+    function: bool,
+}
+
+struct Resolver {
+    scopes: Vec<Scope>,
+    resolution: Resolution,
+}
+
+impl Resolver {
+    fn push_scope(&mut self, function: bool) {
+        self.scopes.push(Scope {
+            slots: vec![],
+            function,
+        });
+    }
+
+    fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().expect("resolver has no scope");
+        for slot in scope.slots {
+            if slot.used || slot.exported || slot.name.starts_with('_') {
+                continue;
+            }
+            if let Some(declaration) = slot.declaration {
+                self.resolution.diagnostics.push(Diagnostic::UnusedBinding {
+                    name: slot.name,
+                    position: declaration.start,
+                });
+            }
+        }
+    }
+
+    fn declare(&mut self, identifier: &ast::Identifier, defined: bool, exported: bool) {
+        let ast::Identifier(name, span) = identifier;
+        let scope = self.scopes.last_mut().expect("resolver has no scope");
+        self.resolution
+            .declarations
+            .insert(span.start, scope.slots.len());
+        scope.slots.push(Slot {
+            name: name.clone(),
+            declaration: Some(*span),
+            defined,
+            used: false,
+            exported,
+        });
+    }
+
+    /// Marks a declaration found by `declare_statements` as reached.
+    fn define(&mut self, identifier: &ast::Identifier) {
+        let ast::Identifier(name, span) = identifier;
+        let scope = self.scopes.last_mut().expect("resolver has no scope");
+        match scope
+            .slots
+            .iter_mut()
+            .find(|slot| slot.name == *name && slot.declaration == Some(*span))
+        {
+            Some(slot) => slot.defined = true,
+            None => self.declare(identifier, true, false),
+        }
+    }
+
+    fn lookup(&mut self, identifier: &ast::Identifier) {
+        let ast::Identifier(name, span) = identifier;
+        let mut in_function = false;
+        for (depth, scope) in self.scopes.iter_mut().rev().enumerate() {
+            let slot = match scope
+                .slots
+                .iter()
+                .rposition(|slot| slot.name == *name && slot.defined)
+            {
+                None if in_function => scope.slots.iter().position(|slot| slot.name == *name),
+                slot => slot,
+            };
+            if let Some(slot) = slot {
+                scope.slots[slot].used = true;
+                let binding = Binding {
+                    depth,
+                    slot,
+                    declaration: scope.slots[slot].declaration,
+                };
+                self.resolution.bindings.insert(span.start, binding);
+                return;
+            }
+            in_function |= scope.function;
+        }
+
+        let pending = self
+            .scopes
+            .iter()
+            .flat_map(|scope| &scope.slots)
+            .find_map(|slot| match slot.declaration {
+                Some(declaration) if slot.name == *name => Some(declaration),
+                _ => None,
+            });
+        let diagnostic = match pending {
+            Some(declaration) => Diagnostic::UseBeforeDefinition {
+                name: name.clone(),
+                used: span.start,
+                defined: declaration.start,
+            },
+            None => Diagnostic::UnknownIdentifier {
+                name: name.clone(),
+                position: span.start,
+                suggestion: self.suggest(name),
+            },
+        };
+        self.resolution.diagnostics.push(diagnostic);
+    }
+
+    /// The name in scope closest to `name`, if any is close enough to be a
+    /// likely typo.
+    fn suggest(&self, name: &str) -> Option<String> {
+        self.scopes
+            .iter()
+            .flat_map(|scope| &scope.slots)
+            .map(|slot| (edit_distance(name, &slot.name), &slot.name))
+            .filter(|(distance, _)| *distance <= 2 && *distance < name.len())
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate.clone())
+    }
+
+    /// Declares everything `statements` bind in the current scope up front,
+    /// so that uses before a `let` can be told apart from unknown names.
+    /// Named functions are hoisted, so they are defined right away.
+    fn declare_statements(&mut self, statements: &[ast::Statement], exported: bool) {
+        for statement in statements {
+            match statement {
+                ast::Statement::Let(pattern, _, _) | ast::Statement::Const(pattern, _, _) => {
+                    for identifier in pattern.identifiers() {
+                        self.declare(identifier, false, exported);
+                    }
+                }
+                ast::Statement::FnDecl { name, .. } => self.declare(name, true, exported),
+                ast::Statement::Import { alias, .. } => self.declare(alias, false, exported),
+                ast::Statement::Export(declaration) => {
+                    self.declare_statements(std::slice::from_ref(declaration), true)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &[ast::Statement]) {
+        self.declare_statements(statements, false);
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_block(&mut self, block: &ast::BlockStatement) {
+        self.push_scope(false);
+        self.resolve_statements(&block.statements);
+        self.pop_scope();
+    }
+
+    fn resolve_statement(&mut self, statement: &ast::Statement) {
+        match statement {
+            ast::Statement::Let(pattern, _, value) | ast::Statement::Const(pattern, _, value) => {
+                self.resolve_expression(value);
+                self.resolve_pattern(pattern);
+                for identifier in pattern.identifiers() {
+                    self.define(identifier);
+                }
+            }
+            ast::Statement::Return(value, _)
+            | ast::Statement::Expr(value)
+            | ast::Statement::Throw(value, _) => self.resolve_expression(value),
+            ast::Statement::While { condition, body } => {
+                self.resolve_expression(condition);
+                self.resolve_block(body);
+            }
+            ast::Statement::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                self.resolve_expression(iterable);
+                self.push_scope(false);
+                self.declare(variable, true, false);
+                self.resolve_block(body);
+                self.pop_scope();
+            }
+            ast::Statement::Break(_) | ast::Statement::Continue(_) => {}
+            ast::Statement::FnDecl {
+                parameters, body, ..
+            } => self.resolve_function(parameters, |resolver| resolver.resolve_block(body)),
+            ast::Statement::Import { alias, .. } => self.define(alias),
+            ast::Statement::Export(declaration) => self.resolve_statement(declaration),
+            ast::Statement::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.resolve_block(body);
+                if let Some(catch) = catch {
+                    self.push_scope(false);
+                    self.declare(&catch.parameter, true, false);
+                    self.resolve_block(&catch.body);
+                    self.pop_scope();
+                }
+                if let Some(finally) = finally {
+                    self.resolve_block(finally);
+                }
+            }
+        }
+    }
+
+    fn resolve_function(
+        &mut self,
+        parameters: &[ast::Parameter],
+        body: impl FnOnce(&mut Resolver),
+    ) {
+        self.push_scope(true);
+        for parameter in parameters {
+            if let Some(default) = &parameter.default {
+                self.resolve_expression(default);
+            }
+            self.declare(&parameter.name, true, false);
+        }
+        body(self);
+        self.pop_scope();
+    }
+
+    /// Resolves the expressions inside a pattern: literals, ranges and
+    /// defaults. The names the pattern binds are declared by the caller.
+    fn resolve_pattern(&mut self, pattern: &ast::Pattern) {
+        match pattern {
+            ast::Pattern::Wildcard | ast::Pattern::Binding(_) | ast::Pattern::Rest(_) => {}
+            ast::Pattern::Literal(literal) => self.resolve_expression(literal),
+            ast::Pattern::Range { start, end, .. } => {
+                self.resolve_expression(start);
+                self.resolve_expression(end);
+            }
+            ast::Pattern::Array(elements) => {
+                for element in elements {
+                    self.resolve_pattern(element);
+                }
+            }
+            ast::Pattern::Hash(fields) => {
+                for (_, field) in fields {
+                    self.resolve_pattern(field);
+                }
+            }
+            ast::Pattern::Default { pattern, default } => {
+                self.resolve_expression(default);
+                self.resolve_pattern(pattern);
+            }
+        }
+    }
+
+    fn resolve_expression(&mut self, expression: &ast::Expression) {
+        match expression {
+            ast::Expression::Ident(identifier) => self.lookup(identifier),
+            ast::Expression::IntegerLiteral { .. }
+            | ast::Expression::BooleanLiteral(_)
+            | ast::Expression::NullLiteral
+            | ast::Expression::StringLiteral { .. }
+            | ast::Expression::CharLiteral(_)
+            | ast::Expression::ByteStringLiteral(_) => {}
+            ast::Expression::Template(segments) => {
+                for segment in segments {
+                    if let ast::TemplateSegment::Interpolation { expression, .. } = segment {
+                        self.resolve_expression(expression);
+                    }
+                }
+            }
+            ast::Expression::Prefix { right, .. } => self.resolve_expression(right),
+            ast::Expression::Infix { left, right, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            ast::Expression::Assign { target, value, .. } => {
+                self.resolve_expression(target);
+                self.resolve_expression(value);
+            }
+            ast::Expression::Index { left, index, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(index);
+            }
+            ast::Expression::Member { object, .. } => self.resolve_expression(object),
+            ast::Expression::Function {
+                parameters, body, ..
+            } => self.resolve_function(parameters, |resolver| resolver.resolve_block(body)),
+            ast::Expression::Lambda {
+                parameters, body, ..
+            } => self.resolve_function(parameters, |resolver| resolver.resolve_expression(body)),
+            ast::Expression::Call {
+                function,
+                arguments,
+            } => {
+                self.resolve_expression(function);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            ast::Expression::Spread(value) | ast::Expression::Propagate(value) => {
+                self.resolve_expression(value)
+            }
+            ast::Expression::Pipe { value, function } => {
+                self.resolve_expression(value);
+                self.resolve_expression(function);
+            }
+            ast::Expression::Range { start, end, .. } => {
+                self.resolve_expression(start);
+                self.resolve_expression(end);
+            }
+            ast::Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_block(consequence);
+                if let Some(alternative) = alternative {
+                    self.resolve_block(alternative);
+                }
+            }
+            ast::Expression::Match { value, arms } => {
+                self.resolve_expression(value);
+                for arm in arms {
+                    self.push_scope(false);
+                    self.resolve_pattern(&arm.pattern);
+                    for identifier in arm.pattern.identifiers() {
+                        self.declare(identifier, true, false);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.resolve_expression(guard);
+                    }
+                    self.resolve_expression(&arm.body);
+                    self.pop_scope();
+                }
+            }
+        }
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn resolve_source(input: &str) -> Resolution {
+        match parse(input) {
+            Ok(program) => resolve(&program),
+            Err(errors) => panic!("parser errors:\n{}", errors),
+        }
+    }
+
+    #[test]
+    fn test_bindings() {
+        let input = "let a = 1;\nlet f = fn(x) { let y = x; a + y };\nf(len(a));";
+        let resolution = resolve_source(input);
+        assert_eq!(resolution.diagnostics, vec![]);
+
+        let tests = vec![
+            // `x` in `let y = x`: the function's parameter scope, one out
+            // from the body block.
+            ((2, 25), 1, 0, Some((2, 12))),
+            // `a` in the body: body block, parameters, then the program.
+            ((2, 28), 2, 0, Some((1, 5))),
+            ((2, 32), 0, 0, Some((2, 21))),
+            ((3, 1), 0, 1, Some((2, 5))),
+            // Builtins live in the outermost scope.
+            ((3, 3), 1, 0, None),
+        ];
+
+        let declarations = vec![((1, 5), 0), ((2, 5), 1), ((2, 12), 0), ((2, 21), 0)];
+        for ((line, column), slot) in declarations {
+            let position = Position { line, column };
+            assert_eq!(resolution.declarations[&position], slot, "{}", position);
+        }
+
+        for ((line, column), depth, slot, declaration) in tests {
+            let binding = resolution.bindings[&Position { line, column }];
+            assert_eq!(binding.depth, depth, "depth at {}:{}", line, column);
+            assert_eq!(binding.slot, slot, "slot at {}:{}", line, column);
+            assert_eq!(
+                binding
+                    .declaration
+                    .map(|span| (span.start.line, span.start.column)),
+                declaration,
+            );
+        }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let inputs = vec![
+            ("let x = 1; x", vec![]),
+            (
+                "let length = 1; lenght",
+                vec![
+                    "unknown identifier lenght at 1:17, did you mean length?",
+                    "length is declared at 1:5 but never used",
+                ],
+            ),
+            ("let x = 1; y + x", vec!["unknown identifier y at 1:12"]),
+            (
+                "x + 1; let x = 1; x",
+                vec!["x is used at 1:1 before its definition at 1:12"],
+            ),
+            (
+                "let x = x + 1;",
+                vec![
+                    "x is used at 1:9 before its definition at 1:5",
+                    "x is declared at 1:5 but never used",
+                ],
+            ),
+            ("let x = 1; if (x) { let x = 2; x } else { x }", vec![]),
+            ("fn f() { g() } let g = fn() { f() };", vec![]),
+            (
+                "fn is_even(n) { is_odd(n) } fn is_odd(n) { is_even(n) }",
+                vec![],
+            ),
+            (
+                "let unused = 1; let _ignored = 2; export let shared = 3;",
+                vec!["unused is declared at 1:5 but never used"],
+            ),
+            (
+                "let f = |a, b| a;",
+                vec![
+                    "b is declared at 1:13 but never used",
+                    "f is declared at 1:5 but never used",
+                ],
+            ),
+            (
+                "match (m) { [head, .._rest] => head, {kind: k} if k > 0 => k, _ => null }",
+                vec!["unknown identifier m at 1:8"],
+            ),
+            (
+                "for (i in 0..3) { puts(i) } i",
+                vec!["unknown identifier i at 1:29"],
+            ),
+            (
+                "try { f() } catch (e) { puts(e) }",
+                vec!["unknown identifier f at 1:7"],
+            ),
+            (
+                "let n = 1; \"${n + m}\"",
+                vec!["unknown identifier m at 1:19"],
+            ),
+            (
+                "import \"lib\" as lib;",
+                vec!["lib is declared at 1:17 but never used"],
+            ),
+            (
+                "let {port = defualt_port} = cfg; let default_port = 1; port",
+                vec![
+                    "unknown identifier cfg at 1:29",
+                    "unknown identifier defualt_port at 1:13, did you mean default_port?",
+                    "default_port is declared at 1:38 but never used",
+                ],
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            let diagnostics: Vec<String> = resolve_source(input)
+                .diagnostics
+                .iter()
+                .map(|d| d.to_string())
+                .collect();
+            assert_eq!(diagnostics, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("lenght", "length"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
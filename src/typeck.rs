@@ -0,0 +1,5 @@
+mod checker;
+mod types;
+
+pub use self::checker::{check, infer, Inference, TypeError};
+pub use self::types::Type;
@@ -0,0 +1,1176 @@
+use super::types::{Scheme, Substitution, Type, UnifyError};
+use crate::lexer::Position;
+use crate::parser::ast;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TypeError {
+    #[error("mismatched types in {context} at {position}: expected {expected}, found {found}")]
+    Mismatch {
+        context: String,
+        position: Position,
+        expected: Type,
+        found: Type,
+    },
+    #[error(
+        "infinite type in {context} at {position}: {expected} refers to itself through {found}"
+    )]
+    InfiniteType {
+        context: String,
+        position: Position,
+        expected: Type,
+        found: Type,
+    },
+    #[error(
+        "wrong number of arguments in {context} at {position}: expected {expected}, found {found}"
+    )]
+    Arity {
+        context: String,
+        position: Position,
+        expected: String,
+        found: usize,
+    },
+    #[error("unknown type {name} at {position}")]
+    UnknownType { name: String, position: Position },
+}
+
+impl TypeError {
+    pub fn position(&self) -> Position {
+        match self {
+            TypeError::Mismatch { position, .. }
+            | TypeError::InfiniteType { position, .. }
+            | TypeError::Arity { position, .. }
+            | TypeError::UnknownType { position, .. } => *position,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Inference {
+    /// The type of every identifier, keyed by where it is written.
+    pub types: HashMap<Position, Type>,
+    pub errors: Vec<TypeError>,
+}
+
+/// Infers types for `program`, using annotations where they are given, and
+/// returns every mismatch found.
+///
+/// The checker is deliberately lenient where the language is dynamic: `null`
+/// fits any type, and members, hash lookups and unknown names are left
+/// unconstrained.
+pub fn check(program: &ast::Program) -> Vec<TypeError> {
+    infer(program).errors
+}
+
+/// Like `check`, but also returns the type inferred for each identifier.
+pub fn infer(program: &ast::Program) -> Inference {
+    let mut checker = Checker {
+        substitution: Substitution::default(),
+        scopes: vec![HashMap::new()],
+        return_types: vec![],
+        types: HashMap::new(),
+        position: Position::default(),
+        errors: vec![],
+    };
+    checker.check_statements(&program.statements, false);
+    Inference {
+        types: checker
+            .types
+            .iter()
+            .map(|(position, ty)| (*position, checker.substitution.resolve(ty)))
+            .collect(),
+        errors: checker.errors,
+    }
+}
+
+struct Checker {
+    substitution: Substitution,
+    scopes: Vec<HashMap<String, Scheme>>,
+    return_types: Vec<Type>,
+    types: HashMap<Position, Type>,
+    /// Where the innermost statement or expression being checked starts, as
+    /// near as the AST records it. Errors are reported there.
+    position: Position,
+    errors: Vec<TypeError>,
+}
+
+impl Checker {
+    fn unify(&mut self, expected: &Type, found: &Type, context: &dyn ToString) {
+        let error = match self.substitution.unify(expected, found) {
+            Ok(()) => return,
+            Err(UnifyError::Mismatch) => TypeError::Mismatch {
+                context: context.to_string(),
+                position: self.position,
+                expected: self.substitution.resolve(expected),
+                found: self.substitution.resolve(found),
+            },
+            Err(UnifyError::InfiniteType) => TypeError::InfiniteType {
+                context: context.to_string(),
+                position: self.position,
+                expected: self.substitution.resolve(expected),
+                found: self.substitution.resolve(found),
+            },
+        };
+        self.errors.push(error);
+    }
+
+    fn bind(&mut self, name: &ast::Identifier, scheme: Scheme) {
+        self.types.insert(name.1.start, scheme.ty.clone());
+        let scope = self.scopes.last_mut().expect("checker has no scope");
+        scope.insert(name.0.clone(), scheme);
+    }
+
+    fn generalize(&mut self, name: &ast::Identifier, ty: &Type) {
+        self.scopes
+            .last_mut()
+            .expect("checker has no scope")
+            .remove(&name.0);
+        let scheme = self
+            .substitution
+            .generalize(ty, self.scopes.iter().flat_map(|scope| scope.values()));
+        self.bind(name, scheme);
+    }
+
+    fn in_scope<T>(&mut self, check: impl FnOnce(&mut Checker) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let result = check(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Runs `check` with errors reported at `position`, if that is known.
+    fn at<T>(&mut self, position: Option<Position>, check: impl FnOnce(&mut Checker) -> T) -> T {
+        let outer = self.position;
+        self.position = position.unwrap_or(outer);
+        let result = check(self);
+        self.position = outer;
+        result
+    }
+
+    /// Checks a sequence of statements and returns the type of the last one
+    /// if it is an expression whose `value` is used.
+    fn check_statements(&mut self, statements: &[ast::Statement], value: bool) -> Type {
+        // Named functions are hoisted, so every one of them is in scope from
+        // the start of the block. Each is checked just before its first use
+        // or its declaration, whichever comes first, so that earlier uses see
+        // the generalized type.
+        let declarations: Vec<&ast::Statement> = statements
+            .iter()
+            .map(hoisted)
+            .filter(|statement| matches!(statement, ast::Statement::FnDecl { .. }))
+            .collect();
+        for declaration in &declarations {
+            if let ast::Statement::FnDecl { name, .. } = declaration {
+                let ty = self.substitution.fresh();
+                self.bind(name, Scheme::monomorphic(ty));
+            }
+        }
+        let mut pending = recursive_groups(&declarations);
+
+        let mut ty = None;
+        for (i, statement) in statements.iter().enumerate() {
+            let last = i + 1 == statements.len();
+            let mut names = vec![];
+            match hoisted(statement) {
+                ast::Statement::FnDecl { name, .. } => names.push(name.0.as_str()),
+                statement => statement_references(statement, &mut names),
+            }
+            self.check_pending_functions(&mut pending, &names);
+
+            ty = match statement {
+                ast::Statement::Expr(expression) if last && value => Some(self.infer(expression)),
+                statement => {
+                    self.check_statement(statement);
+                    None
+                }
+            };
+        }
+        ty.unwrap_or_else(|| self.substitution.fresh())
+    }
+
+    /// Checks the pending groups of function declarations that contain any
+    /// of `names`, after the groups those refer to in turn.
+    fn check_pending_functions(&mut self, pending: &mut Vec<Vec<&ast::Statement>>, names: &[&str]) {
+        while let Some(i) = pending.iter().position(|group| {
+            group
+                .iter()
+                .any(|declaration| names.contains(&declared_name(declaration)))
+        }) {
+            let group = pending.remove(i);
+            let mut references = vec![];
+            for declaration in &group {
+                statement_references(declaration, &mut references);
+            }
+            self.check_pending_functions(pending, &references);
+            self.check_functions(&group);
+        }
+    }
+
+    /// Checks a group of mutually recursive function declarations, which
+    /// use each other at a single type, then generalizes all of them.
+    fn check_functions(&mut self, declarations: &[&ast::Statement]) {
+        let mut types = vec![];
+        for declaration in declarations {
+            if let ast::Statement::FnDecl {
+                name,
+                parameters,
+                return_type,
+                body,
+            } = declaration
+            {
+                let ty = self.at(Some(name.1.start), |checker| {
+                    let context = format!("fn {}", name);
+                    let ty = checker.infer_function(parameters, return_type, &context, |checker| {
+                        checker.check_block(body, true)
+                    });
+                    if let Some(hoisted) = checker.lookup(&name.0) {
+                        let hoisted = checker.substitution.instantiate(&hoisted);
+                        checker.unify(&hoisted, &ty, &context);
+                    }
+                    ty
+                });
+                types.push((name, ty));
+            }
+        }
+
+        let scope = self.scopes.last_mut().expect("checker has no scope");
+        for (name, _) in &types {
+            scope.remove(&name.0);
+        }
+        for (name, ty) in &types {
+            let scheme = self
+                .substitution
+                .generalize(ty, self.scopes.iter().flat_map(|scope| scope.values()));
+            self.bind(name, scheme);
+        }
+    }
+
+    fn check_block(&mut self, block: &ast::BlockStatement, value: bool) -> Type {
+        self.in_scope(|checker| checker.check_statements(&block.statements, value))
+    }
+
+    fn check_statement(&mut self, statement: &ast::Statement) {
+        self.at(statement.position(), |checker| {
+            checker.check_statement_at(statement)
+        })
+    }
+
+    fn check_statement_at(&mut self, statement: &ast::Statement) {
+        match statement {
+            ast::Statement::Let(pattern, annotation, value)
+            | ast::Statement::Const(pattern, annotation, value) => {
+                let ty = self.infer(value);
+                if let Some(annotation) = annotation {
+                    let expected = self.annotation(annotation);
+                    let context = format!("{} {}", keyword(statement), pattern);
+                    self.unify(&expected, &ty, &context);
+                }
+                match (pattern, value) {
+                    (
+                        ast::Pattern::Binding(name),
+                        ast::Expression::Function { .. } | ast::Expression::Lambda { .. },
+                    ) => self.generalize(name, &ty),
+                    _ => self.bind_pattern(pattern, &ty),
+                }
+            }
+            ast::Statement::Return(value, _) => {
+                let ty = self.infer(value);
+                if let Some(expected) = self.return_types.last().cloned() {
+                    self.unify(&expected, &ty, value);
+                }
+            }
+            ast::Statement::Expr(expression) => self.check_expression_statement(expression),
+            ast::Statement::While { condition, body } => {
+                self.infer(condition);
+                self.check_block(body, false);
+            }
+            ast::Statement::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                let ty = self.infer(iterable);
+                let element = match self.substitution.resolve(&ty) {
+                    Type::Range | Type::Bytes => Type::Int,
+                    Type::String => Type::Char,
+                    Type::Array(element) => *element,
+                    _ => self.substitution.fresh(),
+                };
+                self.in_scope(|checker| {
+                    checker.bind(variable, Scheme::monomorphic(element));
+                    checker.check_block(body, false);
+                });
+            }
+            ast::Statement::Break(_) | ast::Statement::Continue(_) => {}
+            // Checked by `check_statements`, before its first use.
+            ast::Statement::FnDecl { .. } => {}
+            ast::Statement::Import { alias, .. } => {
+                let ty = self.substitution.fresh();
+                self.bind(alias, Scheme::monomorphic(ty));
+            }
+            ast::Statement::Export(declaration) => self.check_statement(declaration),
+            ast::Statement::Throw(value, _) => {
+                self.infer(value);
+            }
+            ast::Statement::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.check_block(body, false);
+                if let Some(catch) = catch {
+                    let ty = self.substitution.fresh();
+                    self.in_scope(|checker| {
+                        checker.bind(&catch.parameter, Scheme::monomorphic(ty));
+                        checker.check_block(&catch.body, false);
+                    });
+                }
+                if let Some(finally) = finally {
+                    self.check_block(finally, false);
+                }
+            }
+        }
+    }
+
+    /// Checks an expression whose value is discarded, so that the branches of
+    /// an `if` or `match` statement don't have to agree on a type.
+    fn check_expression_statement(&mut self, expression: &ast::Expression) {
+        match expression {
+            ast::Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                self.infer(condition);
+                self.check_block(consequence, false);
+                if let Some(alternative) = alternative {
+                    self.check_block(alternative, false);
+                }
+            }
+            ast::Expression::Match { value, arms } => {
+                let ty = self.infer(value);
+                for arm in arms {
+                    self.check_match_arm(arm, &ty);
+                }
+            }
+            expression => {
+                self.infer(expression);
+            }
+        }
+    }
+
+    fn check_match_arm(&mut self, arm: &ast::MatchArm, ty: &Type) -> Type {
+        self.in_scope(|checker| {
+            checker.bind_pattern(&arm.pattern, ty);
+            if let Some(guard) = &arm.guard {
+                checker.infer(guard);
+            }
+            checker.infer(&arm.body)
+        })
+    }
+
+    fn bind_pattern(&mut self, pattern: &ast::Pattern, ty: &Type) {
+        match pattern {
+            ast::Pattern::Wildcard | ast::Pattern::Rest(None) => {}
+            ast::Pattern::Binding(name) | ast::Pattern::Rest(Some(name)) => {
+                self.bind(name, Scheme::monomorphic(ty.clone()))
+            }
+            ast::Pattern::Literal(literal) => {
+                let found = self.infer(literal);
+                self.unify(ty, &found, literal);
+            }
+            ast::Pattern::Range { start, end, .. } => {
+                let found = self.infer(start);
+                self.unify(ty, &found, pattern);
+                let found = self.infer(end);
+                self.unify(ty, &found, pattern);
+            }
+            ast::Pattern::Array(elements) => {
+                let element = self.substitution.fresh();
+                self.unify(ty, &Type::Array(Box::new(element.clone())), pattern);
+                for p in elements {
+                    match p {
+                        ast::Pattern::Rest(Some(name)) => {
+                            let rest = Type::Array(Box::new(element.clone()));
+                            self.bind(name, Scheme::monomorphic(rest));
+                        }
+                        p => self.bind_pattern(p, &element),
+                    }
+                }
+            }
+            ast::Pattern::Hash(fields) => {
+                for (_, p) in fields {
+                    let field = self.substitution.fresh();
+                    self.bind_pattern(p, &field);
+                }
+            }
+            ast::Pattern::Default { pattern, default } => {
+                let found = self.infer(default);
+                self.unify(ty, &found, default);
+                self.bind_pattern(pattern, ty);
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Scheme> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+    }
+
+    fn annotation(&mut self, annotation: &ast::TypeAnnotation) -> Type {
+        match annotation {
+            ast::TypeAnnotation::Named(ast::Identifier(name, span)) => match name.as_str() {
+                "int" => Type::Int,
+                "bool" => Type::Bool,
+                "string" => Type::String,
+                "char" => Type::Char,
+                "bytes" => Type::Bytes,
+                "range" => Type::Range,
+                "null" => Type::Null,
+                "any" => self.substitution.fresh(),
+                _ => {
+                    self.errors.push(TypeError::UnknownType {
+                        name: name.clone(),
+                        position: span.start,
+                    });
+                    self.substitution.fresh()
+                }
+            },
+            ast::TypeAnnotation::Array(element) => Type::Array(Box::new(self.annotation(element))),
+            ast::TypeAnnotation::Function {
+                parameters,
+                return_type,
+            } => Type::Function(
+                parameters.iter().map(|p| self.annotation(p)).collect(),
+                Box::new(self.annotation(return_type)),
+            ),
+        }
+    }
+
+    fn infer(&mut self, expression: &ast::Expression) -> Type {
+        self.at(expression.position(), |checker| {
+            checker.infer_at(expression)
+        })
+    }
+
+    fn infer_at(&mut self, expression: &ast::Expression) -> Type {
+        match expression {
+            ast::Expression::Ident(ast::Identifier(name, span)) => {
+                let ty = match self.lookup(name) {
+                    Some(scheme) => self.substitution.instantiate(&scheme),
+                    None => self.substitution.fresh(),
+                };
+                self.types.insert(span.start, ty.clone());
+                ty
+            }
+            ast::Expression::IntegerLiteral { .. } => Type::Int,
+            ast::Expression::BooleanLiteral(_) => Type::Bool,
+            ast::Expression::NullLiteral => self.substitution.fresh(),
+            ast::Expression::StringLiteral { .. } => Type::String,
+            ast::Expression::Template(segments) => {
+                for segment in segments {
+                    if let ast::TemplateSegment::Interpolation { expression, .. } = segment {
+                        self.infer(expression);
+                    }
+                }
+                Type::String
+            }
+            ast::Expression::CharLiteral(_) => Type::Char,
+            ast::Expression::ByteStringLiteral(_) => Type::Bytes,
+            ast::Expression::Prefix {
+                operator, right, ..
+            } => {
+                let ty = self.infer(right);
+                match operator {
+                    ast::Operator::Bang => Type::Bool,
+                    _ => {
+                        self.unify(&Type::Int, &ty, expression);
+                        Type::Int
+                    }
+                }
+            }
+            ast::Expression::Infix {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = self.infer(left);
+                let right = self.infer(right);
+                self.infer_operator(expression, operator, &left, &right)
+            }
+            ast::Expression::Assign {
+                target,
+                operator,
+                value,
+                ..
+            } => {
+                let target = self.infer(target);
+                let value = self.infer(value);
+                match operator {
+                    ast::Operator::Assign => {
+                        self.unify(&target, &value, expression);
+                        target
+                    }
+                    ast::Operator::PlusAssign => {
+                        self.infer_operator(expression, &ast::Operator::Plus, &target, &value)
+                    }
+                    _ => self.infer_operator(expression, &ast::Operator::Minus, &target, &value),
+                }
+            }
+            ast::Expression::Index { left, index, .. } => {
+                let container = self.infer(left);
+                let index_ty = self.infer(index);
+                let slice = matches!(**index, ast::Expression::Range { .. });
+                match self.substitution.resolve(&container) {
+                    // Anything else may be a hash, indexed by any key.
+                    Type::Array(_) | Type::String | Type::Bytes if slice => container,
+                    Type::Array(element) => {
+                        self.unify(&Type::Int, &index_ty, expression);
+                        *element
+                    }
+                    Type::String => {
+                        self.unify(&Type::Int, &index_ty, expression);
+                        Type::Char
+                    }
+                    Type::Bytes => {
+                        self.unify(&Type::Int, &index_ty, expression);
+                        Type::Int
+                    }
+                    _ => self.substitution.fresh(),
+                }
+            }
+            ast::Expression::Member { object, .. } => {
+                self.infer(object);
+                self.substitution.fresh()
+            }
+            ast::Expression::Function {
+                parameters,
+                return_type,
+                body,
+            } => {
+                let context = format!("fn({})", join(parameters));
+                self.infer_function(parameters, return_type, &context, |checker| {
+                    checker.check_block(body, true)
+                })
+            }
+            ast::Expression::Lambda {
+                parameters, body, ..
+            } => {
+                let context = format!("|{}|", join(parameters));
+                self.infer_function(parameters, &None, &context, |checker| checker.infer(body))
+            }
+            ast::Expression::Call {
+                function,
+                arguments,
+            } => {
+                let arguments = self.infer_arguments(arguments);
+                self.infer_call(expression, function, arguments)
+            }
+            ast::Expression::Spread(value) | ast::Expression::Propagate(value) => {
+                self.infer(value);
+                self.substitution.fresh()
+            }
+            ast::Expression::Pipe { value, function } => {
+                let value = self.infer(value);
+                let (function, arguments) = match function.as_ref() {
+                    ast::Expression::Call {
+                        function,
+                        arguments,
+                    } => (&**function, self.infer_arguments(arguments)),
+                    function => (function, Some(vec![])),
+                };
+                let arguments = arguments
+                    .map(|arguments| std::iter::once(value).chain(arguments).collect::<Vec<_>>());
+                self.infer_call(expression, function, arguments)
+            }
+            ast::Expression::Range { start, end, .. } => {
+                let ty = self.infer(start);
+                self.unify(&Type::Int, &ty, expression);
+                let ty = self.infer(end);
+                self.unify(&Type::Int, &ty, expression);
+                Type::Range
+            }
+            ast::Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                self.infer(condition);
+                let ty = self.check_block(consequence, true);
+                match alternative {
+                    Some(alternative) => {
+                        let alternative = self.check_block(alternative, true);
+                        self.unify(&ty, &alternative, expression);
+                        ty
+                    }
+                    // Without an `else` the value may be null.
+                    None => ty,
+                }
+            }
+            ast::Expression::Match { value, arms } => {
+                let value = self.infer(value);
+                let ty = self.substitution.fresh();
+                for arm in arms {
+                    let body = self.check_match_arm(arm, &value);
+                    self.at(arm.body.position(), |checker| {
+                        checker.unify(&ty, &body, &arm.body)
+                    });
+                }
+                ty
+            }
+        }
+    }
+
+    fn infer_operator(
+        &mut self,
+        expression: &ast::Expression,
+        operator: &ast::Operator,
+        left: &Type,
+        right: &Type,
+    ) -> Type {
+        match operator {
+            ast::Operator::Plus => {
+                self.unify(left, right, expression);
+                match self.substitution.resolve(left) {
+                    ty @ (Type::String | Type::Bytes | Type::Array(_) | Type::Var(_)) => ty,
+                    _ => {
+                        self.unify(&Type::Int, left, expression);
+                        Type::Int
+                    }
+                }
+            }
+            ast::Operator::Lt | ast::Operator::Gt | ast::Operator::LtEq | ast::Operator::GtEq => {
+                self.unify(left, right, expression);
+                Type::Bool
+            }
+            ast::Operator::Eq | ast::Operator::Neq | ast::Operator::In => Type::Bool,
+            ast::Operator::And | ast::Operator::Or => {
+                self.unify(&Type::Bool, left, expression);
+                self.unify(&Type::Bool, right, expression);
+                Type::Bool
+            }
+            ast::Operator::Coalesce => {
+                self.unify(left, right, expression);
+                left.clone()
+            }
+            _ => {
+                self.unify(&Type::Int, left, expression);
+                self.unify(&Type::Int, right, expression);
+                Type::Int
+            }
+        }
+    }
+
+    /// Infers the argument types of a call, or `None` if an argument is
+    /// spread and the number of arguments is unknown.
+    fn infer_arguments(&mut self, arguments: &[ast::Expression]) -> Option<Vec<Type>> {
+        let types: Vec<Type> = arguments.iter().map(|a| self.infer(a)).collect();
+        match arguments
+            .iter()
+            .any(|a| matches!(a, ast::Expression::Spread(_)))
+        {
+            true => None,
+            false => Some(types),
+        }
+    }
+
+    fn infer_call(
+        &mut self,
+        expression: &ast::Expression,
+        function: &ast::Expression,
+        arguments: Option<Vec<Type>>,
+    ) -> Type {
+        let function = self.infer(function);
+        let arguments = match arguments {
+            Some(arguments) => arguments,
+            None => return self.substitution.fresh(),
+        };
+
+        let expected = match self.substitution.resolve(&function) {
+            Type::Function(parameters, _) if parameters.len() != arguments.len() => {
+                parameters.len().to_string()
+            }
+            Type::Optional {
+                parameters,
+                required,
+                rest,
+                return_type,
+            } => {
+                let found = arguments.len();
+                if found >= required && (rest.is_some() || found <= parameters.len()) {
+                    for (i, argument) in arguments.into_iter().enumerate() {
+                        match parameters.get(i) {
+                            Some(parameter) => self.unify(parameter, &argument, expression),
+                            None => {
+                                let rest = rest.as_deref().expect("argument past the rest");
+                                self.unify(rest, &Type::Array(Box::new(argument)), expression)
+                            }
+                        }
+                    }
+                    return *return_type;
+                }
+                match rest {
+                    Some(_) => format!("at least {}", required),
+                    None => format!("{} to {}", required, parameters.len()),
+                }
+            }
+            _ => {
+                let return_type = self.substitution.fresh();
+                let called = Type::Function(arguments, Box::new(return_type.clone()));
+                self.unify(&function, &called, expression);
+                return return_type;
+            }
+        };
+        self.errors.push(TypeError::Arity {
+            context: expression.to_string(),
+            position: self.position,
+            expected,
+            found: arguments.len(),
+        });
+        self.substitution.fresh()
+    }
+
+    /// Infers the type of a function. Functions with default or rest
+    /// parameters get a [`Type::Optional`], which records how many arguments
+    /// they accept.
+    fn infer_function(
+        &mut self,
+        parameters: &[ast::Parameter],
+        return_type: &Option<ast::TypeAnnotation>,
+        context: &str,
+        body: impl FnOnce(&mut Checker) -> Type,
+    ) -> Type {
+        let return_type = match return_type {
+            Some(annotation) => self.annotation(annotation),
+            None => self.substitution.fresh(),
+        };
+
+        let parameter_types = self.in_scope(|checker| {
+            let mut types = vec![];
+            for parameter in parameters {
+                let ty = match &parameter.annotation {
+                    Some(annotation) => checker.annotation(annotation),
+                    None if parameter.rest => Type::Array(Box::new(checker.substitution.fresh())),
+                    None => checker.substitution.fresh(),
+                };
+                if let Some(default) = &parameter.default {
+                    let found = checker.infer(default);
+                    checker.unify(&ty, &found, default);
+                }
+                checker.bind(&parameter.name, Scheme::monomorphic(ty.clone()));
+                types.push(ty);
+            }
+
+            checker.return_types.push(return_type.clone());
+            let found = body(checker);
+            checker.return_types.pop();
+            checker.unify(&return_type, &found, &context);
+            types
+        });
+
+        if !parameters.iter().any(|p| p.default.is_some() || p.rest) {
+            return Type::Function(parameter_types, Box::new(return_type));
+        }
+        let mut parameter_types = parameter_types;
+        let rest = match parameters.last() {
+            Some(parameter) if parameter.rest => parameter_types.pop().map(Box::new),
+            _ => None,
+        };
+        Type::Optional {
+            required: parameters
+                .iter()
+                .take_while(|p| p.default.is_none() && !p.rest)
+                .count(),
+            parameters: parameter_types,
+            rest,
+            return_type: Box::new(return_type),
+        }
+    }
+}
+
+fn hoisted(statement: &ast::Statement) -> &ast::Statement {
+    match statement {
+        ast::Statement::Export(declaration) => declaration,
+        statement => statement,
+    }
+}
+
+/// Groups function declarations into sets that refer to each other, in the
+/// order they are declared.
+fn recursive_groups<'a>(declarations: &[&'a ast::Statement]) -> Vec<Vec<&'a ast::Statement>> {
+    let edges: Vec<Vec<usize>> = declarations
+        .iter()
+        .map(|declaration| {
+            let mut names = vec![];
+            statement_references(declaration, &mut names);
+            (0..declarations.len())
+                .filter(|i| names.contains(&declared_name(declarations[*i])))
+                .collect()
+        })
+        .collect();
+
+    // Which declarations each one reaches, directly or not.
+    let reaches: Vec<Vec<bool>> = (0..declarations.len())
+        .map(|start| {
+            let mut reached = vec![false; declarations.len()];
+            let mut pending = vec![start];
+            while let Some(i) = pending.pop() {
+                for &next in &edges[i] {
+                    if !reached[next] {
+                        reached[next] = true;
+                        pending.push(next);
+                    }
+                }
+            }
+            reached
+        })
+        .collect();
+
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for (i, reached) in reaches.iter().enumerate() {
+        match groups
+            .iter_mut()
+            .find(|group| reached[group[0]] && reaches[group[0]][i])
+        {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|group| group.into_iter().map(|i| declarations[i]).collect())
+        .collect()
+}
+
+fn declared_name(declaration: &ast::Statement) -> &str {
+    match declaration {
+        ast::Statement::FnDecl { name, .. } => &name.0,
+        _ => "",
+    }
+}
+
+/// Adds every name used in `statement` to `names`. Shadowing is ignored, so
+/// this may report more than the statement really refers to.
+fn statement_references<'a>(statement: &'a ast::Statement, names: &mut Vec<&'a str>) {
+    let block = |block: &'a ast::BlockStatement, names: &mut Vec<&'a str>| {
+        for statement in &block.statements {
+            statement_references(statement, names);
+        }
+    };
+    match statement {
+        ast::Statement::Let(pattern, _, value) | ast::Statement::Const(pattern, _, value) => {
+            pattern_references(pattern, names);
+            expression_references(value, names);
+        }
+        ast::Statement::Return(value, _)
+        | ast::Statement::Expr(value)
+        | ast::Statement::Throw(value, _) => expression_references(value, names),
+        ast::Statement::While { condition, body } => {
+            expression_references(condition, names);
+            block(body, names);
+        }
+        ast::Statement::For { iterable, body, .. } => {
+            expression_references(iterable, names);
+            block(body, names);
+        }
+        ast::Statement::FnDecl {
+            parameters, body, ..
+        } => {
+            parameter_references(parameters, names);
+            block(body, names);
+        }
+        ast::Statement::Export(declaration) => statement_references(declaration, names),
+        ast::Statement::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            block(body, names);
+            if let Some(catch) = catch {
+                block(&catch.body, names);
+            }
+            if let Some(finally) = finally {
+                block(finally, names);
+            }
+        }
+        ast::Statement::Break(_) | ast::Statement::Continue(_) | ast::Statement::Import { .. } => {}
+    }
+}
+
+fn expression_references<'a>(expression: &'a ast::Expression, names: &mut Vec<&'a str>) {
+    match expression {
+        ast::Expression::Ident(identifier) => names.push(&identifier.0),
+        ast::Expression::IntegerLiteral { .. }
+        | ast::Expression::BooleanLiteral(_)
+        | ast::Expression::NullLiteral
+        | ast::Expression::StringLiteral { .. }
+        | ast::Expression::CharLiteral(_)
+        | ast::Expression::ByteStringLiteral(_) => {}
+        ast::Expression::Template(segments) => {
+            for segment in segments {
+                if let ast::TemplateSegment::Interpolation { expression, .. } = segment {
+                    expression_references(expression, names);
+                }
+            }
+        }
+        ast::Expression::Prefix { right: value, .. }
+        | ast::Expression::Member { object: value, .. }
+        | ast::Expression::Spread(value)
+        | ast::Expression::Propagate(value) => expression_references(value, names),
+        ast::Expression::Infix { left, right, .. }
+        | ast::Expression::Assign {
+            target: left,
+            value: right,
+            ..
+        }
+        | ast::Expression::Index {
+            left, index: right, ..
+        }
+        | ast::Expression::Pipe {
+            value: left,
+            function: right,
+        }
+        | ast::Expression::Range {
+            start: left,
+            end: right,
+            ..
+        } => {
+            expression_references(left, names);
+            expression_references(right, names);
+        }
+        ast::Expression::Function {
+            parameters, body, ..
+        } => {
+            parameter_references(parameters, names);
+            for statement in &body.statements {
+                statement_references(statement, names);
+            }
+        }
+        ast::Expression::Lambda {
+            parameters, body, ..
+        } => {
+            parameter_references(parameters, names);
+            expression_references(body, names);
+        }
+        ast::Expression::Call {
+            function,
+            arguments,
+        } => {
+            expression_references(function, names);
+            for argument in arguments {
+                expression_references(argument, names);
+            }
+        }
+        ast::Expression::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } => {
+            expression_references(condition, names);
+            for block in std::iter::once(consequence).chain(alternative) {
+                for statement in &block.statements {
+                    statement_references(statement, names);
+                }
+            }
+        }
+        ast::Expression::Match { value, arms } => {
+            expression_references(value, names);
+            for arm in arms {
+                pattern_references(&arm.pattern, names);
+                if let Some(guard) = &arm.guard {
+                    expression_references(guard, names);
+                }
+                expression_references(&arm.body, names);
+            }
+        }
+    }
+}
+
+fn parameter_references<'a>(parameters: &'a [ast::Parameter], names: &mut Vec<&'a str>) {
+    for default in parameters.iter().filter_map(|p| p.default.as_ref()) {
+        expression_references(default, names);
+    }
+}
+
+/// Only default values in a pattern can refer to names.
+fn pattern_references<'a>(pattern: &'a ast::Pattern, names: &mut Vec<&'a str>) {
+    match pattern {
+        ast::Pattern::Default { pattern, default } => {
+            pattern_references(pattern, names);
+            expression_references(default, names);
+        }
+        ast::Pattern::Array(elements) => {
+            for element in elements {
+                pattern_references(element, names);
+            }
+        }
+        ast::Pattern::Hash(fields) => {
+            for (_, pattern) in fields {
+                pattern_references(pattern, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn keyword(statement: &ast::Statement) -> &'static str {
+    match statement {
+        ast::Statement::Const(..) => "const",
+        _ => "let",
+    }
+}
+
+fn join(parameters: &[ast::Parameter]) -> String {
+    parameters
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn check_source(input: &str) -> Vec<String> {
+        match parse(input) {
+            Ok(program) => check(&program).iter().map(|e| e.to_string()).collect(),
+            Err(errors) => panic!("parser errors:\n{}", errors),
+        }
+    }
+
+    #[test]
+    fn test_well_typed_programs() {
+        let inputs = vec![
+            "let x: int = 5; let y = x * 2 + 1;",
+            "let id = fn(x) { x }; id(1); id(true);",
+            "fn is_even(n) { if (n == 0) { true } else { is_odd(n - 1) } }\nfn is_odd(n) { if (n == 0) { false } else { is_even(n - 1) } }\nis_even(10) && true",
+            "let greet = fn(name: string) -> string { \"Hello, \" + name };",
+            "let total = 0; for (i in 0..10) { total += i; }",
+            "if (a) { x = 1 } else { x = \"one\" }",
+            "let port = cfg[\"port\"] ?? 8080; port + 1;",
+            "let s = null; s + 1;",
+            "let f = fn(a, b = 1) { a + b }; f(1); f(1, 2); f(...args);",
+            "let g = fn(a, ...xs) { a }; g(1); g(1, 2, 3); 1 |> g",
+            "match (n) { 0 => \"zero\", 1..=9 => \"digit\", _ => \"many\" }",
+            "let double = |x| x * 2; xs |> map(double)",
+            "fn first(xs) { match (xs) { [head, ..rest] => head, [] => null } }",
+            "let g: fn(int) -> bool = |n| n > 0;",
+            "puts(id(1)); puts(id(true)); fn id(x) { x }",
+            "fn a() { id(1); id(true) } fn id(x) { x }",
+        ];
+
+        for input in inputs {
+            assert_eq!(check_source(input), Vec::<String>::new(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let inputs = vec![
+            (
+                "1 + true",
+                "mismatched types in (1 + true) at 1:3: expected int, found bool",
+            ),
+            (
+                "let x: int = \"five\";",
+                "mismatched types in let x at 1:5: expected int, found string",
+            ),
+            (
+                "fn f(a: string) -> bool { a }",
+                "mismatched types in fn f at 1:4: expected bool, found string",
+            ),
+            (
+                "let x = 1;\nx = \"s\";",
+                "mismatched types in (x = \"s\") at 2:1: expected int, found string",
+            ),
+            (
+                "fn even(n) { if (n == 0) { true } else { odd(n - 1) } }\nfn odd(n) { even(n) }\neven(\"x\")",
+                "mismatched types in even(\"x\") at 3:1: expected fn(int) -> bool, found fn(string) -> 't8",
+            ),
+            (
+                "let f = fn(a, b) { a };\nf(1)",
+                "wrong number of arguments in f(1) at 2:1: expected 2, found 1",
+            ),
+            (
+                "let f = fn(a, b = 1) { a };\nf(1, 2, 3)",
+                "wrong number of arguments in f(1, 2, 3) at 2:1: expected 1 to 2, found 3",
+            ),
+            (
+                "let g = fn(a, b, ...xs) { a };\ng(1)",
+                "wrong number of arguments in g(1) at 2:1: expected at least 2, found 1",
+            ),
+            (
+                "let f = fn(a, b = 1) { a + b };\nf(1, \"s\")",
+                "mismatched types in f(1, \"s\") at 2:1: expected int, found string",
+            ),
+            (
+                "let g = fn(...xs: [int]) { xs };\ng(1, true)",
+                "mismatched types in g(1, true) at 2:1: expected [int], found [bool]",
+            ),
+            ("let x: foo = 1;", "unknown type foo at 1:8"),
+            (
+                "match (1) { true => 1, _ => 2 }",
+                "mismatched types in true at 1:1: expected int, found bool",
+            ),
+            (
+                "let v = if (c) { 1 } else { \"one\" };",
+                "mismatched types in if (c) {1} else {\"one\"} at 1:9: expected int, found string",
+            ),
+            (
+                "fn f(x) { return 1; \"a\" }",
+                "mismatched types in fn f at 1:4: expected int, found string",
+            ),
+            (
+                "let xs: [int] = ys; xs[0] && true",
+                "mismatched types in ((xs[0]) && true) at 1:27: expected bool, found int",
+            ),
+            (
+                "fn f(x) { f }",
+                "infinite type in fn f at 1:4: 't0 refers to itself through fn('t2) -> 't0",
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            assert_eq!(check_source(input), vec![expected], "{}", input);
+        }
+
+        assert_eq!(
+            check_source("let f = fn(a, b = 1) { a }; f(); f(1, 2, 3)"),
+            vec![
+                "wrong number of arguments in f() at 1:29: expected 1 to 2, found 0",
+                "wrong number of arguments in f(1, 2, 3) at 1:34: expected 1 to 2, found 3",
+            ]
+        );
+    }
+    #[test]
+    fn test_identifier_types() {
+        let input = "let n = 5;\nlet f = fn(x) { x + n };\nlet s = \"a\";\nf(n);\nlet g = fn(a, b = true, ...c) { a + n };";
+        let inference = match parse(input) {
+            Ok(program) => infer(&program),
+            Err(errors) => panic!("parser errors:\n{}", errors),
+        };
+        assert!(inference.errors.is_empty());
+
+        let tests = vec![
+            ((1, 5), "int"),
+            ((2, 5), "fn(int) -> int"),
+            ((2, 21), "int"),
+            ((3, 5), "string"),
+            ((4, 1), "fn(int) -> int"),
+            ((5, 5), "fn(int, bool?, ...['t6]) -> int"),
+        ];
+        for ((line, column), expected) in tests {
+            let ty = &inference.types[&Position { line, column }];
+            assert_eq!(ty.to_string(), expected, "{}:{}", line, column);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    Int,
+    Bool,
+    String,
+    Char,
+    Bytes,
+    Range,
+    Null,
+    Array(Box<Type>),
+    Function(Vec<Type>, Box<Type>),
+    /// A function with default or rest parameters. It takes at least
+    /// `required` arguments and, unless it has a `rest` array, at most
+    /// `parameters.len()`.
+    Optional {
+        parameters: Vec<Type>,
+        required: usize,
+        rest: Option<Box<Type>>,
+        return_type: Box<Type>,
+    },
+    /// A type still being inferred.
+    Var(usize),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Char => write!(f, "char"),
+            Type::Bytes => write!(f, "bytes"),
+            Type::Range => write!(f, "range"),
+            Type::Null => write!(f, "null"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Function(parameters, return_type) => write!(
+                f,
+                "fn({}) -> {}",
+                parameters
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                return_type
+            ),
+            Type::Optional {
+                parameters,
+                required,
+                rest,
+                return_type,
+            } => {
+                let mut shown: Vec<String> = parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| match i < *required {
+                        true => p.to_string(),
+                        false => format!("{}?", p),
+                    })
+                    .collect();
+                if let Some(rest) = rest {
+                    shown.push(format!("...{}", rest));
+                }
+                write!(f, "fn({}) -> {}", shown.join(", "), return_type)
+            }
+            Type::Var(var) => write!(f, "'t{}", var),
+        }
+    }
+}
+
+impl Type {
+    /// Rebuilds `self` with `f` applied to each type directly inside it.
+    fn map(&self, mut f: impl FnMut(&Type) -> Type) -> Type {
+        match self {
+            Type::Array(element) => Type::Array(Box::new(f(element))),
+            Type::Function(parameters, return_type) => Type::Function(
+                parameters.iter().map(&mut f).collect(),
+                Box::new(f(return_type)),
+            ),
+            Type::Optional {
+                parameters,
+                required,
+                rest,
+                return_type,
+            } => Type::Optional {
+                parameters: parameters.iter().map(&mut f).collect(),
+                required: *required,
+                rest: rest.as_ref().map(|r| Box::new(f(r))),
+                return_type: Box::new(f(return_type)),
+            },
+            ty => ty.clone(),
+        }
+    }
+
+    fn free_vars(&self, vars: &mut Vec<usize>) {
+        match self {
+            Type::Var(var) if !vars.contains(var) => vars.push(*var),
+            Type::Array(element) => element.free_vars(vars),
+            Type::Function(parameters, return_type) => {
+                parameters.iter().for_each(|p| p.free_vars(vars));
+                return_type.free_vars(vars);
+            }
+            Type::Optional {
+                parameters,
+                rest,
+                return_type,
+                ..
+            } => {
+                parameters.iter().for_each(|p| p.free_vars(vars));
+                rest.iter().for_each(|r| r.free_vars(vars));
+                return_type.free_vars(vars);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A type generalized over `vars`, such as the type of `fn(x) { x }`, which
+/// can be used at a different type each time it is referenced.
+#[derive(Debug, Clone)]
+pub struct Scheme {
+    pub vars: Vec<usize>,
+    pub ty: Type,
+}
+
+impl Scheme {
+    pub fn monomorphic(ty: Type) -> Scheme {
+        Scheme { vars: vec![], ty }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UnifyError {
+    Mismatch,
+    InfiniteType,
+}
+
+/// The solutions found so far for each type variable.
+#[derive(Default)]
+pub struct Substitution {
+    bindings: Vec<Option<Type>>,
+}
+
+impl Substitution {
+    pub fn fresh(&mut self) -> Type {
+        self.bindings.push(None);
+        Type::Var(self.bindings.len() - 1)
+    }
+
+    /// Replaces every solved variable in `ty`, recursively.
+    pub fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(var) => match &self.bindings[*var] {
+                Some(bound) => self.resolve(bound),
+                None => ty.clone(),
+            },
+            ty => ty.map(|inner| self.resolve(inner)),
+        }
+    }
+
+    pub fn unify(&mut self, expected: &Type, found: &Type) -> Result<(), UnifyError> {
+        match (self.resolve(expected), self.resolve(found)) {
+            (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+            (Type::Var(var), ty) | (ty, Type::Var(var)) => {
+                if self.occurs(var, &ty) {
+                    return Err(UnifyError::InfiniteType);
+                }
+                self.bindings[var] = Some(ty);
+                Ok(())
+            }
+            (Type::Array(a), Type::Array(b)) => self.unify(&a, &b),
+            (Type::Function(a_params, a_return), Type::Function(b_params, b_return)) => {
+                if a_params.len() != b_params.len() {
+                    return Err(UnifyError::Mismatch);
+                }
+                for (a, b) in a_params.iter().zip(&b_params) {
+                    self.unify(a, b)?;
+                }
+                self.unify(&a_return, &b_return)
+            }
+            (
+                Type::Optional {
+                    parameters,
+                    required,
+                    rest,
+                    return_type,
+                },
+                Type::Function(arguments, found_return),
+            )
+            | (
+                Type::Function(arguments, found_return),
+                Type::Optional {
+                    parameters,
+                    required,
+                    rest,
+                    return_type,
+                },
+            ) => {
+                if !self.accepts(&parameters, required, &rest, &arguments)? {
+                    return Err(UnifyError::Mismatch);
+                }
+                self.unify(&return_type, &found_return)
+            }
+            (
+                Type::Optional {
+                    parameters: a_params,
+                    required: a_required,
+                    rest: a_rest,
+                    return_type: a_return,
+                },
+                Type::Optional {
+                    parameters: b_params,
+                    required: b_required,
+                    rest: b_rest,
+                    return_type: b_return,
+                },
+            ) => {
+                if a_params.len() != b_params.len()
+                    || a_required != b_required
+                    || a_rest.is_some() != b_rest.is_some()
+                {
+                    return Err(UnifyError::Mismatch);
+                }
+                for (a, b) in a_params.iter().zip(&b_params) {
+                    self.unify(a, b)?;
+                }
+                if let (Some(a), Some(b)) = (a_rest, b_rest) {
+                    self.unify(&a, &b)?;
+                }
+                self.unify(&a_return, &b_return)
+            }
+            (a, b) if a == b => Ok(()),
+            _ => Err(UnifyError::Mismatch),
+        }
+    }
+
+    /// Unifies each of `arguments` with the parameter it is passed to. Returns
+    /// `false`, without unifying anything, if there are too few or too many.
+    fn accepts(
+        &mut self,
+        parameters: &[Type],
+        required: usize,
+        rest: &Option<Box<Type>>,
+        arguments: &[Type],
+    ) -> Result<bool, UnifyError> {
+        if arguments.len() < required || (rest.is_none() && arguments.len() > parameters.len()) {
+            return Ok(false);
+        }
+        for (i, argument) in arguments.iter().enumerate() {
+            match (parameters.get(i), rest) {
+                (Some(parameter), _) => self.unify(parameter, argument)?,
+                (None, Some(rest)) => self.unify(rest, &Type::Array(Box::new(argument.clone())))?,
+                (None, None) => unreachable!(),
+            }
+        }
+        Ok(true)
+    }
+
+    fn occurs(&self, var: usize, ty: &Type) -> bool {
+        let mut vars = vec![];
+        self.resolve(ty).free_vars(&mut vars);
+        vars.contains(&var)
+    }
+
+    /// Generalizes the variables of `ty` that are not also free in
+    /// `environment`.
+    pub fn generalize<'a>(
+        &self,
+        ty: &Type,
+        environment: impl Iterator<Item = &'a Scheme>,
+    ) -> Scheme {
+        let mut bound = vec![];
+        for scheme in environment {
+            let mut vars = vec![];
+            self.resolve(&scheme.ty).free_vars(&mut vars);
+            bound.extend(vars.into_iter().filter(|v| !scheme.vars.contains(v)));
+        }
+
+        let ty = self.resolve(ty);
+        let mut vars = vec![];
+        ty.free_vars(&mut vars);
+        vars.retain(|v| !bound.contains(v));
+        Scheme { vars, ty }
+    }
+
+    pub fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        let fresh: HashMap<usize, Type> =
+            scheme.vars.iter().map(|var| (*var, self.fresh())).collect();
+        replace(&scheme.ty, &fresh)
+    }
+}
+
+fn replace(ty: &Type, vars: &HashMap<usize, Type>) -> Type {
+    match ty {
+        Type::Var(var) => vars.get(var).cloned().unwrap_or_else(|| ty.clone()),
+        ty => ty.map(|inner| replace(inner, vars)),
+    }
+}